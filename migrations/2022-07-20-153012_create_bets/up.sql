@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE bets (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL,
+    event_id INT NOT NULL,
+    stake BIGINT NOT NULL CHECK (stake > 0),
+    odds INT NOT NULL,
+    placed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE ledger_entries;
+DROP TABLE accounts;
+DROP TYPE entry_kind;
+DROP TYPE account_kind;
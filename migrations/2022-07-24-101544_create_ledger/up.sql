@@ -0,0 +1,32 @@
+-- Your SQL goes here
+CREATE TYPE account_kind AS ENUM ('wallet', 'house', 'escrow', 'external');
+CREATE TYPE entry_kind AS ENUM ('deposit', 'stake', 'payout', 'refund', 'adjustment');
+
+CREATE TABLE accounts (
+    id SERIAL PRIMARY KEY,
+    kind ACCOUNT_KIND NOT NULL,
+    user_id INT NULL UNIQUE,
+    CHECK ((kind = 'wallet') = (user_id IS NOT NULL)),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- There is exactly one house, escrow and external account
+CREATE UNIQUE INDEX accounts_system_kind ON accounts (kind) WHERE user_id IS NULL;
+INSERT INTO accounts (kind) VALUES ('house'), ('escrow'), ('external');
+
+-- Every entry moves `amount` cents out of one account and into another, so the sum of all
+-- balances is always zero.
+CREATE TABLE ledger_entries (
+    id SERIAL PRIMARY KEY,
+    kind ENTRY_KIND NOT NULL,
+    from_account_id INT NOT NULL,
+    to_account_id INT NOT NULL,
+    amount BIGINT NOT NULL CHECK (amount > 0),
+    bet_id INT NULL,
+    memo VARCHAR(255) NOT NULL DEFAULT '',
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CHECK (from_account_id <> to_account_id),
+    FOREIGN KEY (from_account_id) REFERENCES accounts(id),
+    FOREIGN KEY (to_account_id) REFERENCES accounts(id),
+    FOREIGN KEY (bet_id) REFERENCES bets(id) ON DELETE SET NULL
+);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE game_results DROP CONSTRAINT game_results_game_id_key;
+ALTER TABLE bets DROP COLUMN settled_at, DROP COLUMN status;
+DROP TYPE bet_status;
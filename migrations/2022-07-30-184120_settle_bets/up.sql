@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE TYPE bet_status AS ENUM ('open', 'won', 'lost', 'push', 'void');
+
+ALTER TABLE bets
+    ADD COLUMN status BET_STATUS NOT NULL DEFAULT 'open',
+    ADD COLUMN settled_at TIMESTAMP NULL;
+
+-- A game has at most one final score; corrections update the existing row
+ALTER TABLE game_results ADD CONSTRAINT game_results_game_id_key UNIQUE (game_id);
@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE events
+    DROP COLUMN line,
+    DROP COLUMN total,
+    DROP COLUMN team,
+    DROP COLUMN market_kind;
+DROP TYPE total_side;
+DROP TYPE team_side;
+DROP TYPE market_kind;
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TYPE market_kind AS ENUM ('moneyline', 'spread', 'total', 'team_total');
+CREATE TYPE team_side AS ENUM ('home', 'away');
+CREATE TYPE total_side AS ENUM ('over', 'under');
+
+-- Legacy free-text events keep a NULL market and are never graded automatically. Spread and total
+-- lines are stored in half-points, so -5.5 is stored as -11.
+ALTER TABLE events
+    ADD COLUMN market_kind MARKET_KIND NULL,
+    ADD COLUMN team TEAM_SIDE NULL,
+    ADD COLUMN total TOTAL_SIDE NULL,
+    ADD COLUMN line INT NULL;
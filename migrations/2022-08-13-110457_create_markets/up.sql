@@ -0,0 +1,34 @@
+-- Your SQL goes here
+CREATE TABLE markets (
+    id SERIAL PRIMARY KEY,
+    game_id INT NOT NULL,
+    kind MARKET_KIND NOT NULL,
+    team TEAM_SIDE NULL,
+    line INT NULL,
+    suspended BOOLEAN NOT NULL DEFAULT FALSE,
+    FOREIGN KEY (game_id) REFERENCES games(id) ON DELETE CASCADE
+);
+
+ALTER TABLE events
+    ADD COLUMN market_id INT NULL,
+    ADD FOREIGN KEY (market_id) REFERENCES markets(id) ON DELETE CASCADE;
+
+-- Group existing typed events into markets. Spread lines are stored from the home team's point of
+-- view, so an away selection at +5.5 belongs to the market with line -5.5.
+INSERT INTO markets (game_id, kind, team, line)
+SELECT DISTINCT
+    game_id,
+    market_kind,
+    CASE WHEN market_kind = 'team_total' THEN team END,
+    CASE WHEN market_kind = 'spread' AND team = 'away' THEN -line ELSE line END
+FROM events
+WHERE market_kind IS NOT NULL AND game_id IS NOT NULL;
+
+UPDATE events e
+SET market_id = m.id
+FROM markets m
+WHERE e.game_id = m.game_id
+    AND e.market_kind = m.kind
+    AND m.team IS NOT DISTINCT FROM (CASE WHEN e.market_kind = 'team_total' THEN e.team END)
+    AND m.line IS NOT DISTINCT FROM
+        (CASE WHEN e.market_kind = 'spread' AND e.team = 'away' THEN -e.line ELSE e.line END);
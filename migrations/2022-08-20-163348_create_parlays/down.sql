@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE parlay_legs;
+DELETE FROM bets WHERE event_id IS NULL;
+ALTER TABLE bets ALTER COLUMN event_id SET NOT NULL;
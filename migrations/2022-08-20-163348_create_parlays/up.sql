@@ -0,0 +1,13 @@
+-- Your SQL goes here
+-- A parlay is a bet without an event of its own; its selections are stored as legs
+ALTER TABLE bets ALTER COLUMN event_id DROP NOT NULL;
+
+CREATE TABLE parlay_legs (
+    id SERIAL PRIMARY KEY,
+    bet_id INT NOT NULL,
+    event_id INT NOT NULL,
+    odds INT NOT NULL,
+    status BET_STATUS NOT NULL DEFAULT 'open',
+    UNIQUE (bet_id, event_id),
+    FOREIGN KEY (bet_id) REFERENCES bets(id) ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE parlay_legs DROP COLUMN event_timestamp;
+ALTER TABLE bets DROP COLUMN event_timestamp;
@@ -0,0 +1,22 @@
+-- Your SQL goes here
+-- Bets and parlay legs point at the exact version of the event whose price they took
+ALTER TABLE bets ADD COLUMN event_timestamp TIMESTAMP NULL;
+ALTER TABLE parlay_legs ADD COLUMN event_timestamp TIMESTAMP NULL;
+
+-- Existing bets took the newest version that was live when they were placed
+UPDATE bets b
+SET event_timestamp = (
+    SELECT MAX(e.timestamp) FROM events e WHERE e.id = b.event_id AND e.timestamp <= b.placed_at
+)
+WHERE b.event_id IS NOT NULL;
+
+UPDATE parlay_legs l
+SET event_timestamp = (
+    SELECT MAX(e.timestamp) FROM events e, bets b
+    WHERE e.id = l.event_id AND b.id = l.bet_id AND e.timestamp <= b.placed_at
+);
+
+ALTER TABLE bets
+    ADD FOREIGN KEY (event_id, event_timestamp) REFERENCES events(id, timestamp);
+ALTER TABLE parlay_legs
+    ADD FOREIGN KEY (event_id, event_timestamp) REFERENCES events(id, timestamp);
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+-- The version of each event that was live when its game started
+CREATE TABLE closing_lines (
+    id SERIAL PRIMARY KEY,
+    event_id INT NOT NULL UNIQUE,
+    event_timestamp TIMESTAMP NOT NULL,
+    odds INT NOT NULL,
+    captured_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (event_id, event_timestamp) REFERENCES events(id, timestamp) ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE price_moves;
+DROP TABLE pricing_policies;
@@ -0,0 +1,32 @@
+-- Your SQL goes here
+-- How a market's prices move on their own as bets come in. `threshold` is in cents, `step` in
+-- cents of American odds, and prices never go shorter than `min_odds` or longer than `max_odds`.
+CREATE TABLE pricing_policies (
+    id SERIAL PRIMARY KEY,
+    market_id INT NOT NULL UNIQUE,
+    threshold BIGINT NOT NULL CHECK (threshold > 0),
+    step INT NOT NULL CHECK (step > 0),
+    min_odds INT NOT NULL,
+    max_odds INT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    FOREIGN KEY (market_id) REFERENCES markets(id) ON DELETE CASCADE,
+    -- `min_odds` is no longer than `max_odds`, compared across even money
+    CHECK (
+        CASE WHEN min_odds > 0 THEN min_odds - 100 ELSE min_odds + 100 END
+            <= CASE WHEN max_odds > 0 THEN max_odds - 100 ELSE max_odds + 100 END
+    )
+);
+
+-- Every automatic price move, with the version it wrote and why
+CREATE TABLE price_moves (
+    id SERIAL PRIMARY KEY,
+    market_id INT NOT NULL,
+    event_id INT NOT NULL,
+    event_timestamp TIMESTAMP NOT NULL,
+    old_odds INT NOT NULL,
+    new_odds INT NOT NULL,
+    reason VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (market_id) REFERENCES markets(id) ON DELETE CASCADE,
+    FOREIGN KEY (event_id, event_timestamp) REFERENCES events(id, timestamp) ON DELETE CASCADE
+);
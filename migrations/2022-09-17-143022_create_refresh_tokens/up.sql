@@ -0,0 +1,12 @@
+-- Your SQL goes here
+-- Long-lived tokens that API clients exchange for new access tokens. Only a hash of each token's
+-- secret is stored, and a token can be revoked before it expires.
+CREATE TABLE refresh_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL,
+    token_hash VARCHAR(255) NOT NULL,
+    issued_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
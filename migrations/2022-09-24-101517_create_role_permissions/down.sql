@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE role_permissions;
+DROP TYPE permission;
+
+ALTER TYPE role RENAME TO role_new;
+CREATE TYPE role AS ENUM ('bookie', 'punter');
+ALTER TABLE users ALTER COLUMN role DROP DEFAULT;
+ALTER TABLE users ALTER COLUMN role TYPE role
+    USING (CASE role::text WHEN 'punter' THEN 'punter' ELSE 'bookie' END)::role;
+ALTER TABLE users ALTER COLUMN role SET DEFAULT 'punter';
+DROP TYPE role_new;
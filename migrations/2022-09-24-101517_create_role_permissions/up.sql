@@ -0,0 +1,45 @@
+-- Your SQL goes here
+-- Replace the catch-all bookie role with admins, traders, support agents and risk officers.
+-- Existing bookies become admins so they keep every permission they had.
+ALTER TYPE role RENAME TO role_old;
+CREATE TYPE role AS ENUM ('admin', 'trader', 'support', 'risk', 'punter');
+ALTER TABLE users ALTER COLUMN role DROP DEFAULT;
+ALTER TABLE users ALTER COLUMN role TYPE role
+    USING (CASE role::text WHEN 'bookie' THEN 'admin' ELSE role::text END)::role;
+ALTER TABLE users ALTER COLUMN role SET DEFAULT 'punter';
+DROP TYPE role_old;
+
+-- What each role is allowed to do. Handlers check permissions, never roles, so the matrix can
+-- be changed without touching code.
+CREATE TYPE permission AS ENUM (
+    'manage_users',
+    'manage_games',
+    'manage_markets',
+    'set_prices',
+    'view_book',
+    'view_exposure',
+    'view_accounts',
+    'move_money',
+    'place_bets'
+);
+CREATE TABLE role_permissions (
+    role ROLE NOT NULL,
+    permission PERMISSION NOT NULL,
+    PRIMARY KEY (role, permission)
+);
+
+INSERT INTO role_permissions (role, permission)
+SELECT 'admin', unnest(enum_range(NULL::permission));
+
+INSERT INTO role_permissions (role, permission) VALUES
+    ('trader', 'manage_markets'),
+    ('trader', 'set_prices'),
+    ('trader', 'view_book'),
+    ('trader', 'view_exposure'),
+    ('support', 'view_book'),
+    ('support', 'view_accounts'),
+    ('risk', 'manage_markets'),
+    ('risk', 'view_book'),
+    ('risk', 'view_exposure'),
+    ('risk', 'view_accounts'),
+    ('punter', 'place_bets');
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE result_corrections;
+ALTER TABLE game_results DROP CONSTRAINT game_results_score_check;
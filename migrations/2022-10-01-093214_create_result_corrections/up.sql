@@ -0,0 +1,17 @@
+-- Your SQL goes here
+-- Scores are never negative
+ALTER TABLE game_results ADD CONSTRAINT game_results_score_check CHECK (home >= 0 AND away >= 0);
+
+-- Every correction to a final score: what it was, what it became and who changed it
+CREATE TABLE result_corrections (
+    id SERIAL PRIMARY KEY,
+    result_id INT NOT NULL,
+    user_id INT NOT NULL,
+    old_home INT NOT NULL,
+    old_away INT NOT NULL,
+    new_home INT NOT NULL,
+    new_away INT NOT NULL,
+    corrected_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (result_id) REFERENCES game_results(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
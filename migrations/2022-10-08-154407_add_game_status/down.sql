@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE game_transitions;
+ALTER TABLE markets DROP COLUMN voided;
+ALTER TABLE games DROP COLUMN status;
+DROP TYPE game_status;
@@ -0,0 +1,22 @@
+-- Your SQL goes here
+CREATE TYPE game_status AS ENUM('scheduled', 'live', 'final', 'postponed', 'cancelled');
+
+ALTER TABLE games ADD COLUMN status GAME_STATUS NOT NULL DEFAULT 'scheduled';
+UPDATE games SET status = 'final' WHERE id IN (SELECT game_id FROM game_results);
+
+-- Markets suspended because their game was postponed or cancelled, as opposed to by a trader.
+-- They are resumed if a postponed game is rescheduled.
+ALTER TABLE markets ADD COLUMN voided BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Every change of a game's status. `user_id` is empty for changes the system made, such as a
+-- game becoming final when its result is recorded.
+CREATE TABLE game_transitions (
+    id SERIAL PRIMARY KEY,
+    game_id INT NOT NULL,
+    from_status GAME_STATUS NOT NULL,
+    to_status GAME_STATUS NOT NULL,
+    user_id INT,
+    changed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (game_id) REFERENCES games(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE games DROP CONSTRAINT games_league_home_fkey;
+ALTER TABLE games DROP CONSTRAINT games_league_away_fkey;
+DROP TABLE teams;
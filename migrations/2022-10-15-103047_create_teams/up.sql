@@ -0,0 +1,85 @@
+-- Your SQL goes here
+-- Every team a game can be played by. Teams are never deleted, only made inactive, so that old
+-- games keep pointing at them. `aliases` are other names and abbreviations the team goes by.
+CREATE TABLE teams (
+    league LEAGUE NOT NULL,
+    abbreviation VARCHAR(3) NOT NULL,
+    name VARCHAR(63) NOT NULL,
+    aliases TEXT[] NOT NULL DEFAULT '{}',
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    PRIMARY KEY (league, abbreviation)
+);
+
+INSERT INTO teams (league, abbreviation, name, aliases) VALUES
+    ('nfl', 'ARI', 'Arizona Cardinals', ARRAY['Cardinals', 'ARZ']),
+    ('nfl', 'ATL', 'Atlanta Falcons', ARRAY['Falcons']),
+    ('nfl', 'BAL', 'Baltimore Ravens', ARRAY['Ravens']),
+    ('nfl', 'BUF', 'Buffalo Bills', ARRAY['Bills']),
+    ('nfl', 'CAR', 'Carolina Panthers', ARRAY['Panthers']),
+    ('nfl', 'CHI', 'Chicago Bears', ARRAY['Bears']),
+    ('nfl', 'CIN', 'Cincinnati Bengals', ARRAY['Bengals']),
+    ('nfl', 'CLE', 'Cleveland Browns', ARRAY['Browns']),
+    ('nfl', 'DAL', 'Dallas Cowboys', ARRAY['Cowboys']),
+    ('nfl', 'DEN', 'Denver Broncos', ARRAY['Broncos']),
+    ('nfl', 'DET', 'Detroit Lions', ARRAY['Lions']),
+    ('nfl', 'GB', 'Green Bay Packers', ARRAY['Packers', 'GNB']),
+    ('nfl', 'HOU', 'Houston Texans', ARRAY['Texans']),
+    ('nfl', 'IND', 'Indianapolis Colts', ARRAY['Colts']),
+    ('nfl', 'JAX', 'Jacksonville Jaguars', ARRAY['Jaguars', 'JAC']),
+    ('nfl', 'KC', 'Kansas City Chiefs', ARRAY['Chiefs', 'KAN']),
+    ('nfl', 'LAC', 'Los Angeles Chargers', ARRAY['Chargers', 'San Diego Chargers']),
+    ('nfl', 'LAR', 'Los Angeles Rams', ARRAY['Rams', 'LA', 'St. Louis Rams']),
+    ('nfl', 'LV', 'Las Vegas Raiders', ARRAY['Raiders', 'LVR', 'Oakland Raiders']),
+    ('nfl', 'MIA', 'Miami Dolphins', ARRAY['Dolphins']),
+    ('nfl', 'MIN', 'Minnesota Vikings', ARRAY['Vikings']),
+    ('nfl', 'NE', 'New England Patriots', ARRAY['Patriots', 'NWE']),
+    ('nfl', 'NO', 'New Orleans Saints', ARRAY['Saints', 'NOR']),
+    ('nfl', 'NYG', 'New York Giants', ARRAY['Giants']),
+    ('nfl', 'NYJ', 'New York Jets', ARRAY['Jets']),
+    ('nfl', 'PHI', 'Philadelphia Eagles', ARRAY['Eagles']),
+    ('nfl', 'PIT', 'Pittsburgh Steelers', ARRAY['Steelers']),
+    ('nfl', 'SEA', 'Seattle Seahawks', ARRAY['Seahawks']),
+    ('nfl', 'SF', 'San Francisco 49ers', ARRAY['49ers', 'SFO']),
+    ('nfl', 'TB', 'Tampa Bay Buccaneers', ARRAY['Buccaneers', 'TAM']),
+    ('nfl', 'TEN', 'Tennessee Titans', ARRAY['Titans']),
+    ('nfl', 'WAS', 'Washington Commanders', ARRAY['Commanders', 'WSH', 'Washington Football Team']),
+    ('nba', 'ATL', 'Atlanta Hawks', ARRAY['Hawks']),
+    ('nba', 'BOS', 'Boston Celtics', ARRAY['Celtics']),
+    ('nba', 'BKN', 'Brooklyn Nets', ARRAY['Nets', 'BRK']),
+    ('nba', 'CHA', 'Charlotte Hornets', ARRAY['Hornets', 'CHO']),
+    ('nba', 'CHI', 'Chicago Bulls', ARRAY['Bulls']),
+    ('nba', 'CLE', 'Cleveland Cavaliers', ARRAY['Cavaliers']),
+    ('nba', 'DAL', 'Dallas Mavericks', ARRAY['Mavericks']),
+    ('nba', 'DEN', 'Denver Nuggets', ARRAY['Nuggets']),
+    ('nba', 'DET', 'Detroit Pistons', ARRAY['Pistons']),
+    ('nba', 'GSW', 'Golden State Warriors', ARRAY['Warriors', 'GS']),
+    ('nba', 'HOU', 'Houston Rockets', ARRAY['Rockets']),
+    ('nba', 'IND', 'Indiana Pacers', ARRAY['Pacers']),
+    ('nba', 'LAC', 'Los Angeles Clippers', ARRAY['Clippers', 'LA Clippers']),
+    ('nba', 'LAL', 'Los Angeles Lakers', ARRAY['Lakers', 'LA Lakers']),
+    ('nba', 'MEM', 'Memphis Grizzlies', ARRAY['Grizzlies']),
+    ('nba', 'MIA', 'Miami Heat', ARRAY['Heat']),
+    ('nba', 'MIL', 'Milwaukee Bucks', ARRAY['Bucks']),
+    ('nba', 'MIN', 'Minnesota Timberwolves', ARRAY['Timberwolves']),
+    ('nba', 'NOP', 'New Orleans Pelicans', ARRAY['Pelicans', 'NO']),
+    ('nba', 'NYK', 'New York Knicks', ARRAY['Knicks', 'NY']),
+    ('nba', 'OKC', 'Oklahoma City Thunder', ARRAY['Thunder']),
+    ('nba', 'ORL', 'Orlando Magic', ARRAY['Magic']),
+    ('nba', 'PHI', 'Philadelphia 76ers', ARRAY['76ers']),
+    ('nba', 'PHX', 'Phoenix Suns', ARRAY['Suns', 'PHO']),
+    ('nba', 'POR', 'Portland Trail Blazers', ARRAY['Trail Blazers', 'Portland Trailblazers']),
+    ('nba', 'SAC', 'Sacramento Kings', ARRAY['Kings']),
+    ('nba', 'SAS', 'San Antonio Spurs', ARRAY['Spurs', 'SA']),
+    ('nba', 'TOR', 'Toronto Raptors', ARRAY['Raptors']),
+    ('nba', 'UTA', 'Utah Jazz', ARRAY['Jazz', 'UTAH']),
+    ('nba', 'WAS', 'Washington Wizards', ARRAY['Wizards', 'WSH']);
+
+-- Games already played by teams the registry doesn't know keep them, as inactive teams
+INSERT INTO teams (league, abbreviation, name, active)
+SELECT DISTINCT league, team, team, FALSE
+FROM (SELECT league, home AS team FROM games UNION SELECT league, away FROM games) AS played
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE games
+    ADD FOREIGN KEY (league, home) REFERENCES teams (league, abbreviation) ON UPDATE CASCADE,
+    ADD FOREIGN KEY (league, away) REFERENCES teams (league, abbreviation) ON UPDATE CASCADE;
@@ -0,0 +1,18 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE games
+    DROP CONSTRAINT games_league_fkey,
+    DROP CONSTRAINT games_league_home_fkey,
+    DROP CONSTRAINT games_league_away_fkey;
+ALTER TABLE teams DROP CONSTRAINT teams_league_fkey;
+DELETE FROM games WHERE league NOT IN ('NBA', 'NFL');
+DELETE FROM teams WHERE league NOT IN ('NBA', 'NFL');
+
+CREATE TYPE league AS ENUM('nba', 'nfl');
+ALTER TABLE games ALTER COLUMN league TYPE LEAGUE USING lower(league)::league;
+ALTER TABLE teams ALTER COLUMN league TYPE LEAGUE USING lower(league)::league;
+ALTER TABLE games
+    ADD FOREIGN KEY (league, home) REFERENCES teams (league, abbreviation) ON UPDATE CASCADE,
+    ADD FOREIGN KEY (league, away) REFERENCES teams (league, abbreviation) ON UPDATE CASCADE;
+
+DROP TABLE market_templates;
+DROP TABLE leagues;
@@ -0,0 +1,60 @@
+-- Your SQL goes here
+-- Leagues become data instead of an enum, so a new competition is a row rather than a release.
+-- `draws_allowed` decides whether a final score can be level and `periods` is the number of
+-- quarters, halves, periods or innings in a game.
+CREATE TABLE leagues (
+    code VARCHAR(15) PRIMARY KEY,
+    name VARCHAR(63) NOT NULL,
+    sport VARCHAR(31) NOT NULL,
+    draws_allowed BOOLEAN NOT NULL DEFAULT FALSE,
+    periods INT NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT TRUE
+);
+
+INSERT INTO leagues (code, name, sport, draws_allowed, periods) VALUES
+    ('NBA', 'National Basketball Association', 'Basketball', FALSE, 4),
+    ('NFL', 'National Football League', 'American football', TRUE, 4),
+    ('MLB', 'Major League Baseball', 'Baseball', FALSE, 9),
+    ('NHL', 'National Hockey League', 'Ice hockey', FALSE, 3),
+    ('NCAAF', 'NCAA Football', 'American football', FALSE, 4),
+    ('NCAAB', 'NCAA Men''s Basketball', 'Basketball', FALSE, 2),
+    ('EPL', 'Premier League', 'Soccer', TRUE, 2),
+    ('MLS', 'Major League Soccer', 'Soccer', TRUE, 2);
+
+-- Markets opened on every new game in a league. `line` is in half-points and `odds` is the
+-- price of both selections until a trader moves them.
+CREATE TABLE market_templates (
+    id SERIAL PRIMARY KEY,
+    league VARCHAR(15) NOT NULL,
+    kind MARKET_KIND NOT NULL,
+    team TEAM_SIDE NULL,
+    line INT NULL,
+    odds INT NOT NULL DEFAULT -110,
+    FOREIGN KEY (league) REFERENCES leagues(code) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+INSERT INTO market_templates (league, kind, line) VALUES
+    ('NBA', 'moneyline', NULL),
+    ('NFL', 'moneyline', NULL),
+    ('MLB', 'moneyline', NULL),
+    ('MLB', 'total', 17),
+    ('NHL', 'moneyline', NULL),
+    ('NHL', 'total', 11),
+    ('NCAAF', 'moneyline', NULL),
+    ('NCAAB', 'moneyline', NULL),
+    ('EPL', 'total', 5),
+    ('MLS', 'total', 5);
+
+ALTER TABLE games
+    DROP CONSTRAINT games_league_home_fkey,
+    DROP CONSTRAINT games_league_away_fkey;
+ALTER TABLE games ALTER COLUMN league TYPE VARCHAR(15) USING upper(league::text);
+ALTER TABLE teams ALTER COLUMN league TYPE VARCHAR(15) USING upper(league::text);
+DROP TYPE league;
+
+ALTER TABLE teams
+    ADD FOREIGN KEY (league) REFERENCES leagues(code) ON UPDATE CASCADE;
+ALTER TABLE games
+    ADD FOREIGN KEY (league) REFERENCES leagues(code) ON UPDATE CASCADE,
+    ADD FOREIGN KEY (league, home) REFERENCES teams (league, abbreviation) ON UPDATE CASCADE,
+    ADD FOREIGN KEY (league, away) REFERENCES teams (league, abbreviation) ON UPDATE CASCADE;
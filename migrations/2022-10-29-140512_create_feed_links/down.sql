@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE feed_teams;
+DROP TABLE feed_games;
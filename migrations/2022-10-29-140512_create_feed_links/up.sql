@@ -0,0 +1,19 @@
+-- Your SQL goes here
+-- Odds feeds identify games and teams by their own ids. Once a provider's game or team has been
+-- matched to ours the link is kept, so later polls don't have to match it again.
+CREATE TABLE feed_games (
+    provider VARCHAR(31) NOT NULL,
+    provider_id VARCHAR(63) NOT NULL,
+    game_id INTEGER NOT NULL REFERENCES games (id) ON DELETE CASCADE,
+    PRIMARY KEY (provider, provider_id)
+);
+
+CREATE TABLE feed_teams (
+    provider VARCHAR(31) NOT NULL,
+    provider_id VARCHAR(63) NOT NULL,
+    league VARCHAR(15) NOT NULL,
+    abbreviation VARCHAR(3) NOT NULL,
+    -- Team ids are only unique within a league for some providers
+    PRIMARY KEY (provider, league, provider_id),
+    FOREIGN KEY (league, abbreviation) REFERENCES teams (league, abbreviation) ON UPDATE CASCADE
+);
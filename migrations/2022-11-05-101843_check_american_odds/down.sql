@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE bets DROP CONSTRAINT bets_odds_american_check;
+ALTER TABLE closing_lines DROP CONSTRAINT closing_lines_odds_american_check;
+ALTER TABLE events DROP CONSTRAINT events_odds_american_check;
+ALTER TABLE market_templates DROP CONSTRAINT market_templates_odds_american_check;
+ALTER TABLE parlay_legs DROP CONSTRAINT parlay_legs_odds_american_check;
+ALTER TABLE price_moves DROP CONSTRAINT price_moves_old_odds_american_check;
+ALTER TABLE price_moves DROP CONSTRAINT price_moves_new_odds_american_check;
+ALTER TABLE pricing_policies DROP CONSTRAINT pricing_policies_min_odds_american_check;
+ALTER TABLE pricing_policies DROP CONSTRAINT pricing_policies_max_odds_american_check;
@@ -0,0 +1,57 @@
+-- Your SQL goes here
+-- American odds are never between -100 and +100, and never longer than 1000000 either way.
+--
+-- Bets and parlay legs keep the odds they were placed at, and rewriting those would change what
+-- open bets pay. If any are invalid the migration stops and lists them, so they can be voided or
+-- corrected through the ledger first.
+DO $$
+DECLARE
+    invalid TEXT;
+BEGIN
+    SELECT string_agg(label, ', ') INTO invalid FROM (
+        SELECT 'bet ' || id || ' at ' || odds AS label FROM bets
+        WHERE (odds > -100 AND odds < 100) OR odds < -1000000 OR odds > 1000000
+        UNION ALL
+        SELECT 'parlay leg ' || id || ' at ' || odds FROM parlay_legs
+        WHERE (odds > -100 AND odds < 100) OR odds < -1000000 OR odds > 1000000
+    ) AS invalid_bets;
+    IF invalid IS NOT NULL THEN
+        RAISE EXCEPTION 'Bets placed at invalid odds, void them first: %', invalid;
+    END IF;
+END $$;
+
+-- Everywhere else invalid prices are read as even money, which is what -100 and +100 already
+-- mean, and prices past the limit are brought back to it.
+UPDATE closing_lines SET odds = 100 WHERE odds > -100 AND odds < 100;
+UPDATE events SET odds = 100 WHERE odds > -100 AND odds < 100;
+UPDATE market_templates SET odds = 100 WHERE odds > -100 AND odds < 100;
+UPDATE price_moves SET old_odds = 100 WHERE old_odds > -100 AND old_odds < 100;
+UPDATE price_moves SET new_odds = 100 WHERE new_odds > -100 AND new_odds < 100;
+UPDATE pricing_policies SET min_odds = 100 WHERE min_odds > -100 AND min_odds < 100;
+UPDATE pricing_policies SET max_odds = 100 WHERE max_odds > -100 AND max_odds < 100;
+UPDATE closing_lines SET odds = GREATEST(-1000000, LEAST(1000000, odds)) WHERE odds < -1000000 OR odds > 1000000;
+UPDATE events SET odds = GREATEST(-1000000, LEAST(1000000, odds)) WHERE odds < -1000000 OR odds > 1000000;
+UPDATE market_templates SET odds = GREATEST(-1000000, LEAST(1000000, odds)) WHERE odds < -1000000 OR odds > 1000000;
+UPDATE price_moves SET old_odds = GREATEST(-1000000, LEAST(1000000, old_odds)) WHERE old_odds < -1000000 OR old_odds > 1000000;
+UPDATE price_moves SET new_odds = GREATEST(-1000000, LEAST(1000000, new_odds)) WHERE new_odds < -1000000 OR new_odds > 1000000;
+UPDATE pricing_policies SET min_odds = GREATEST(-1000000, LEAST(1000000, min_odds)) WHERE min_odds < -1000000 OR min_odds > 1000000;
+UPDATE pricing_policies SET max_odds = GREATEST(-1000000, LEAST(1000000, max_odds)) WHERE max_odds < -1000000 OR max_odds > 1000000;
+
+ALTER TABLE bets ADD CONSTRAINT bets_odds_american_check
+    CHECK ((odds <= -100 OR odds >= 100) AND odds BETWEEN -1000000 AND 1000000);
+ALTER TABLE closing_lines ADD CONSTRAINT closing_lines_odds_american_check
+    CHECK ((odds <= -100 OR odds >= 100) AND odds BETWEEN -1000000 AND 1000000);
+ALTER TABLE events ADD CONSTRAINT events_odds_american_check
+    CHECK ((odds <= -100 OR odds >= 100) AND odds BETWEEN -1000000 AND 1000000);
+ALTER TABLE market_templates ADD CONSTRAINT market_templates_odds_american_check
+    CHECK ((odds <= -100 OR odds >= 100) AND odds BETWEEN -1000000 AND 1000000);
+ALTER TABLE parlay_legs ADD CONSTRAINT parlay_legs_odds_american_check
+    CHECK ((odds <= -100 OR odds >= 100) AND odds BETWEEN -1000000 AND 1000000);
+ALTER TABLE price_moves ADD CONSTRAINT price_moves_old_odds_american_check
+    CHECK ((old_odds <= -100 OR old_odds >= 100) AND old_odds BETWEEN -1000000 AND 1000000);
+ALTER TABLE price_moves ADD CONSTRAINT price_moves_new_odds_american_check
+    CHECK ((new_odds <= -100 OR new_odds >= 100) AND new_odds BETWEEN -1000000 AND 1000000);
+ALTER TABLE pricing_policies ADD CONSTRAINT pricing_policies_min_odds_american_check
+    CHECK ((min_odds <= -100 OR min_odds >= 100) AND min_odds BETWEEN -1000000 AND 1000000);
+ALTER TABLE pricing_policies ADD CONSTRAINT pricing_policies_max_odds_american_check
+    CHECK ((max_odds <= -100 OR max_odds >= 100) AND max_odds BETWEEN -1000000 AND 1000000);
@@ -0,0 +1,22 @@
+-- Your SQL goes here
+-- Users log in by email, so no two users may share one.
+--
+-- Accounts that already share an email can't be told apart at login, and picking one to keep
+-- would hand its wallet to whoever logs in. If there are any the migration stops and lists them,
+-- so they can be merged or given new emails first.
+DO $$
+DECLARE
+    duplicates TEXT;
+BEGIN
+    SELECT string_agg(label, ', ') INTO duplicates FROM (
+        SELECT email || ' (users ' || string_agg(id::TEXT, ', ' ORDER BY id) || ')' AS label
+        FROM users
+        GROUP BY email
+        HAVING COUNT(*) > 1
+    ) AS shared_emails;
+    IF duplicates IS NOT NULL THEN
+        RAISE EXCEPTION 'Emails shared by several users, resolve them first: %', duplicates;
+    END IF;
+END $$;
+
+CREATE UNIQUE INDEX users_email ON users (email);
@@ -0,0 +1,370 @@
+//! Authentication
+//!
+//! Browsers log in with a form: a successful login creates a `Session` row and stores its id in a
+//! signed cookie. API clients exchange credentials at `POST /api/token` for a short-lived JWT
+//! access token, sent as `Authorization: Bearer <token>`, and a `RefreshToken` to get a new one
+//! with. The `CurrentUser` extractor accepts either and resolves the caller to a `User`, refusing
+//! sessions that have been logged out and tokens that have expired. The `Authorized` extractor
+//! additionally refuses anyone whose role doesn't hold a given permission.
+use crate::form::AuthError;
+use crate::handler::api::ApiError;
+use crate::model::permission::Permission;
+use crate::model::session::Session;
+use crate::model::token::RefreshToken;
+use crate::model::user::{Role, User};
+use crate::DbPool;
+
+use actix_session::{CookieSession, UserSession};
+use actix_web::cookie::SameSite;
+use actix_web::dev::Payload;
+use actix_web::error::{ErrorInternalServerError, InternalError};
+use actix_web::http::header::{ACCEPT, AUTHORIZATION};
+use actix_web::http::StatusCode;
+use actix_web::{web, Error, FromRequest, HttpRequest, HttpResponse, ResponseError};
+use chrono::{Duration, Utc};
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+use diesel::Connection;
+use handlebars::Handlebars;
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::{env, error, fmt};
+use utoipa::ToSchema;
+
+/// Name of the session cookie
+pub const SESSION_COOKIE: &str = "sportsbet_session";
+
+/// Key under which the `Session` id is kept in the cookie
+pub const SESSION_ID: &str = "session_id";
+
+/// How long an access token is accepted for
+pub const ACCESS_TOKEN_MINUTES: i64 = 15;
+
+#[derive(Debug)]
+pub enum TokenError {
+    Auth(AuthError),
+    /// The token is malformed, expired, revoked or wasn't signed by us
+    InvalidToken,
+    Jwt(jsonwebtoken::errors::Error),
+    Db(DieselError),
+}
+
+/// The logged in user making the request. `session` is the browser session they are using, or
+/// `None` for API clients authenticated with a bearer token.
+#[derive(Clone)]
+pub struct CurrentUser {
+    pub user: User,
+    pub session: Option<Session>,
+}
+
+/// A logged in user whose role holds the permission `P`. Handlers that take one refuse
+/// everyone else with a 403, e.g. `_: Authorized<perm::ManageGames>`.
+pub struct Authorized<P> {
+    pub current: CurrentUser,
+    permission: PhantomData<P>,
+}
+
+/// A permission a handler can require through `Authorized`
+pub trait Requirement: 'static {
+    const PERMISSION: Permission;
+}
+
+/// Marker types naming each `Permission`, for use as `Authorized`'s parameter
+pub mod perm {
+    use super::Requirement;
+    use crate::model::permission::Permission;
+
+    macro_rules! requirements {
+        ($($name:ident),*) => {
+            $(
+                pub struct $name;
+
+                impl Requirement for $name {
+                    const PERMISSION: Permission = Permission::$name;
+                }
+            )*
+        };
+    }
+
+    requirements!(
+        ManageUsers,
+        ManageGames,
+        ManageMarkets,
+        SetPrices,
+        ViewBook,
+        ViewExposure,
+        ViewAccounts,
+        MoveMoney,
+        PlaceBets
+    );
+}
+
+/// The claims carried by an access token
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Claims {
+    /// The user's id
+    pub sub: i32,
+    pub role: Role,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+/// The response to a successful token request
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub token_type: &'static str,
+    /// Seconds until the access token expires
+    pub expires_in: i64,
+    pub refresh_token: String,
+}
+
+#[derive(Clone, Debug, Deserialize, ToSchema)]
+pub struct RefreshForm {
+    pub refresh_token: String,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+/////// Implementations ///////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenError::Auth(e) => write!(f, "{}", e),
+            TokenError::InvalidToken => write!(f, "InvalidToken"),
+            TokenError::Jwt(e) => write!(f, "{}", e),
+            TokenError::Db(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for TokenError {}
+
+impl From<AuthError> for TokenError {
+    fn from(e: AuthError) -> Self {
+        TokenError::Auth(e)
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for TokenError {
+    fn from(e: jsonwebtoken::errors::Error) -> Self {
+        TokenError::Jwt(e)
+    }
+}
+
+impl From<DieselError> for TokenError {
+    fn from(e: DieselError) -> Self {
+        TokenError::Db(e)
+    }
+}
+
+/// Middleware that signs the session cookie with `SESSION_KEY`, which must be at least 32 bytes.
+/// The cookie is only sent over HTTPS when `SESSION_SECURE` is `true`, and never with requests
+/// started by other sites, so they can't submit our forms as the logged in user.
+pub fn session_middleware() -> CookieSession {
+    let key = env::var("SESSION_KEY").expect("SESSION_KEY must be set.");
+    assert!(key.len() >= 32, "SESSION_KEY must be at least 32 bytes.");
+    CookieSession::signed(key.as_bytes())
+        .name(SESSION_COOKIE)
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .secure(env::var("SESSION_SECURE").map_or(false, |v| v == "true"))
+}
+
+/// The secret access tokens are signed with
+fn jwt_secret() -> String {
+    env::var("JWT_SECRET").expect("JWT_SECRET must be set.")
+}
+
+impl Claims {
+    /// Claims for an access token issued to `user` now.
+    pub fn new(user: &User) -> Self {
+        let now = Utc::now();
+        Claims {
+            sub: user.id,
+            role: user.role,
+            iat: now.timestamp(),
+            exp: (now + Duration::minutes(ACCESS_TOKEN_MINUTES)).timestamp(),
+        }
+    }
+
+    /// Sign the claims into an access token.
+    pub fn encode(&self) -> Result<String, TokenError> {
+        let key = EncodingKey::from_secret(jwt_secret().as_bytes());
+        Ok(jsonwebtoken::encode(&Header::default(), self, &key)?)
+    }
+
+    /// Verify an access token's signature and expiry, and return its claims.
+    pub fn decode(token: &str) -> Result<Claims, TokenError> {
+        let secret = jwt_secret();
+        jsonwebtoken::decode::<Claims>(
+            token,
+            &DecodingKey::from_secret(secret.as_bytes()),
+            &Validation::default(),
+        )
+        .map(|data| data.claims)
+        .map_err(|_| TokenError::InvalidToken)
+    }
+}
+
+impl TokenPair {
+    /// Issue a new access token and refresh token to `user`.
+    pub fn issue(conn: &PgConnection, user: &User) -> Result<TokenPair, TokenError> {
+        let (_, refresh_token) = RefreshToken::issue(conn, user.id)?;
+        Ok(TokenPair {
+            access_token: Claims::new(user).encode()?,
+            token_type: "Bearer",
+            expires_in: ACCESS_TOKEN_MINUTES * 60,
+            refresh_token,
+        })
+    }
+
+    /// Redeem the refresh token the client presented as `value` for a new pair. The old refresh
+    /// token is revoked.
+    pub fn refresh(conn: &PgConnection, value: &str) -> Result<TokenPair, TokenError> {
+        conn.transaction::<TokenPair, TokenError, _>(|| {
+            let token = RefreshToken::find_active(conn, value)?.ok_or(TokenError::InvalidToken)?;
+            token.revoke(conn)?;
+            TokenPair::issue(conn, &User::get(conn, token.user_id)?)
+        })
+    }
+}
+
+/// The token in the request's `Authorization: Bearer` header, if it has one
+fn bearer_token(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(|t| t.trim().to_string())
+}
+
+/// Whether the request comes from an API client, which should get errors as JSON
+fn wants_json(req: &HttpRequest) -> bool {
+    let accepts_json = req
+        .headers()
+        .get(ACCEPT)
+        .and_then(|h| h.to_str().ok())
+        .map_or(false, |h| h.contains("application/json"));
+    req.path().starts_with("/api")
+        || req.path().ends_with(".json")
+        || bearer_token(req).is_some()
+        || accepts_json
+}
+
+/// Refuse the request with `status`, as an `ApiError` for API clients or as the error page.
+/// `message` is the error code, e.g. `"NotLoggedIn"`.
+pub fn deny(req: &HttpRequest, status: StatusCode, message: &str) -> Error {
+    let response = match req.app_data::<web::Data<Handlebars<'static>>>() {
+        Some(hb) if !wants_json(req) => {
+            let body = hb
+                .render(
+                    "error",
+                    &json!({
+                        "status": status.as_u16(),
+                        "message": message,
+                        "login": status == StatusCode::UNAUTHORIZED
+                    }),
+                )
+                .unwrap();
+            HttpResponse::build(status).body(body)
+        }
+        _ => ApiError::new(
+            status,
+            message,
+            status.canonical_reason().unwrap_or(message),
+        )
+        .error_response(),
+    };
+    InternalError::from_response(message.to_string(), response).into()
+}
+
+impl FromRequest for CurrentUser {
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<CurrentUser, Error>>>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let bearer = bearer_token(req);
+        let session_id = req.get_session().get::<i32>(SESSION_ID);
+        let pool = req.app_data::<web::Data<DbPool>>().cloned();
+        let req = req.clone();
+        Box::pin(async move {
+            let unauthorized = |message: &str| deny(&req, StatusCode::UNAUTHORIZED, message);
+            let pool = pool.ok_or_else(|| ErrorInternalServerError("No database pool"))?;
+            match bearer {
+                Some(token) => {
+                    let claims =
+                        Claims::decode(&token).map_err(|_| unauthorized("InvalidToken"))?;
+                    web::block(move || {
+                        let conn = pool.get().expect("Could not establish connection.");
+                        User::get(&conn, claims.sub)
+                    })
+                    .await
+                    .map(|user| CurrentUser {
+                        user,
+                        session: None,
+                    })
+                    .map_err(|_| unauthorized("InvalidToken"))
+                }
+                None => {
+                    let session_id = session_id?.ok_or_else(|| unauthorized("NotLoggedIn"))?;
+                    web::block(move || {
+                        let conn = pool.get().expect("Could not establish connection.");
+                        Session::active(&conn, session_id)
+                    })
+                    .await
+                    .map_err(ErrorInternalServerError)?
+                    .map(|(session, user)| CurrentUser {
+                        user,
+                        session: Some(session),
+                    })
+                    .ok_or_else(|| unauthorized("SessionExpired"))
+                }
+            }
+        })
+    }
+}
+
+impl CurrentUser {
+    /// Whether the user's role holds `permission`.
+    pub fn can(&self, conn: &PgConnection, permission: Permission) -> Result<bool, DieselError> {
+        permission.granted(conn, self.user.role)
+    }
+}
+
+impl<P: Requirement> FromRequest for Authorized<P> {
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Authorized<P>, Error>>>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let current = CurrentUser::from_request(req, payload);
+        let pool = req.app_data::<web::Data<DbPool>>().cloned();
+        let req = req.clone();
+        Box::pin(async move {
+            let current = current.await?;
+            let pool = pool.ok_or_else(|| ErrorInternalServerError("No database pool"))?;
+            let role = current.user.role;
+            let granted = web::block(move || {
+                let conn = pool.get().expect("Could not establish connection.");
+                P::PERMISSION.granted(&conn, role)
+            })
+            .await
+            .map_err(ErrorInternalServerError)?;
+            match granted {
+                true => Ok(Authorized {
+                    current,
+                    permission: PhantomData,
+                }),
+                false => Err(deny(&req, StatusCode::FORBIDDEN, "Forbidden")),
+            }
+        })
+    }
+}
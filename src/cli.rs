@@ -0,0 +1,69 @@
+//! Command line subcommands, run in place of the server
+//!
+//! ```text
+//! sportsbet import <league> <file> [--timezone <tz>] [--commit]
+//! ```
+//!
+//! `import` prints what importing a schedule file would do, and imports it with `--commit`. See
+//! `model::import` for the file format.
+use crate::model::import::{self, Action, ImportPlan};
+
+use diesel::pg::PgConnection;
+use std::fs;
+
+const USAGE: &str = "usage: sportsbet import <league> <file> [--timezone <tz>] [--commit]";
+
+/// Run the subcommand named by `args`, which exclude the program name.
+pub fn run(conn: &PgConnection, args: &[String]) -> Result<(), String> {
+    match args[0].as_str() {
+        "import" => import(conn, &args[1..]),
+        other => Err(format!("unknown subcommand '{}'\n{}", other, USAGE)),
+    }
+}
+
+fn import(conn: &PgConnection, args: &[String]) -> Result<(), String> {
+    let mut positional = Vec::new();
+    let mut timezone = "UTC".to_string();
+    let mut commit = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--commit" => commit = true,
+            "--timezone" => timezone = args.next().ok_or(USAGE)?.clone(),
+            _ => positional.push(arg),
+        }
+    }
+    let (league, path) = match positional[..] {
+        [league, path] => (league, path),
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let data = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    let rows = import::parse(&data).map_err(|e| e.to_string())?;
+    let plan = ImportPlan::new(conn, league, &rows, &timezone).map_err(|e| e.to_string())?;
+    for game in &plan.games {
+        let sign = match game.action {
+            Action::Create => '+',
+            Action::Skip => '=',
+            Action::Reject => '!',
+        };
+        let start = game.start.map(|s| s.to_string()).unwrap_or_default();
+        let reason = game.reason.as_deref().unwrap_or_default();
+        println!(
+            "{} row {:>4}  {:<5} @ {:<5} {:<20} {}",
+            sign, game.row, game.away, game.home, start, reason
+        );
+    }
+    println!(
+        "{} new, {} skipped, {} rejected",
+        plan.created, plan.skipped, plan.rejected
+    );
+
+    if !commit {
+        println!("Dry run; pass --commit to import.");
+        return Ok(());
+    }
+    let games = import::commit(conn, league, &rows, &timezone).map_err(|e| e.to_string())?;
+    println!("{} games imported", games.len());
+    Ok(())
+}
@@ -0,0 +1,275 @@
+//! Matching a provider's games to ours and mirroring their prices
+//!
+//! A provider's game is matched to ours the first time it is seen: its teams are looked up by
+//! provider id, or else by name through the team registry, and the game is the one between those
+//! teams starting within `START_TOLERANCE` of the provider's start time. Matches are stored so
+//! later polls go straight to the game. Each game is ingested in its own transaction, so one that
+//! fails doesn't hold back the rest.
+use super::{FeedError, FeedGame, FeedPrice, FeedTeam};
+use crate::db::{Creatable, Retrievable};
+use crate::model::market::{Market, MarketQuery, NewMarket, Selection, TeamSide};
+use crate::model::team::Team;
+use crate::model::{Game, GameStatus};
+use crate::schema::feed_games::{self, dsl as feed_games_dsl};
+use crate::schema::feed_teams::{self, dsl as feed_teams_dsl};
+use crate::schema::games::dsl as games_dsl;
+
+use chrono::{Duration, Utc};
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+use diesel::{
+    Connection, ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable, RunQueryDsl,
+};
+use serde::Serialize;
+
+/// How far a provider's start time may be from ours for the games to match, in hours
+const START_TOLERANCE: i64 = 12;
+
+/// One of a provider's games, matched to ours
+#[derive(Clone, Debug, Queryable, Insertable)]
+#[table_name = "feed_games"]
+pub struct FeedGameLink {
+    pub provider: String,
+    pub provider_id: String,
+    pub game_id: i32,
+}
+
+/// One of a provider's teams, matched to ours
+#[derive(Clone, Debug, Queryable, Insertable)]
+#[table_name = "feed_teams"]
+pub struct FeedTeamLink {
+    pub provider: String,
+    pub provider_id: String,
+    pub league: String,
+    pub abbreviation: String,
+}
+
+/// What one poll of a provider changed
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct IngestReport {
+    pub matched: usize,
+    /// Provider ids of games we have no match for
+    pub unmatched: Vec<String>,
+    /// Markets opened because the provider prices them and we didn't have them
+    pub opened: usize,
+    /// Selections given a new version at the provider's price
+    pub repriced: usize,
+    /// Provider ids of games that couldn't be ingested, with the reason
+    pub failed: Vec<(String, String)>,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+/////// Implementations ///////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl Creatable for FeedGameLink {
+    type Output = FeedGameLink;
+    fn create(&self, conn: &PgConnection) -> Result<FeedGameLink, DieselError> {
+        diesel::insert_into(feed_games_dsl::feed_games)
+            .values(self)
+            .get_result(conn)
+    }
+}
+
+impl Creatable for FeedTeamLink {
+    type Output = FeedTeamLink;
+    fn create(&self, conn: &PgConnection) -> Result<FeedTeamLink, DieselError> {
+        diesel::insert_into(feed_teams_dsl::feed_teams)
+            .values(self)
+            .get_result(conn)
+    }
+}
+
+impl IngestReport {
+    /// Add what ingesting another game changed.
+    fn merge(&mut self, other: IngestReport) {
+        self.matched += other.matched;
+        self.unmatched.extend(other.unmatched);
+        self.opened += other.opened;
+        self.repriced += other.repriced;
+        self.failed.extend(other.failed);
+    }
+}
+
+/// Mirror the prices `provider` offers on `games`, one game per transaction. Games that fail are
+/// rolled back and listed in the report.
+pub fn ingest(conn: &PgConnection, provider: &str, games: &[FeedGame]) -> IngestReport {
+    let mut report = IngestReport::default();
+    for feed in games {
+        match conn.transaction(|| ingest_game(conn, provider, feed)) {
+            Ok(game) => report.merge(game),
+            Err(e) => report.failed.push((feed.id.clone(), e.to_string())),
+        }
+    }
+    report
+}
+
+/// Mirror the prices `provider` offers on one game.
+fn ingest_game(
+    conn: &PgConnection,
+    provider: &str,
+    feed: &FeedGame,
+) -> Result<IngestReport, FeedError> {
+    let mut report = IngestReport::default();
+    match match_game(conn, provider, feed)? {
+        Some(game) => {
+            report.matched += 1;
+            mirror(conn, &game, &feed.prices, &mut report)?;
+        }
+        None => report.unmatched.push(feed.id.clone()),
+    }
+    Ok(report)
+}
+
+/// Our game for the provider's `feed` game, if there is one.
+fn match_game(
+    conn: &PgConnection,
+    provider: &str,
+    feed: &FeedGame,
+) -> Result<Option<Game>, DieselError> {
+    let link: Option<FeedGameLink> = feed_games_dsl::feed_games
+        .find((provider, &feed.id))
+        .first(conn)
+        .optional()?;
+    if let Some(link) = link {
+        return Game::get(conn, link.game_id).map(Some);
+    }
+
+    let home = match_team(conn, provider, &feed.league, &feed.home)?;
+    let away = match_team(conn, provider, &feed.league, &feed.away)?;
+    let (home, away) = match (home, away) {
+        (Some(home), Some(away)) => (home, away),
+        _ => return Ok(None),
+    };
+    let tolerance = Duration::hours(START_TOLERANCE);
+    let game: Option<Game> = games_dsl::games
+        .filter(games_dsl::league.eq(&feed.league))
+        .filter(games_dsl::home.eq(&home))
+        .filter(games_dsl::away.eq(&away))
+        .filter(games_dsl::start.between(feed.start - tolerance, feed.start + tolerance))
+        .filter(games_dsl::status.ne(GameStatus::Cancelled))
+        .first(conn)
+        .optional()?;
+    if let Some(game) = &game {
+        FeedGameLink {
+            provider: provider.to_string(),
+            provider_id: feed.id.clone(),
+            game_id: game.id,
+        }
+        .create(conn)?;
+    }
+    Ok(game)
+}
+
+/// The abbreviation of our team for the provider's `team` in `league`, if there is one.
+fn match_team(
+    conn: &PgConnection,
+    provider: &str,
+    league: &str,
+    team: &FeedTeam,
+) -> Result<Option<String>, DieselError> {
+    let link: Option<FeedTeamLink> = feed_teams_dsl::feed_teams
+        .find((provider, league, &team.id))
+        .first(conn)
+        .optional()?;
+    if let Some(link) = link {
+        return Ok(Some(link.abbreviation));
+    }
+    match Team::lookup(conn, league, &team.name).optional()? {
+        Some(ours) => {
+            FeedTeamLink {
+                provider: provider.to_string(),
+                provider_id: team.id.clone(),
+                league: ours.league,
+                abbreviation: ours.abbreviation.clone(),
+            }
+            .create(conn)?;
+            Ok(Some(ours.abbreviation))
+        }
+        None => Ok(None),
+    }
+}
+
+/// The market on game `game_id` that `selection` belongs to
+fn market_for(game_id: i32, selection: &Selection) -> NewMarket {
+    let (team, line) = match *selection {
+        Selection::Moneyline(_) => (None, None),
+        Selection::Spread(TeamSide::Home, line) => (None, Some(line.0)),
+        Selection::Spread(TeamSide::Away, line) => (None, Some(-line.0)),
+        Selection::Total(_, line) => (None, Some(line.0)),
+        Selection::TeamTotal(team, _, line) => (Some(team), Some(line.0)),
+    };
+    NewMarket {
+        game_id,
+        kind: selection.kind(),
+        team,
+        line,
+    }
+}
+
+/// Bring `game`'s markets in line with `prices`. Selections whose price has moved get a new
+/// version; markets the game doesn't have are opened once both their selections are priced.
+/// Suspended markets, and games that aren't taking bets, such as those past their start that
+/// haven't been marked live, are left alone.
+fn mirror(
+    conn: &PgConnection,
+    game: &Game,
+    prices: &[FeedPrice],
+    report: &mut IngestReport,
+) -> Result<(), FeedError> {
+    if !game.accepts_bets(Utc::now().naive_utc()) {
+        return Ok(());
+    }
+    let price = |selection: Selection| {
+        prices
+            .iter()
+            .find(|p| p.selection == selection)
+            .map(|p| p.odds)
+    };
+    let markets = Market::query(
+        conn,
+        &MarketQuery {
+            game_id: Some(game.id),
+        },
+    )?;
+
+    let mut wanted: Vec<NewMarket> = Vec::new();
+    for p in prices {
+        let new = market_for(game.id, &p.selection);
+        if !wanted
+            .iter()
+            .any(|w| w.kind == new.kind && w.team == new.team && w.line == new.line)
+        {
+            wanted.push(new);
+        }
+    }
+
+    for new in wanted {
+        let existing = markets
+            .iter()
+            .find(|m| m.kind == new.kind && m.team == new.team && m.line == new.line);
+        match existing {
+            Some(market) if market.suspended => {}
+            Some(market) => {
+                for event in market.selections(conn)? {
+                    match event.selection().and_then(price) {
+                        Some(odds) if odds != event.odds => {
+                            event.reprice(conn, odds)?;
+                            report.repriced += 1;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            None => {
+                let [first, second] = new.selections()?;
+                if let (Some(first), Some(second)) = (price(first), price(second)) {
+                    Market::open(conn, game, &new, [first, second])?;
+                    report.opened += 1;
+                }
+            }
+        }
+    }
+    Ok(())
+}
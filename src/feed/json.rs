@@ -0,0 +1,109 @@
+//! Adapter for providers that publish prices in our own JSON shape
+//!
+//! The feed is an array of games, each with its provider id, our league code, a UTC start time,
+//! both teams and the prices offered:
+//!
+//! ```json
+//! [{
+//!     "id": "401468",
+//!     "league": "NBA",
+//!     "start": "2022-10-18T23:30:00Z",
+//!     "home": { "id": "2", "name": "Boston Celtics" },
+//!     "away": { "id": "20", "name": "Philadelphia 76ers" },
+//!     "prices": [
+//!         { "market": "Spread", "team": "Home", "line": -2.5, "odds": -110 },
+//!         { "market": "Total", "total": "Over", "line": 215.5, "odds": -105 }
+//!     ]
+//! }]
+//! ```
+//!
+//! `market`, `team` and `total` take the same values as on our markets, and lines are in points.
+//! For spreads `line` is the priced team's line. The key, if any, is sent as a bearer token.
+use super::{FeedError, FeedGame, FeedPrice, FeedProvider, FeedTeam};
+use crate::model::market::{Line, MarketKind, Selection, TeamSide, TotalSide};
+use crate::odds::Odds;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+
+pub struct JsonFeed {
+    pub url: String,
+    pub key: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct JsonGame {
+    pub id: String,
+    pub league: String,
+    pub start: DateTime<Utc>,
+    pub home: FeedTeam,
+    pub away: FeedTeam,
+    #[serde(default)]
+    pub prices: Vec<JsonPrice>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct JsonPrice {
+    pub market: MarketKind,
+    pub team: Option<TeamSide>,
+    pub total: Option<TotalSide>,
+    pub line: Option<f64>,
+    pub odds: Odds,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+/////// Implementations ///////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl JsonPrice {
+    /// The price as one of our selections, or `None` if it is missing a side or line.
+    fn to_feed_price(&self) -> Option<FeedPrice> {
+        let line = self.line.map(Line::from_points).transpose().ok()?;
+        let selection = Selection::from_parts(self.market, self.team, self.total, line).ok()?;
+        Some(FeedPrice {
+            selection,
+            odds: self.odds,
+        })
+    }
+}
+
+impl From<JsonGame> for FeedGame {
+    fn from(game: JsonGame) -> Self {
+        FeedGame {
+            prices: game
+                .prices
+                .iter()
+                .filter_map(JsonPrice::to_feed_price)
+                .collect(),
+            id: game.id,
+            league: game.league,
+            home: game.home,
+            away: game.away,
+            start: game.start.naive_utc(),
+        }
+    }
+}
+
+#[async_trait]
+impl FeedProvider for JsonFeed {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn request(&self, client: &Client) -> RequestBuilder {
+        let request = client.get(&self.url);
+        match &self.key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        }
+    }
+
+    fn parse(&self, body: &[u8]) -> Result<Vec<FeedGame>, FeedError> {
+        let games: Vec<JsonGame> =
+            serde_json::from_slice(body).map_err(|e| FeedError::Parse(e.to_string()))?;
+        Ok(games.into_iter().map(FeedGame::from).collect())
+    }
+}
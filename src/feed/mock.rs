@@ -0,0 +1,92 @@
+//! A stand-in odds provider for tests and local development
+//!
+//! `MockServer` serves a JSON body at `/odds` on a free local port, so the HTTP client and the
+//! adapters can be exercised end to end without a real provider. The body can be swapped between
+//! polls, and the server told to fail requests to exercise backoff.
+use actix_web::dev::Server;
+use actix_web::{web, App, HttpResponse, HttpServer};
+use serde_json::Value;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[derive(Default)]
+struct MockState {
+    body: Mutex<Value>,
+    /// Requests still to be answered with a 503
+    failures: AtomicUsize,
+    requests: AtomicUsize,
+}
+
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<MockState>,
+    server: Server,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+/////// Implementations ///////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl MockServer {
+    /// Start serving `body`. Must be called from within an actix runtime.
+    pub fn start(body: Value) -> io::Result<MockServer> {
+        let state = Arc::new(MockState {
+            body: Mutex::new(body),
+            ..MockState::default()
+        });
+        let data = web::Data::from(state.clone());
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(data.clone())
+                .route("/odds", web::get().to(odds))
+        })
+        .workers(1)
+        .bind("127.0.0.1:0")?;
+        let addr = server.addrs()[0];
+        Ok(MockServer {
+            addr,
+            state,
+            server: server.run(),
+        })
+    }
+
+    /// Where the prices are served
+    pub fn url(&self) -> String {
+        format!("http://{}/odds", self.addr)
+    }
+
+    /// Serve `body` from now on.
+    pub fn set(&self, body: Value) {
+        *self.state.body.lock().unwrap() = body;
+    }
+
+    /// Answer the next `n` requests with a 503.
+    pub fn fail_next(&self, n: usize) {
+        self.state.failures.store(n, Ordering::SeqCst);
+    }
+
+    /// Requests received so far, failed ones included
+    pub fn requests(&self) -> usize {
+        self.state.requests.load(Ordering::SeqCst)
+    }
+
+    pub async fn stop(self) {
+        self.server.stop(true).await;
+    }
+}
+
+async fn odds(state: web::Data<MockState>) -> HttpResponse {
+    state.requests.fetch_add(1, Ordering::SeqCst);
+    let failing = state
+        .failures
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+        .is_ok();
+    if failing {
+        return HttpResponse::ServiceUnavailable().finish();
+    }
+    let body = state.body.lock().unwrap().clone();
+    HttpResponse::Ok().json(body)
+}
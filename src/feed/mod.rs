@@ -0,0 +1,277 @@
+//! Odds feed ingestion
+//!
+//! Mirrors market prices from an external odds provider. A `FeedProvider` adapter fetches the
+//! provider's current prices and translates them into `FeedGame`s; `ingest` matches those to our
+//! games and writes every price that moved as a new version of its event. `poll` runs the two on
+//! an interval, backing off while the provider is failing.
+//!
+//! The feed is configured through the environment and is off unless `ODDS_FEED_URL` is set:
+//!
+//! - `ODDS_FEED_URL`: where to fetch prices from
+//! - `ODDS_FEED_PROVIDER`: `json` (the default) or `the-odds-api`
+//! - `ODDS_FEED_KEY`: API key, if the provider needs one
+//! - `ODDS_FEED_BOOKMAKER`: bookmaker to mirror, for providers that list several
+//! - `ODDS_FEED_INTERVAL`: seconds between polls, 60 by default
+pub mod ingest;
+pub mod json;
+pub mod mock;
+pub mod odds_api;
+
+pub use ingest::{ingest, IngestReport};
+
+use crate::model::market::{MarketError, Selection};
+use crate::odds::Odds;
+use crate::DbPool;
+
+use actix_web::error::BlockingError;
+use actix_web::{rt, web};
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use diesel::result::Error as DieselError;
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::time::Duration;
+use std::{error, fmt};
+
+#[derive(Debug)]
+pub enum FeedError {
+    UnknownProvider(String),
+    /// The provider couldn't be reached or answered with an error
+    Http(reqwest::Error),
+    /// The provider's response wasn't in the shape its adapter expects
+    Parse(String),
+    Market(MarketError),
+    Db(DieselError),
+    Canceled,
+}
+
+/// A game as a provider lists it, with the prices it offers on it. `league` is our league code.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeedGame {
+    pub id: String,
+    pub league: String,
+    pub home: FeedTeam,
+    pub away: FeedTeam,
+    pub start: NaiveDateTime,
+    pub prices: Vec<FeedPrice>,
+}
+
+/// A team as a provider names it. Providers without team ids use the name as the id.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct FeedTeam {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FeedPrice {
+    pub selection: Selection,
+    pub odds: Odds,
+}
+
+#[derive(Clone, Debug)]
+pub struct FeedConfig {
+    pub provider: String,
+    pub url: String,
+    pub key: Option<String>,
+    pub bookmaker: Option<String>,
+    pub interval: Duration,
+    /// Longest wait between polls while the provider is failing
+    pub max_backoff: Duration,
+}
+
+/// Delays between polls: the configured interval while polls succeed, doubling with each
+/// consecutive failure up to a limit.
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    interval: Duration,
+    max: Duration,
+    failures: u32,
+}
+
+/// An odds provider. Adapters build the request for the provider's current prices and translate
+/// its response; fetching is shared.
+#[async_trait]
+pub trait FeedProvider: Send + Sync {
+    /// Name the provider's game and team ids are stored under
+    fn name(&self) -> &str;
+
+    /// The request for the provider's current prices
+    fn request(&self, client: &Client) -> RequestBuilder;
+
+    /// Translate a response body into games. Games and prices we have no equivalent of are left
+    /// out.
+    fn parse(&self, body: &[u8]) -> Result<Vec<FeedGame>, FeedError>;
+
+    async fn fetch(&self, client: &Client) -> Result<Vec<FeedGame>, FeedError> {
+        let body = self
+            .request(client)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        self.parse(&body)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+/////// Implementations ///////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl fmt::Display for FeedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FeedError::UnknownProvider(p) => write!(f, "UnknownProvider: {}", p),
+            FeedError::Http(e) => write!(f, "Http: {}", e),
+            FeedError::Parse(e) => write!(f, "Parse: {}", e),
+            FeedError::Market(e) => write!(f, "{}", e),
+            FeedError::Db(e) => write!(f, "{}", e),
+            FeedError::Canceled => write!(f, "Canceled"),
+        }
+    }
+}
+
+impl error::Error for FeedError {}
+
+impl From<reqwest::Error> for FeedError {
+    fn from(e: reqwest::Error) -> Self {
+        FeedError::Http(e)
+    }
+}
+
+impl From<DieselError> for FeedError {
+    fn from(e: DieselError) -> Self {
+        FeedError::Db(e)
+    }
+}
+
+impl From<MarketError> for FeedError {
+    fn from(e: MarketError) -> Self {
+        match e {
+            MarketError::Db(e) => FeedError::Db(e),
+            e => FeedError::Market(e),
+        }
+    }
+}
+
+impl From<BlockingError<FeedError>> for FeedError {
+    fn from(e: BlockingError<FeedError>) -> Self {
+        match e {
+            BlockingError::Error(e) => e,
+            BlockingError::Canceled => FeedError::Canceled,
+        }
+    }
+}
+
+impl FeedConfig {
+    /// Read the feed's configuration from the environment, or `None` if no feed is configured.
+    pub fn from_env() -> Option<FeedConfig> {
+        let interval = env::var("ODDS_FEED_INTERVAL")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(60);
+        Some(FeedConfig {
+            provider: env::var("ODDS_FEED_PROVIDER").unwrap_or_else(|_| "json".to_string()),
+            url: env::var("ODDS_FEED_URL").ok()?,
+            key: env::var("ODDS_FEED_KEY").ok(),
+            bookmaker: env::var("ODDS_FEED_BOOKMAKER").ok(),
+            interval: Duration::from_secs(interval),
+            max_backoff: Duration::from_secs(interval * 16),
+        })
+    }
+
+    /// The adapter for the configured provider
+    pub fn provider(&self) -> Result<Box<dyn FeedProvider>, FeedError> {
+        match self.provider.as_str() {
+            "json" => Ok(Box::new(json::JsonFeed {
+                url: self.url.clone(),
+                key: self.key.clone(),
+            })),
+            "the-odds-api" => Ok(Box::new(odds_api::OddsApi {
+                url: self.url.clone(),
+                key: self.key.clone().unwrap_or_default(),
+                bookmaker: self.bookmaker.clone(),
+            })),
+            other => Err(FeedError::UnknownProvider(other.to_string())),
+        }
+    }
+}
+
+impl Backoff {
+    pub fn new(interval: Duration, max: Duration) -> Self {
+        Backoff {
+            interval,
+            max,
+            failures: 0,
+        }
+    }
+
+    /// The delay before the next poll after one that succeeded
+    pub fn succeed(&mut self) -> Duration {
+        self.failures = 0;
+        self.interval
+    }
+
+    /// The delay before the next poll after one that failed
+    pub fn fail(&mut self) -> Duration {
+        self.failures = self.failures.saturating_add(1);
+        let factor = 1u32 << self.failures.min(16);
+        self.interval
+            .checked_mul(factor)
+            .map_or(self.max, |delay| delay.min(self.max))
+    }
+}
+
+/// Fetch `provider`'s current prices once and ingest them.
+pub async fn poll_once(
+    pool: &DbPool,
+    provider: &dyn FeedProvider,
+    client: &Client,
+) -> Result<IngestReport, FeedError> {
+    let games = provider.fetch(client).await?;
+    let pool = pool.clone();
+    let name = provider.name().to_string();
+    Ok(web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        Ok::<_, FeedError>(ingest(&conn, &name, &games))
+    })
+    .await?)
+}
+
+/// Poll the configured provider forever, logging what each poll did.
+pub async fn poll(pool: DbPool, config: FeedConfig) {
+    let provider = match config.provider() {
+        Ok(provider) => provider,
+        Err(e) => return eprintln!("Odds feed not started: {}", e),
+    };
+    let client = Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .expect("Could not build HTTP client.");
+    let mut backoff = Backoff::new(config.interval, config.max_backoff);
+    loop {
+        let delay = match poll_once(&pool, &*provider, &client).await {
+            Ok(report) => {
+                if !report.unmatched.is_empty() {
+                    eprintln!(
+                        "Odds feed {}: unmatched games {:?}",
+                        provider.name(),
+                        report.unmatched
+                    );
+                }
+                for (id, e) in &report.failed {
+                    eprintln!("Odds feed {}: game {} failed: {}", provider.name(), id, e);
+                }
+                backoff.succeed()
+            }
+            Err(e) => {
+                eprintln!("Odds feed {} failed: {}", provider.name(), e);
+                backoff.fail()
+            }
+        };
+        rt::time::delay_for(delay).await;
+    }
+}
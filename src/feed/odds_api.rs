@@ -0,0 +1,164 @@
+//! Adapter for The Odds API (v4)
+//!
+//! `url` is a sport's odds endpoint, e.g.
+//! `https://api.the-odds-api.com/v4/sports/basketball_nba/odds`. Moneylines, spreads and totals
+//! are requested in American odds from one bookmaker, the configured one or else the first
+//! listed. The API has no team ids, so teams are matched by name. Three-way moneylines, which
+//! include the draw, have no equivalent here and are skipped.
+use super::{FeedError, FeedGame, FeedPrice, FeedProvider, FeedTeam};
+use crate::model::market::{Line, Selection, TeamSide, TotalSide};
+use crate::odds::Odds;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+
+/// The API's sport keys and the leagues they are
+const SPORTS: [(&str, &str); 8] = [
+    ("americanfootball_nfl", "NFL"),
+    ("americanfootball_ncaaf", "NCAAF"),
+    ("baseball_mlb", "MLB"),
+    ("basketball_nba", "NBA"),
+    ("basketball_ncaab", "NCAAB"),
+    ("icehockey_nhl", "NHL"),
+    ("soccer_epl", "EPL"),
+    ("soccer_usa_mls", "MLS"),
+];
+
+pub struct OddsApi {
+    pub url: String,
+    pub key: String,
+    pub bookmaker: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ApiEvent {
+    id: String,
+    sport_key: String,
+    commence_time: DateTime<Utc>,
+    home_team: String,
+    away_team: String,
+    #[serde(default)]
+    bookmakers: Vec<ApiBookmaker>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ApiBookmaker {
+    key: String,
+    markets: Vec<ApiMarket>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ApiMarket {
+    key: String,
+    outcomes: Vec<ApiOutcome>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ApiOutcome {
+    name: String,
+    price: f64,
+    point: Option<f64>,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+/////// Implementations ///////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl OddsApi {
+    /// Our version of `event`, or `None` if it is in a league we don't have.
+    fn to_feed_game(&self, event: &ApiEvent) -> Option<FeedGame> {
+        let league = SPORTS
+            .iter()
+            .find(|(key, _)| *key == event.sport_key)
+            .map(|(_, league)| league.to_string())?;
+        let bookmaker = event.bookmakers.iter().find(|b| {
+            self.bookmaker
+                .as_ref()
+                .map_or(true, |wanted| b.key == *wanted)
+        });
+        let prices = bookmaker
+            .map(|b| {
+                b.markets
+                    .iter()
+                    .flat_map(|market| prices(event, market))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let team = |name: &str| FeedTeam {
+            id: name.to_string(),
+            name: name.to_string(),
+        };
+        Some(FeedGame {
+            id: event.id.clone(),
+            league,
+            home: team(&event.home_team),
+            away: team(&event.away_team),
+            start: event.commence_time.naive_utc(),
+            prices,
+        })
+    }
+}
+
+/// The prices in one of `event`'s markets we have an equivalent of
+fn prices(event: &ApiEvent, market: &ApiMarket) -> Vec<FeedPrice> {
+    if market.key == "h2h" && market.outcomes.iter().any(|o| o.name == "Draw") {
+        return Vec::new();
+    }
+    let side = |name: &str| {
+        if name == event.home_team {
+            Some(TeamSide::Home)
+        } else if name == event.away_team {
+            Some(TeamSide::Away)
+        } else {
+            None
+        }
+    };
+    let total = |name: &str| match name {
+        "Over" => Some(TotalSide::Over),
+        "Under" => Some(TotalSide::Under),
+        _ => None,
+    };
+    market
+        .outcomes
+        .iter()
+        .filter_map(|outcome| {
+            let line = || outcome.point.and_then(|p| Line::from_points(p).ok());
+            let selection = match market.key.as_str() {
+                "h2h" => Selection::Moneyline(side(&outcome.name)?),
+                "spreads" => Selection::Spread(side(&outcome.name)?, line()?),
+                "totals" => Selection::Total(total(&outcome.name)?, line()?),
+                _ => return None,
+            };
+            let odds = Odds::american(outcome.price.round() as i32).ok()?;
+            Some(FeedPrice { selection, odds })
+        })
+        .collect()
+}
+
+#[async_trait]
+impl FeedProvider for OddsApi {
+    fn name(&self) -> &str {
+        "the-odds-api"
+    }
+
+    fn request(&self, client: &Client) -> RequestBuilder {
+        client.get(&self.url).query(&[
+            ("apiKey", self.key.as_str()),
+            ("regions", "us"),
+            ("markets", "h2h,spreads,totals"),
+            ("oddsFormat", "american"),
+        ])
+    }
+
+    fn parse(&self, body: &[u8]) -> Result<Vec<FeedGame>, FeedError> {
+        let events: Vec<ApiEvent> =
+            serde_json::from_slice(body).map_err(|e| FeedError::Parse(e.to_string()))?;
+        Ok(events
+            .iter()
+            .filter_map(|event| self.to_feed_game(event))
+            .collect())
+    }
+}
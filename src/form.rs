@@ -3,15 +3,21 @@
 use std::fmt;
 
 use crate::db::Retrievable;
+use crate::model::game_result::NewGameResult;
+use crate::model::market::{Line, MarketError, MarketKind, NewMarket, TeamSide};
+use crate::model::pricing::NewPricingPolicy;
 use crate::model::user::Role;
 use crate::model::user::{AuthedUser, NewUser, User, UserQuery};
-use crate::schema::events;
+use crate::model::GameStatus;
+use crate::odds::Odds;
+use crate::password;
 use async_trait::async_trait;
-use chrono::{NaiveDate, NaiveDateTime};
+use chrono::NaiveDateTime;
 use diesel::pg::PgConnection;
-use diesel::{Connection, Insertable};
+use diesel::Connection;
 use serde::{Deserialize, Serialize};
 use std::error;
+use utoipa::ToSchema;
 
 pub trait Form {}
 
@@ -25,6 +31,12 @@ pub enum AuthError {
 #[derive(Debug, Clone)]
 pub enum ValidationError {
     PasswordMismatch,
+    MissingUsername,
+    InvalidStake,
+    InvalidAmount,
+    InvalidLegs,
+    InvalidPolicy,
+    InvalidStart,
 }
 
 #[async_trait]
@@ -42,16 +54,15 @@ where
 //                                                                                               //
 ///////////////////////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub struct SignupForm {
     pub email: String,
     pub username: String,
     pub password1: String,
     pub password2: String,
-    pub role: Role,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct LoginForm {
     pub email: String,
     pub password: String,
@@ -64,12 +75,76 @@ pub struct GameForm {
     pub start: String,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, Insertable)]
-#[table_name = "events"]
-pub struct EventForm {
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MarketForm {
     pub game_id: i32,
-    pub description: String,
-    pub odds: i32,
+    pub market: MarketKind,
+    pub team: TeamSide,
+    pub line: String,
+    pub first_odds: Odds,
+    pub second_odds: Odds,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OddsForm {
+    pub odds: Odds,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BetForm {
+    pub stake: f64,
+}
+
+/// A parlay on the events listed in `legs`, e.g. "3, 7, 12"
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ParlayForm {
+    pub stake: f64,
+    pub legs: String,
+}
+
+/// A market's pricing policy. The threshold is entered in dollars and the step in cents of
+/// American odds; the policy is disabled when the `enabled` checkbox is left unticked.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PolicyForm {
+    pub threshold: f64,
+    pub step: i32,
+    pub min_odds: Odds,
+    pub max_odds: Odds,
+    pub enabled: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DepositForm {
+    pub amount: f64,
+    /// The user to credit, if not the caller
+    pub user_id: Option<i32>,
+}
+
+/// A game's final score
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct ScoreForm {
+    pub home: i32,
+    pub away: i32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct RoleForm {
+    pub role: Role,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct StatusForm {
+    pub status: GameStatus,
+}
+
+/// A schedule file pasted or loaded into the import page. Without `commit` the import is only
+/// planned.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ScheduleForm {
+    pub league: String,
+    pub timezone: String,
+    pub data: String,
+    pub commit: Option<String>,
 }
 
 ///////////////////////////////////////////////////////////////////////////////////////////////////
@@ -81,6 +156,12 @@ impl fmt::Display for ValidationError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             ValidationError::PasswordMismatch => write!(f, "PasswordMismatch"),
+            ValidationError::MissingUsername => write!(f, "MissingUsername"),
+            ValidationError::InvalidStake => write!(f, "InvalidStake"),
+            ValidationError::InvalidAmount => write!(f, "InvalidAmount"),
+            ValidationError::InvalidLegs => write!(f, "InvalidLegs"),
+            ValidationError::InvalidPolicy => write!(f, "InvalidPolicy"),
+            ValidationError::InvalidStart => write!(f, "InvalidStart"),
         }
     }
 }
@@ -96,20 +177,22 @@ impl SignupForm {
             username: String::new(),
             password1: String::new(),
             password2: String::new(),
-            role: Role::Punter,
         }
     }
 
-    /// Validates form by checking if passwords match
-    pub fn validate(self) -> Result<Self, impl error::Error> {
-        if &self.password2 == &self.password1 {
+    /// Validates form by checking that a username was given and that the passwords match
+    pub fn validate(self) -> Result<Self, ValidationError> {
+        if self.username.trim().is_empty() {
+            Err(ValidationError::MissingUsername)
+        } else if &self.password2 == &self.password1 {
             Ok(self)
         } else {
             Err(ValidationError::PasswordMismatch)
         }
     }
 
-    /// Authenticates signup form by checking database to see if email or username is available
+    /// Authenticates signup form by checking database to see if email or username is available.
+    /// Everyone signs up as a punter; other roles are granted by an admin.
     pub fn authenticate(self, conn: &PgConnection) -> Result<NewUser, AuthError> {
         let usr = User::query(
             conn,
@@ -126,7 +209,7 @@ impl SignupForm {
                 email: self.email,
                 username: self.username,
                 password: self.password2,
-                role: self.role,
+                role: Role::Punter,
             })
         }
     }
@@ -161,40 +244,25 @@ impl LoginForm {
         }
     }
 
-    /// Check the form instance's password against the associated user object's password
+    /// Check the form instance's password against the associated user object's password hash.
+    /// A password stored in plaintext or with outdated parameters is rehashed on success.
     pub fn authenticate(self, conn: &PgConnection) -> Result<User, AuthError> {
-        let usrs = User::query(
-            &conn,
-            &UserQuery {
-                email: &self.email,
-                username: "",
-            },
-        )
-        .unwrap();
-        match usrs.len() {
-            0 => Err(AuthError::EmailNotFound),
-            _ => match &usrs[0].password == &self.password {
-                true => Ok(usrs[0].clone()),
-                false => Err(AuthError::IncorrectPassword),
-            },
+        let usr = User::by_email(conn, &self.email)
+            .unwrap()
+            .ok_or(AuthError::EmailNotFound)?;
+        match password::verify(&self.password, &usr.password) {
+            true if password::needs_rehash(&usr.password) => {
+                // The password was right, so a failed rehash shouldn't fail the login
+                Ok(usr.rehash(conn, &self.password).unwrap_or(usr))
+            }
+            true => Ok(usr),
+            false => Err(AuthError::IncorrectPassword),
         }
     }
 
     /// Return the associated user object or None if no user is found
     pub async fn user(&self, conn: &PgConnection) -> Option<User> {
-        let usrs = User::query(
-            conn,
-            &UserQuery {
-                email: &self.email,
-                username: "",
-            },
-        )
-        .unwrap();
-        if usrs.len() > 0 {
-            Some(usrs[0].clone())
-        } else {
-            None
-        }
+        User::by_email(conn, &self.email).unwrap()
     }
 }
 
@@ -207,32 +275,142 @@ impl GameForm {
         }
     }
 
-    /// Return a NaiveDateTime made from `GameForm`'s `start` String.
-    pub fn start_to_naive(&self) -> NaiveDateTime {
-        let start = self.start.as_bytes();
-        let (yr, mo, dy, hr, mn) = (
-            String::from_utf8(start[0..4].to_vec())
-                .unwrap()
-                .parse::<i32>()
-                .unwrap(),
-            String::from_utf8(start[5..7].to_vec())
-                .unwrap()
-                .parse::<u32>()
-                .unwrap(),
-            String::from_utf8(start[8..10].to_vec())
-                .unwrap()
-                .parse::<u32>()
-                .unwrap(),
-            String::from_utf8(start[11..13].to_vec())
-                .unwrap()
-                .parse::<u32>()
-                .unwrap(),
-            String::from_utf8(start[14..16].to_vec())
-                .unwrap()
-                .parse::<u32>()
-                .unwrap(),
-        );
-
-        NaiveDate::from_ymd(yr, mo, dy).and_hms(hr, mn, 0)
+    /// Return a NaiveDateTime made from `GameForm`'s `start` String, e.g. "1987-10-03T17:00".
+    /// Seconds are ignored.
+    pub fn start_to_naive(&self) -> Result<NaiveDateTime, ValidationError> {
+        self.start
+            .get(0..16)
+            .and_then(|start| NaiveDateTime::parse_from_str(start, "%Y-%m-%dT%H:%M").ok())
+            .ok_or(ValidationError::InvalidStart)
+    }
+}
+
+impl BetForm {
+    /// Validates form by checking that the stake is a positive amount
+    pub fn validate(self) -> Result<Self, ValidationError> {
+        if self.stake_to_cents() > 0 {
+            Ok(self)
+        } else {
+            Err(ValidationError::InvalidStake)
+        }
+    }
+
+    /// Return the form's stake, entered in dollars, as a whole number of cents.
+    pub fn stake_to_cents(&self) -> i64 {
+        (self.stake * 100.0).round() as i64
+    }
+}
+
+impl ParlayForm {
+    /// Validates form by checking that the stake is a positive amount
+    pub fn validate(self) -> Result<Self, ValidationError> {
+        if self.stake_to_cents() > 0 {
+            Ok(self)
+        } else {
+            Err(ValidationError::InvalidStake)
+        }
+    }
+
+    /// Return the form's stake, entered in dollars, as a whole number of cents.
+    pub fn stake_to_cents(&self) -> i64 {
+        (self.stake * 100.0).round() as i64
+    }
+
+    /// Parse the comma separated event ids of the parlay's legs.
+    pub fn event_ids(&self) -> Result<Vec<i32>, ValidationError> {
+        self.legs
+            .split(',')
+            .map(|id| id.trim().parse().map_err(|_| ValidationError::InvalidLegs))
+            .collect()
+    }
+}
+
+impl PolicyForm {
+    /// Validates form by checking that the threshold and step are positive and that `min_odds`
+    /// is no longer than `max_odds`
+    pub fn validate(self) -> Result<Self, ValidationError> {
+        if self.threshold_to_cents() > 0
+            && self.step > 0
+            && self.min_odds.implied_probability() >= self.max_odds.implied_probability()
+        {
+            Ok(self)
+        } else {
+            Err(ValidationError::InvalidPolicy)
+        }
+    }
+
+    /// Return the form's threshold, entered in dollars, as a whole number of cents.
+    pub fn threshold_to_cents(&self) -> i64 {
+        (self.threshold * 100.0).round() as i64
+    }
+
+    /// Build the policy for the market with the given `market_id`.
+    pub fn to_new_policy(&self, market_id: i32) -> NewPricingPolicy {
+        NewPricingPolicy {
+            market_id,
+            threshold: self.threshold_to_cents(),
+            step: self.step,
+            min_odds: self.min_odds,
+            max_odds: self.max_odds,
+            enabled: self.enabled.is_some(),
+        }
+    }
+}
+
+impl DepositForm {
+    /// Validates form by checking that the deposit is a positive amount
+    pub fn validate(self) -> Result<Self, ValidationError> {
+        if self.amount_to_cents() > 0 {
+            Ok(self)
+        } else {
+            Err(ValidationError::InvalidAmount)
+        }
+    }
+
+    /// Return the form's amount, entered in dollars, as a whole number of cents.
+    pub fn amount_to_cents(&self) -> i64 {
+        (self.amount * 100.0).round() as i64
+    }
+}
+
+impl ScoreForm {
+    /// The result to record for the game with the given `game_id`
+    pub fn to_new_result(&self, game_id: i32) -> NewGameResult {
+        NewGameResult {
+            home: self.home,
+            away: self.away,
+            game_id,
+        }
+    }
+}
+
+impl MarketForm {
+    /// Build the form's market. The line is the home team's line for spreads and the total for
+    /// game and team totals; the team is only kept for team totals.
+    pub fn to_new_market(&self) -> Result<NewMarket, MarketError> {
+        let line = match self.line.trim() {
+            "" => None,
+            l => Some(l.parse::<Line>()?.0),
+        };
+        let team = match self.market {
+            MarketKind::TeamTotal => Some(self.team),
+            _ => None,
+        };
+        let new = NewMarket {
+            game_id: self.game_id,
+            kind: self.market,
+            team,
+            line: if self.market == MarketKind::Moneyline {
+                None
+            } else {
+                line
+            },
+        };
+        new.selections().map(|_| new)
+    }
+
+    /// Prices for the market's selections, home or over first.
+    pub fn prices(&self) -> [Odds; 2] {
+        [self.first_odds, self.second_odds]
     }
 }
@@ -0,0 +1,202 @@
+//! Request handlers for administering roles and permissions, and for importing schedules
+use super::DbPool;
+use crate::auth::{perm, Authorized};
+use crate::db::Retrievable;
+use crate::form::{RoleForm, ScheduleForm};
+use crate::model::import::{self, ImportError, ImportPlan};
+use crate::model::league::League;
+use crate::model::permission::{Permission, RolePermission};
+use crate::model::user::{Role, User};
+use handlebars::Handlebars;
+
+use actix_web::{get, post, web, HttpResponse, Responder};
+use serde_json::json;
+use std::collections::HashMap;
+
+/// Request handler for viewing which roles hold which permissions
+#[get("/roles")]
+async fn get_roles(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    _user: Authorized<perm::ManageUsers>,
+) -> impl Responder {
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        RolePermission::all(&conn)
+    })
+    .await
+    .map(|granted| {
+        let roles: Vec<_> = Role::ALL
+            .iter()
+            .map(|&role| {
+                let permissions: Vec<_> = Permission::ALL
+                    .iter()
+                    .map(|&permission| {
+                        json!({
+                            "permission": permission,
+                            "granted": granted
+                                .iter()
+                                .any(|g| g.role == role && g.permission == permission)
+                        })
+                    })
+                    .collect();
+                json!({ "role": role, "permissions": permissions })
+            })
+            .collect();
+        let body = hb
+            .render(
+                "roles",
+                &json!({ "roles": roles, "permissions": Permission::ALL }),
+            )
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+    .map_err(|e| {
+        let body = hb
+            .render("roles", &json!({"message": e.to_string() }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+}
+
+/// Request handler for replacing a role's permissions. Each ticked checkbox is named after the
+/// permission it grants.
+#[post("/roles/{role}/permissions")]
+async fn post_permissions(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    path: web::Path<Role>,
+    form: web::Form<HashMap<String, String>>,
+    _user: Authorized<perm::ManageUsers>,
+) -> impl Responder {
+    let permissions: Vec<Permission> = Permission::ALL
+        .iter()
+        .copied()
+        .filter(|p| form.contains_key(&format!("{:?}", p)))
+        .collect();
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        RolePermission::set(&conn, path.0, &permissions)
+    })
+    .await
+    .map(|_| {
+        let body = hb
+            .render(
+                "success",
+                &json!({"message": "permissions updated", "redirect": "/roles" }),
+            )
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+    .map_err(|e| {
+        let body = hb
+            .render("roles", &json!({"message": e.to_string() }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+}
+
+/// Request handler for giving a user a new role
+#[post("/users/{id}/role")]
+async fn post_user_role(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    path: web::Path<i32>,
+    form: web::Form<RoleForm>,
+    _user: Authorized<perm::ManageUsers>,
+) -> impl Responder {
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        User::get(&conn, path.0)?.set_role(&conn, form.role)
+    })
+    .await
+    .map(|_| {
+        let body = hb
+            .render(
+                "success",
+                &json!({"message": "role updated", "redirect": "/roles" }),
+            )
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+    .map_err(|e| {
+        let body = hb
+            .render("roles", &json!({"message": e.to_string() }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+}
+
+/// Request handler for the schedule import page
+#[get("/schedule")]
+async fn schedule_form(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    _user: Authorized<perm::ManageGames>,
+) -> impl Responder {
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        League::all(&conn)
+    })
+    .await
+    .map(|leagues| {
+        let body = hb
+            .render(
+                "schedule",
+                &json!({ "leagues": leagues, "form": { "timezone": "UTC" } }),
+            )
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+    .map_err(|e| {
+        let body = hb
+            .render("schedule", &json!({"message": e.to_string() }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+}
+
+/// Request handler for importing a schedule. Without `commit` in the form it only shows what the
+/// import would do; with it, the new games are created.
+#[post("/schedule")]
+async fn post_schedule(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    form: web::Form<ScheduleForm>,
+    _user: Authorized<perm::ManageGames>,
+) -> impl Responder {
+    let form = form.into_inner();
+    let rendered = form.clone();
+    web::block(move || -> Result<_, ImportError> {
+        let conn = pool.get().expect("Could not establish connection.");
+        let rows = import::parse(form.data.as_bytes())?;
+        let leagues = League::all(&conn)?;
+        if form.commit.is_some() {
+            let games = import::commit(&conn, &form.league, &rows, &form.timezone)?;
+            return Ok((leagues, None, Some(games.len())));
+        }
+        let plan = ImportPlan::new(&conn, &form.league, &rows, &form.timezone)?;
+        Ok((leagues, Some(plan), None))
+    })
+    .await
+    .map(|(leagues, plan, imported)| {
+        let body = match imported {
+            Some(n) => hb.render(
+                "success",
+                &json!({"message": format!("{} games imported", n), "redirect": "/games" }),
+            ),
+            None => hb.render(
+                "schedule",
+                &json!({ "leagues": leagues, "plan": plan, "form": rendered }),
+            ),
+        }
+        .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+    .map_err(|e| {
+        let body = hb
+            .render("schedule", &json!({"message": e.to_string() }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+}
@@ -0,0 +1,122 @@
+//! JSON handlers for bets. Bets are settled rather than edited or deleted.
+use super::ApiError;
+use crate::auth::{perm, Authorized, CurrentUser};
+use crate::db::Retrievable;
+use crate::form::ValidationError;
+use crate::model::bet::{Bet, BetQuery, NewBet};
+use crate::model::permission::Permission;
+use crate::model::Event;
+use crate::DbPool;
+
+use actix_web::http::StatusCode;
+use actix_web::{get, post, web, HttpResponse};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A bet to place on an event. `stake` is in cents. If `event_timestamp` is given, the bet is
+/// refused with `PriceChanged` unless that is still the event's latest version; otherwise the
+/// bet takes the latest price.
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct BetRequest {
+    pub event_id: i32,
+    pub stake: i64,
+    pub event_timestamp: Option<NaiveDateTime>,
+}
+
+/// Bets matching `?user_id=` and `?event_id=`. Users who can't view the book only see their own.
+#[utoipa::path(
+    get,
+    path = "/api/v1/bets",
+    params(BetQuery),
+    responses(
+        (status = 200, body = [Bet]),
+        (status = 401, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[get("/bets")]
+async fn get_bets(
+    pool: web::Data<DbPool>,
+    query: web::Query<BetQuery>,
+    current: CurrentUser,
+) -> Result<HttpResponse, ApiError> {
+    let bets = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        let query = match current.can(&conn, Permission::ViewBook)? {
+            true => query.0,
+            false => BetQuery {
+                user_id: Some(current.user.id),
+                ..query.0
+            },
+        };
+        Bet::query(&conn, &query)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(bets))
+}
+
+/// A bet, visible to the punter who placed it and to those who can view the book
+#[utoipa::path(
+    get,
+    path = "/api/v1/bets/{id}",
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, body = Bet),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[get("/bets/{id}")]
+async fn get_bet(
+    pool: web::Data<DbPool>,
+    path: web::Path<i32>,
+    current: CurrentUser,
+) -> Result<HttpResponse, ApiError> {
+    let bet = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        let bet = Bet::get(&conn, path.0)?;
+        match bet.user_id == current.user.id || current.can(&conn, Permission::ViewBook)? {
+            true => Ok(Some(bet)),
+            false => Ok(None),
+        }
+    })
+    .await?
+    .ok_or_else(|| ApiError::new(StatusCode::FORBIDDEN, "Forbidden", "Forbidden"))?;
+    Ok(HttpResponse::Ok().json(bet))
+}
+
+/// Place a bet for the caller
+#[utoipa::path(
+    post,
+    path = "/api/v1/bets",
+    request_body = BetRequest,
+    responses(
+        (status = 201, body = Bet),
+        (status = 403, body = ApiError),
+        (status = 422, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[post("/bets")]
+async fn post_bet(
+    pool: web::Data<DbPool>,
+    body: web::Json<BetRequest>,
+    user: Authorized<perm::PlaceBets>,
+) -> Result<HttpResponse, ApiError> {
+    if body.stake <= 0 {
+        return Err(ValidationError::InvalidStake.into());
+    }
+    let bet = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        let event = Event::latest(&conn, body.event_id)?;
+        NewBet {
+            event_timestamp: body.event_timestamp.or(Some(event.timestamp)),
+            ..NewBet::new(user.current.user.id, &event, body.stake)
+        }
+        .place(&conn)
+    })
+    .await?;
+    Ok(HttpResponse::Created().json(bet))
+}
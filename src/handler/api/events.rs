@@ -0,0 +1,216 @@
+//! JSON handlers for events
+use super::ApiError;
+use crate::auth::{perm, Authorized};
+use crate::db::{Deletable, Retrievable};
+use crate::model::market::{Line, Market, MarketKind, NewMarket, TeamSide};
+use crate::model::{Event, EventQuery, Game, LineHistory};
+use crate::odds::Odds;
+use crate::DbPool;
+
+use actix_web::{delete, get, post, put, web, HttpResponse};
+use diesel::result::Error as DieselError;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A market to open on a game. `line` is in points: the home team's line for spreads and the
+/// total for game and team totals. `team` is only needed for team totals. The first price is the
+/// home team's or the over's.
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct MarketRequest {
+    pub game_id: i32,
+    pub market: MarketKind,
+    pub team: Option<TeamSide>,
+    pub line: Option<f64>,
+    pub first_odds: Odds,
+    pub second_odds: Odds,
+}
+
+/// A selection's new price
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+pub struct PriceRequest {
+    pub odds: Odds,
+}
+
+impl MarketRequest {
+    /// The market to open, checked to have the line and team its kind needs
+    fn to_new_market(&self) -> Result<NewMarket, ApiError> {
+        let line = match self.market {
+            MarketKind::Moneyline => None,
+            _ => self.line.map(Line::from_points).transpose()?.map(|l| l.0),
+        };
+        let team = match self.market {
+            MarketKind::TeamTotal => self.team,
+            _ => None,
+        };
+        let new = NewMarket {
+            game_id: self.game_id,
+            kind: self.market,
+            team,
+            line,
+        };
+        new.selections()?;
+        Ok(new)
+    }
+}
+
+/// Events matching `?id=` and `?odds=`, every version included. Without either filter, the
+/// latest version of up to 100 events.
+#[utoipa::path(
+    get,
+    path = "/api/v1/events",
+    params(EventQuery),
+    responses(
+        (status = 200, body = [Event]),
+        (status = 400, body = ApiError),
+    )
+)]
+#[get("/events")]
+async fn get_events(
+    pool: web::Data<DbPool>,
+    query: web::Query<EventQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let events = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        match query.0 {
+            EventQuery {
+                id: None,
+                odds: None,
+            } => Event::all(&conn),
+            query => Event::query(&conn, &query),
+        }
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(events))
+}
+
+/// The latest version of an event
+#[utoipa::path(
+    get,
+    path = "/api/v1/events/{id}",
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, body = Event),
+        (status = 404, body = ApiError),
+    )
+)]
+#[get("/events/{id}")]
+async fn get_event(
+    pool: web::Data<DbPool>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    let event = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        Event::latest(&conn, path.0)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(event))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/events/{id}/history",
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, body = LineHistory),
+        (status = 404, body = ApiError),
+    )
+)]
+#[get("/events/{id}/history")]
+async fn get_event_history(
+    pool: web::Data<DbPool>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    let history = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        LineHistory::new(&Event::history(&conn, path.0)?).ok_or(DieselError::NotFound)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(history))
+}
+
+/// Open a market on a game with both its selections priced. Returns the selections.
+#[utoipa::path(
+    post,
+    path = "/api/v1/events",
+    request_body = MarketRequest,
+    responses(
+        (status = 201, body = [Event]),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 409, body = ApiError),
+        (status = 422, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[post("/events")]
+async fn post_event(
+    pool: web::Data<DbPool>,
+    body: web::Json<MarketRequest>,
+    _user: Authorized<perm::ManageMarkets>,
+) -> Result<HttpResponse, ApiError> {
+    let selections = web::block(move || -> Result<Vec<Event>, ApiError> {
+        let conn = pool.get().expect("Could not establish connection.");
+        let new = body.to_new_market()?;
+        let game = Game::get(&conn, new.game_id)?;
+        let market = Market::open(&conn, &game, &new, [body.first_odds, body.second_odds])?;
+        Ok(market.selections(&conn)?)
+    })
+    .await?;
+    Ok(HttpResponse::Created().json(selections))
+}
+
+/// Reprice an event, writing a new version of it. Bets placed against the previous version are
+/// refused.
+#[utoipa::path(
+    put,
+    path = "/api/v1/events/{id}",
+    request_body = PriceRequest,
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, body = Event),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[put("/events/{id}")]
+async fn put_event(
+    pool: web::Data<DbPool>,
+    path: web::Path<i32>,
+    body: web::Json<PriceRequest>,
+    _user: Authorized<perm::SetPrices>,
+) -> Result<HttpResponse, ApiError> {
+    let event = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        Event::latest(&conn, path.0)?.reprice(&conn, body.odds)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(event))
+}
+
+/// Delete every version of an event. Events with bets can't be deleted.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/events/{id}",
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, body = Event),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 409, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[delete("/events/{id}")]
+async fn delete_event(
+    pool: web::Data<DbPool>,
+    path: web::Path<i32>,
+    _user: Authorized<perm::ManageMarkets>,
+) -> Result<HttpResponse, ApiError> {
+    let event = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        Event::latest(&conn, path.0)?.delete(&conn)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(event))
+}
@@ -0,0 +1,190 @@
+//! JSON handlers for games
+use super::ApiError;
+use crate::auth::{perm, Authorized};
+use crate::db::{Deletable, Retrievable};
+use crate::form::StatusForm;
+use crate::model::{Game, GameQuery, GameTransition, NewGame};
+use crate::DbPool;
+
+use actix_web::{delete, get, post, put, web, HttpResponse};
+
+/// Games filtered by `?league=` and `?status=`. Without a status, games that haven't finished or
+/// been cancelled.
+#[utoipa::path(
+    get,
+    path = "/api/v1/games",
+    params(GameQuery),
+    responses(
+        (status = 200, body = [Game]),
+        (status = 400, body = ApiError),
+    )
+)]
+#[get("/games")]
+async fn get_games(
+    pool: web::Data<DbPool>,
+    query: web::Query<GameQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let games = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        Game::query(&conn, &query.0)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(games))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{id}",
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, body = Game),
+        (status = 404, body = ApiError),
+    )
+)]
+#[get("/games/{id}")]
+async fn get_game(pool: web::Data<DbPool>, path: web::Path<i32>) -> Result<HttpResponse, ApiError> {
+    let game = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        Game::get(&conn, path.0)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(game))
+}
+
+/// Create a game and open its league's default markets. Both teams must be in the team registry.
+#[utoipa::path(
+    post,
+    path = "/api/v1/games",
+    request_body = NewGame,
+    responses(
+        (status = 201, body = Game),
+        (status = 401, body = ApiError),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 409, body = ApiError),
+        (status = 422, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[post("/games")]
+async fn post_game(
+    pool: web::Data<DbPool>,
+    body: web::Json<NewGame>,
+    _user: Authorized<perm::ManageGames>,
+) -> Result<HttpResponse, ApiError> {
+    let game = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        body.schedule(&conn)
+    })
+    .await?;
+    Ok(HttpResponse::Created().json(game))
+}
+
+/// Replace a game's league, teams and start. Both teams must be in the team registry, and only
+/// scheduled or postponed games can be edited.
+#[utoipa::path(
+    put,
+    path = "/api/v1/games/{id}",
+    request_body = NewGame,
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, body = Game),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 409, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[put("/games/{id}")]
+async fn put_game(
+    pool: web::Data<DbPool>,
+    path: web::Path<i32>,
+    body: web::Json<NewGame>,
+    _user: Authorized<perm::ManageGames>,
+) -> Result<HttpResponse, ApiError> {
+    let game = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        Game::get(&conn, path.0)?.edit(&conn, &body)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(game))
+}
+
+/// Delete a game. Games with events or a result can't be deleted.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/games/{id}",
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, body = Game),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 409, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[delete("/games/{id}")]
+async fn delete_game(
+    pool: web::Data<DbPool>,
+    path: web::Path<i32>,
+    _user: Authorized<perm::ManageGames>,
+) -> Result<HttpResponse, ApiError> {
+    let game = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        Game::get(&conn, path.0)?.delete(&conn)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(game))
+}
+
+/// Move a game to a new status. Postponing or cancelling a game voids every bet on it.
+#[utoipa::path(
+    post,
+    path = "/api/v1/games/{id}/status",
+    request_body = StatusForm,
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, body = Game),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 409, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[post("/games/{id}/status")]
+async fn post_game_status(
+    pool: web::Data<DbPool>,
+    path: web::Path<i32>,
+    body: web::Json<StatusForm>,
+    user: Authorized<perm::ManageGames>,
+) -> Result<HttpResponse, ApiError> {
+    let game = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        Game::get(&conn, path.0)?.transition(&conn, body.status, Some(user.current.user.id))
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(game))
+}
+
+/// Every change of a game's status, oldest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/games/{id}/transitions",
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, body = [GameTransition]),
+        (status = 404, body = ApiError),
+    )
+)]
+#[get("/games/{id}/transitions")]
+async fn get_game_transitions(
+    pool: web::Data<DbPool>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    let transitions = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        Game::get(&conn, path.0)?.transitions(&conn)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(transitions))
+}
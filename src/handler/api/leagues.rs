@@ -0,0 +1,193 @@
+//! JSON handlers for leagues and the market templates opened on their new games
+use super::ApiError;
+use crate::auth::{perm, Authorized};
+use crate::db::{Creatable, Deletable, Retrievable, Updatable};
+use crate::model::league::{League, LeagueQuery, MarketTemplate, NewMarketTemplate};
+use crate::DbPool;
+
+use actix_web::{delete, get, post, put, web, HttpResponse};
+
+/// Leagues, optionally filtered by `?active=`
+#[utoipa::path(
+    get,
+    path = "/api/v1/leagues",
+    params(LeagueQuery),
+    responses(
+        (status = 200, body = [League]),
+        (status = 400, body = ApiError),
+    )
+)]
+#[get("/leagues")]
+async fn get_leagues(
+    pool: web::Data<DbPool>,
+    query: web::Query<LeagueQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let leagues = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        League::query(&conn, &query.0)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(leagues))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/leagues/{code}",
+    params(("code" = String, Path)),
+    responses(
+        (status = 200, body = League),
+        (status = 404, body = ApiError),
+    )
+)]
+#[get("/leagues/{code}")]
+async fn get_league(
+    pool: web::Data<DbPool>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let league = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        League::get(&conn, &path.0)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(league))
+}
+
+/// Add a league. Its teams are added through `POST /api/v1/teams`.
+#[utoipa::path(
+    post,
+    path = "/api/v1/leagues",
+    request_body = League,
+    responses(
+        (status = 201, body = League),
+        (status = 403, body = ApiError),
+        (status = 409, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[post("/leagues")]
+async fn post_league(
+    pool: web::Data<DbPool>,
+    body: web::Json<League>,
+    _user: Authorized<perm::ManageGames>,
+) -> Result<HttpResponse, ApiError> {
+    let league = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        body.create(&conn)
+    })
+    .await?;
+    Ok(HttpResponse::Created().json(league))
+}
+
+/// Change a league's settings. The code in the body is ignored.
+#[utoipa::path(
+    put,
+    path = "/api/v1/leagues/{code}",
+    request_body = League,
+    params(("code" = String, Path)),
+    responses(
+        (status = 200, body = League),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[put("/leagues/{code}")]
+async fn put_league(
+    pool: web::Data<DbPool>,
+    path: web::Path<String>,
+    body: web::Json<League>,
+    _user: Authorized<perm::ManageGames>,
+) -> Result<HttpResponse, ApiError> {
+    let league = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        League {
+            code: League::get(&conn, &path.0)?.code,
+            ..body.0
+        }
+        .update(&conn)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(league))
+}
+
+/// The markets opened on every new game in a league
+#[utoipa::path(
+    get,
+    path = "/api/v1/leagues/{code}/templates",
+    params(("code" = String, Path)),
+    responses(
+        (status = 200, body = [MarketTemplate]),
+        (status = 404, body = ApiError),
+    )
+)]
+#[get("/leagues/{code}/templates")]
+async fn get_templates(
+    pool: web::Data<DbPool>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let templates = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        League::get(&conn, &path.0)?.templates(&conn)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(templates))
+}
+
+/// Open a market on every new game in a league. The league in the body is ignored.
+#[utoipa::path(
+    post,
+    path = "/api/v1/leagues/{code}/templates",
+    request_body = NewMarketTemplate,
+    params(("code" = String, Path)),
+    responses(
+        (status = 201, body = MarketTemplate),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 422, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[post("/leagues/{code}/templates")]
+async fn post_template(
+    pool: web::Data<DbPool>,
+    path: web::Path<String>,
+    body: web::Json<NewMarketTemplate>,
+    _user: Authorized<perm::ManageMarkets>,
+) -> Result<HttpResponse, ApiError> {
+    let template = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        NewMarketTemplate {
+            league: League::get(&conn, &path.0)?.code,
+            ..body.0
+        }
+        .create(&conn)
+    })
+    .await?;
+    Ok(HttpResponse::Created().json(template))
+}
+
+/// Stop opening a market on new games. Markets already opened from the template are kept.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/templates/{id}",
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, body = MarketTemplate),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[delete("/templates/{id}")]
+async fn delete_template(
+    pool: web::Data<DbPool>,
+    path: web::Path<i32>,
+    _user: Authorized<perm::ManageMarkets>,
+) -> Result<HttpResponse, ApiError> {
+    let template = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        MarketTemplate::get(&conn, path.0)?.delete(&conn)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(template))
+}
@@ -0,0 +1,360 @@
+//! JSON API
+//!
+//! A versioned JSON mirror of the HTML handlers, mounted at `/api/v1`. Clients authenticate with
+//! a bearer token from `POST /api/token`. Every error, including malformed bodies, query strings
+//! and paths, comes back as an `ApiError` body such as
+//! `{"error": "NotFound", "message": "Record not found"}`.
+//!
+//! The API is described by an OpenAPI 3 document served at `/api/openapi.json`. It is generated
+//! from the handlers' `utoipa::path` attributes and the request and response types, so every
+//! handler added here must also be listed in `ApiDoc`.
+pub mod bets;
+pub mod events;
+pub mod games;
+pub mod leagues;
+pub mod results;
+pub mod teams;
+pub mod users;
+
+use super::token;
+use crate::auth::{RefreshForm, TokenPair};
+use crate::form::{AuthError, LoginForm, RoleForm, SignupForm, StatusForm, ValidationError};
+use crate::model::bet::{Bet, BetError, BetStatus};
+use crate::model::game_result::{GameResult, NewGameResult, ResultCorrection, ResultError};
+use crate::model::league::{League, MarketTemplate, NewMarketTemplate};
+use crate::model::market::{MarketError, MarketKind, TeamSide, TotalSide};
+use crate::model::settlement::SettlementError;
+use crate::model::team::Team;
+use crate::model::user::{Role, User};
+use crate::model::{
+    Event, Game, GameError, GameStatus, GameTransition, LineHistory, NewGame, PricePoint,
+    PriceSeries,
+};
+use crate::odds::Odds;
+
+use actix_web::error::BlockingError;
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpResponse, ResponseError, Scope};
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use serde::Serialize;
+use std::fmt;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{Components, OpenApi as OpenApiDocument};
+use utoipa::{Modify, OpenApi, ToSchema};
+
+/// The body of every error response from the API. `error` is a stable code clients can match
+/// on; `message` is for people.
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct ApiError {
+    #[serde(skip)]
+    pub status: StatusCode,
+    pub error: String,
+    pub message: String,
+}
+
+/// The OpenAPI document describing the JSON API and the token endpoints
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        token::post_token,
+        token::refresh_token,
+        token::revoke_token,
+        games::get_games,
+        games::get_game,
+        games::post_game,
+        games::put_game,
+        games::delete_game,
+        games::post_game_status,
+        games::get_game_transitions,
+        leagues::get_leagues,
+        leagues::get_league,
+        leagues::post_league,
+        leagues::put_league,
+        leagues::get_templates,
+        leagues::post_template,
+        leagues::delete_template,
+        teams::get_teams,
+        teams::get_team,
+        teams::post_team,
+        teams::put_team,
+        events::get_events,
+        events::get_event,
+        events::get_event_history,
+        events::post_event,
+        events::put_event,
+        events::delete_event,
+        results::get_results,
+        results::get_result,
+        results::post_result,
+        results::put_result,
+        results::get_result_corrections,
+        users::get_me,
+        users::get_users,
+        users::get_user,
+        users::post_user,
+        users::put_user_role,
+        users::delete_user,
+        bets::get_bets,
+        bets::get_bet,
+        bets::post_bet,
+    ),
+    components(schemas(
+        ApiError,
+        Odds,
+        League,
+        MarketTemplate,
+        NewMarketTemplate,
+        Team,
+        Game,
+        NewGame,
+        GameStatus,
+        GameTransition,
+        StatusForm,
+        MarketKind,
+        TeamSide,
+        TotalSide,
+        Event,
+        events::MarketRequest,
+        events::PriceRequest,
+        LineHistory,
+        PricePoint,
+        PriceSeries,
+        GameResult,
+        NewGameResult,
+        ResultCorrection,
+        Role,
+        User,
+        SignupForm,
+        RoleForm,
+        LoginForm,
+        RefreshForm,
+        TokenPair,
+        BetStatus,
+        Bet,
+        bets::BetRequest,
+    )),
+    modifiers(&BearerToken)
+)]
+pub struct ApiDoc;
+
+/// Registers the `bearer_token` security scheme used by handlers that need a logged in user
+struct BearerToken;
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+/////// Implementations ///////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl ApiError {
+    pub fn new(status: StatusCode, error: impl ToString, message: impl ToString) -> Self {
+        ApiError {
+            status,
+            error: error.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// The request's body, query string or path couldn't be parsed.
+    pub fn bad_request(message: impl ToString) -> Self {
+        ApiError::new(StatusCode::BAD_REQUEST, "BadRequest", message)
+    }
+
+    /// An error whose details shouldn't reach the client.
+    pub fn internal() -> Self {
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "InternalError",
+            "Internal server error",
+        )
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.error, self.message)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status).json(self)
+    }
+}
+
+impl From<DieselError> for ApiError {
+    fn from(e: DieselError) -> Self {
+        match e {
+            DieselError::NotFound => ApiError::new(StatusCode::NOT_FOUND, "NotFound", e),
+            DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => {
+                ApiError::new(StatusCode::CONFLICT, "Conflict", "Record already exists")
+            }
+            DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _) => ApiError::new(
+                StatusCode::CONFLICT,
+                "Conflict",
+                "Record refers to, or is referred to by, another record",
+            ),
+            _ => ApiError::internal(),
+        }
+    }
+}
+
+impl From<ValidationError> for ApiError {
+    fn from(e: ValidationError) -> Self {
+        ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, &e, "Invalid request")
+    }
+}
+
+impl From<AuthError> for ApiError {
+    fn from(e: AuthError) -> Self {
+        match e {
+            AuthError::EmailTaken => {
+                ApiError::new(StatusCode::CONFLICT, &e, "Email or username taken")
+            }
+            _ => ApiError::new(StatusCode::UNAUTHORIZED, &e, "Incorrect credentials"),
+        }
+    }
+}
+
+impl From<BetError> for ApiError {
+    fn from(e: BetError) -> Self {
+        match e {
+            BetError::Db(e) => ApiError::from(e),
+            _ => ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, &e, "Bet refused"),
+        }
+    }
+}
+
+impl From<ResultError> for ApiError {
+    fn from(e: ResultError) -> Self {
+        match e {
+            ResultError::Settlement(e) => ApiError::from(e),
+            _ => ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, &e, "Invalid score"),
+        }
+    }
+}
+
+impl From<GameError> for ApiError {
+    fn from(e: GameError) -> Self {
+        match e {
+            GameError::Settlement(e) => ApiError::from(e),
+            GameError::InvalidTransition(..) => {
+                ApiError::new(StatusCode::CONFLICT, "InvalidTransition", e)
+            }
+            GameError::MissingResult => ApiError::new(
+                StatusCode::CONFLICT,
+                &e,
+                "Record the result to make a game final",
+            ),
+            GameError::Locked(_) => ApiError::new(StatusCode::CONFLICT, "Locked", e),
+        }
+    }
+}
+
+impl From<MarketError> for ApiError {
+    fn from(e: MarketError) -> Self {
+        match e {
+            MarketError::Db(e) => ApiError::from(e),
+            MarketError::InvalidLine(_) => {
+                ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "InvalidLine", e)
+            }
+            _ => ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, &e, "Invalid market"),
+        }
+    }
+}
+
+impl From<SettlementError> for ApiError {
+    fn from(e: SettlementError) -> Self {
+        match e {
+            SettlementError::Db(e) => ApiError::from(e),
+            SettlementError::InconsistentMarket(_) => {
+                ApiError::new(StatusCode::CONFLICT, "InconsistentMarket", e)
+            }
+        }
+    }
+}
+
+impl<E> From<BlockingError<E>> for ApiError
+where
+    E: fmt::Debug,
+    ApiError: From<E>,
+{
+    fn from(e: BlockingError<E>) -> Self {
+        match e {
+            BlockingError::Error(e) => ApiError::from(e),
+            BlockingError::Canceled => ApiError::internal(),
+        }
+    }
+}
+
+impl Modify for BearerToken {
+    fn modify(&self, openapi: &mut OpenApiDocument) {
+        openapi
+            .components
+            .get_or_insert_with(Components::new)
+            .add_security_scheme(
+                "bearer_token",
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .bearer_format("JWT")
+                        .build(),
+                ),
+            );
+    }
+}
+
+/// Request handler for the OpenAPI document
+#[get("/api/openapi.json")]
+async fn get_openapi() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+/// The `/api/v1` scope, with extractor errors reported as `ApiError`s.
+pub fn scope() -> Scope {
+    web::scope("/api/v1")
+        .app_data(web::JsonConfig::default().error_handler(|e, _| ApiError::bad_request(e).into()))
+        .app_data(web::QueryConfig::default().error_handler(|e, _| ApiError::bad_request(e).into()))
+        .app_data(web::PathConfig::default().error_handler(|e, _| ApiError::bad_request(e).into()))
+        .service(games::get_games)
+        .service(games::get_game)
+        .service(games::post_game)
+        .service(games::put_game)
+        .service(games::delete_game)
+        .service(games::post_game_status)
+        .service(games::get_game_transitions)
+        .service(leagues::get_leagues)
+        .service(leagues::get_league)
+        .service(leagues::post_league)
+        .service(leagues::put_league)
+        .service(leagues::get_templates)
+        .service(leagues::post_template)
+        .service(leagues::delete_template)
+        .service(teams::get_teams)
+        .service(teams::get_team)
+        .service(teams::post_team)
+        .service(teams::put_team)
+        .service(events::get_events)
+        .service(events::get_event)
+        .service(events::get_event_history)
+        .service(events::post_event)
+        .service(events::put_event)
+        .service(events::delete_event)
+        .service(results::get_results)
+        .service(results::get_result)
+        .service(results::post_result)
+        .service(results::put_result)
+        .service(results::get_result_corrections)
+        .service(users::get_me)
+        .service(users::get_users)
+        .service(users::get_user)
+        .service(users::post_user)
+        .service(users::put_user_role)
+        .service(users::delete_user)
+        .service(bets::get_bets)
+        .service(bets::get_bet)
+        .service(bets::post_bet)
+}
@@ -0,0 +1,136 @@
+//! JSON handlers for game results. Recording or correcting a result settles the game's bets, so
+//! results can't be deleted; corrections are kept as an audit trail.
+use super::ApiError;
+use crate::auth::{perm, Authorized};
+use crate::db::{Creatable, Retrievable};
+use crate::model::game_result::{GameResult, GameResultQuery, NewGameResult, ResultCorrection};
+use crate::DbPool;
+
+use actix_web::{get, post, put, web, HttpResponse};
+
+/// Results, optionally filtered by `?game_id=`
+#[utoipa::path(
+    get,
+    path = "/api/v1/results",
+    params(GameResultQuery),
+    responses(
+        (status = 200, body = [GameResult]),
+        (status = 400, body = ApiError),
+    )
+)]
+#[get("/results")]
+async fn get_results(
+    pool: web::Data<DbPool>,
+    query: web::Query<GameResultQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let results = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        GameResult::query(&conn, &query.0)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(results))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/results/{id}",
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, body = GameResult),
+        (status = 404, body = ApiError),
+    )
+)]
+#[get("/results/{id}")]
+async fn get_result(
+    pool: web::Data<DbPool>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    let result = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        GameResult::get(&conn, path.0)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(result))
+}
+
+/// Record a game's final score and settle its bets
+#[utoipa::path(
+    post,
+    path = "/api/v1/results",
+    request_body = NewGameResult,
+    responses(
+        (status = 201, body = GameResult),
+        (status = 403, body = ApiError),
+        (status = 409, body = ApiError),
+        (status = 422, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[post("/results")]
+async fn post_result(
+    pool: web::Data<DbPool>,
+    body: web::Json<NewGameResult>,
+    _user: Authorized<perm::ManageGames>,
+) -> Result<HttpResponse, ApiError> {
+    let result = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        body.create(&conn)
+    })
+    .await?;
+    Ok(HttpResponse::Created().json(result))
+}
+
+/// Correct a game's final score, recording the correction, and re-settle its bets
+#[utoipa::path(
+    put,
+    path = "/api/v1/results/{id}",
+    request_body = NewGameResult,
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, body = GameResult),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 422, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[put("/results/{id}")]
+async fn put_result(
+    pool: web::Data<DbPool>,
+    path: web::Path<i32>,
+    body: web::Json<NewGameResult>,
+    user: Authorized<perm::ManageGames>,
+) -> Result<HttpResponse, ApiError> {
+    let result = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        GameResult::get(&conn, path.0)?.correct(&conn, body.home, body.away, user.current.user.id)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(result))
+}
+
+/// Every correction made to a result, oldest first
+#[utoipa::path(
+    get,
+    path = "/api/v1/results/{id}/corrections",
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, body = [ResultCorrection]),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[get("/results/{id}/corrections")]
+async fn get_result_corrections(
+    pool: web::Data<DbPool>,
+    path: web::Path<i32>,
+    _user: Authorized<perm::ManageGames>,
+) -> Result<HttpResponse, ApiError> {
+    let corrections = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        GameResult::get(&conn, path.0)?.corrections(&conn)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(corrections))
+}
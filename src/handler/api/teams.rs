@@ -0,0 +1,125 @@
+//! JSON handlers for the team registry
+use super::ApiError;
+use crate::auth::{perm, Authorized};
+use crate::db::{Creatable, Retrievable, Updatable};
+use crate::model::team::{Team, TeamQuery};
+use crate::DbPool;
+
+use actix_web::{get, post, put, web, HttpResponse};
+
+/// Teams matching `?league=` and `?active=`
+#[utoipa::path(
+    get,
+    path = "/api/v1/teams",
+    params(TeamQuery),
+    responses(
+        (status = 200, body = [Team]),
+        (status = 400, body = ApiError),
+    )
+)]
+#[get("/teams")]
+async fn get_teams(
+    pool: web::Data<DbPool>,
+    query: web::Query<TeamQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let teams = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        Team::query(&conn, &query.0)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(teams))
+}
+
+/// Look a team up by its abbreviation, full name or one of its aliases, ignoring case. Use it to
+/// turn an abbreviation into a name or a name into an abbreviation.
+#[utoipa::path(
+    get,
+    path = "/api/v1/teams/{league}/{team}",
+    params(
+        ("league" = String, Path),
+        ("team" = String, Path, description = "Abbreviation, full name or alias"),
+    ),
+    responses(
+        (status = 200, body = Team),
+        (status = 404, body = ApiError),
+    )
+)]
+#[get("/teams/{league}/{team}")]
+async fn get_team(
+    pool: web::Data<DbPool>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, ApiError> {
+    let team = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        let (league, name) = path.into_inner();
+        Team::lookup(&conn, &league, &name)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(team))
+}
+
+/// Add a team to a league
+#[utoipa::path(
+    post,
+    path = "/api/v1/teams",
+    request_body = Team,
+    responses(
+        (status = 201, body = Team),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 409, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[post("/teams")]
+async fn post_team(
+    pool: web::Data<DbPool>,
+    body: web::Json<Team>,
+    _user: Authorized<perm::ManageGames>,
+) -> Result<HttpResponse, ApiError> {
+    let team = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        body.create(&conn)
+    })
+    .await?;
+    Ok(HttpResponse::Created().json(team))
+}
+
+/// Rename a team, change its aliases or retire it. The league and abbreviation in the body are
+/// ignored.
+#[utoipa::path(
+    put,
+    path = "/api/v1/teams/{league}/{team}",
+    request_body = Team,
+    params(
+        ("league" = String, Path),
+        ("team" = String, Path, description = "Abbreviation"),
+    ),
+    responses(
+        (status = 200, body = Team),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[put("/teams/{league}/{team}")]
+async fn put_team(
+    pool: web::Data<DbPool>,
+    path: web::Path<(String, String)>,
+    body: web::Json<Team>,
+    _user: Authorized<perm::ManageGames>,
+) -> Result<HttpResponse, ApiError> {
+    let team = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        let (league, abbreviation) = path.into_inner();
+        let current = Team::get(&conn, &league, &abbreviation)?;
+        Team {
+            league: current.league,
+            abbreviation: current.abbreviation,
+            ..body.0
+        }
+        .update(&conn)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(team))
+}
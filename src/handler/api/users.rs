@@ -0,0 +1,157 @@
+//! JSON handlers for users. Password hashes are never serialized.
+use super::ApiError;
+use crate::auth::{perm, Authorized, CurrentUser};
+use crate::db::{Creatable, Deletable, Retrievable};
+use crate::form::{RoleForm, SignupForm};
+use crate::model::permission::Permission;
+use crate::model::user::User;
+use crate::DbPool;
+
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, put, web, HttpResponse};
+
+/// The caller
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/me",
+    responses(
+        (status = 200, body = User),
+        (status = 401, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[get("/users/me")]
+async fn get_me(current: CurrentUser) -> HttpResponse {
+    HttpResponse::Ok().json(current.user)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/users",
+    responses(
+        (status = 200, body = [User]),
+        (status = 403, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[get("/users")]
+async fn get_users(
+    pool: web::Data<DbPool>,
+    _user: Authorized<perm::ViewAccounts>,
+) -> Result<HttpResponse, ApiError> {
+    let users = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        User::all(&conn)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(users))
+}
+
+/// A user, visible to themselves and to those who can view accounts
+#[utoipa::path(
+    get,
+    path = "/api/v1/users/{id}",
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, body = User),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[get("/users/{id}")]
+async fn get_user(
+    pool: web::Data<DbPool>,
+    path: web::Path<i32>,
+    current: CurrentUser,
+) -> Result<HttpResponse, ApiError> {
+    let user = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        if path.0 != current.user.id && !current.can(&conn, Permission::ViewAccounts)? {
+            return Ok(None);
+        }
+        User::get(&conn, path.0).map(Some)
+    })
+    .await?
+    .ok_or_else(|| ApiError::new(StatusCode::FORBIDDEN, "Forbidden", "Forbidden"))?;
+    Ok(HttpResponse::Ok().json(user))
+}
+
+/// Sign up. New users are punters.
+#[utoipa::path(
+    post,
+    path = "/api/v1/users",
+    request_body = SignupForm,
+    responses(
+        (status = 201, body = User),
+        (status = 409, body = ApiError),
+        (status = 422, body = ApiError),
+    )
+)]
+#[post("/users")]
+async fn post_user(
+    pool: web::Data<DbPool>,
+    body: web::Json<SignupForm>,
+) -> Result<HttpResponse, ApiError> {
+    let user = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        let new = body.0.validate()?.authenticate(&conn)?;
+        new.create(&conn).map_err(ApiError::from)
+    })
+    .await?;
+    Ok(HttpResponse::Created().json(user))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/users/{id}/role",
+    request_body = RoleForm,
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, body = User),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[put("/users/{id}/role")]
+async fn put_user_role(
+    pool: web::Data<DbPool>,
+    path: web::Path<i32>,
+    body: web::Json<RoleForm>,
+    _user: Authorized<perm::ManageUsers>,
+) -> Result<HttpResponse, ApiError> {
+    let user = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        User::get(&conn, path.0)?.set_role(&conn, body.role)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(user))
+}
+
+/// Delete a user. Users with sessions, bets or a wallet can't be deleted.
+#[utoipa::path(
+    delete,
+    path = "/api/v1/users/{id}",
+    params(("id" = i32, Path)),
+    responses(
+        (status = 200, body = User),
+        (status = 403, body = ApiError),
+        (status = 404, body = ApiError),
+        (status = 409, body = ApiError),
+    ),
+    security(("bearer_token" = []))
+)]
+#[delete("/users/{id}")]
+async fn delete_user(
+    pool: web::Data<DbPool>,
+    path: web::Path<i32>,
+    _user: Authorized<perm::ManageUsers>,
+) -> Result<HttpResponse, ApiError> {
+    let user = web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        User::get(&conn, path.0)?.delete(&conn)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(user))
+}
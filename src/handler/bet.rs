@@ -0,0 +1,159 @@
+//! Request handlers for placing and viewing bets
+use super::DbPool;
+use crate::auth::{perm, Authorized, CurrentUser};
+use crate::db::Retrievable;
+use crate::form::{BetForm, ParlayForm};
+use crate::model::bet::{Bet, BetQuery, BetStatus, NewBet};
+use crate::model::closing::closing_line_values;
+use crate::model::parlay::{NewParlay, ParlayLeg, ParlayLegQuery};
+use crate::model::permission::Permission;
+use crate::model::Event;
+use handlebars::Handlebars;
+
+use actix_web::{get, post, web, HttpResponse, Responder};
+use diesel::result::Error as DieselError;
+use serde_json::{json, Value};
+
+/// Request handler for the current user placing a bet on an Event from form data
+#[post("/events/{id}/bet")]
+async fn post_bet(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    form: web::Form<BetForm>,
+    path: web::Path<i32>,
+    user: Authorized<perm::PlaceBets>,
+) -> impl Responder {
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        let form = form.0.validate().map_err(|e| e.to_string())?;
+        let event = Event::latest(&conn, path.0).map_err(|e| e.to_string())?;
+        NewBet::new(user.current.user.id, &event, form.stake_to_cents())
+            .place(&conn)
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map(|bet| {
+        let body = hb
+            .render(
+                "success",
+                &json!({
+                    "message": "bet placed",
+                    "redirect": format!("/bets?user_id={}", bet.user_id)
+                }),
+            )
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+    .map_err(|e| {
+        let body = hb
+            .render("bets", &json!({"message": e.to_string() }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+}
+
+/// Request handler for the current user placing a parlay on several Events from form data
+#[post("/parlays")]
+async fn post_parlay(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    form: web::Form<ParlayForm>,
+    user: Authorized<perm::PlaceBets>,
+) -> impl Responder {
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        let form = form.0.validate().map_err(|e| e.to_string())?;
+        NewParlay {
+            user_id: user.current.user.id,
+            stake: form.stake_to_cents(),
+            event_ids: form.event_ids().map_err(|e| e.to_string())?,
+        }
+        .place(&conn)
+        .map_err(|e| e.to_string())
+    })
+    .await
+    .map(|bet| {
+        let body = hb
+            .render(
+                "success",
+                &json!({
+                    "message": "parlay placed",
+                    "redirect": format!("/bets?user_id={}", bet.user_id)
+                }),
+            )
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+    .map_err(|e| {
+        let body = hb
+            .render("bets", &json!({"message": e.to_string() }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+}
+
+/// Request handler for retrieving a user's bets, with the legs of any parlays and the
+/// closing-line value of bets whose selections have closed. Users who can't view the book only
+/// see their own bets.
+#[get("/bets")]
+async fn get_bets(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    query: web::Query<BetQuery>,
+    current: CurrentUser,
+) -> impl Responder {
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        let query = match current.can(&conn, Permission::ViewBook)? {
+            true => query.0,
+            false => BetQuery {
+                user_id: Some(current.user.id),
+                ..query.0
+            },
+        };
+        let bets = Bet::query(&conn, &query)?;
+        // Only settled bets are measured, as in the CLV report
+        let settled: Vec<Bet> = bets
+            .iter()
+            .filter(|b| b.status != BetStatus::Open)
+            .cloned()
+            .collect();
+        let clv = closing_line_values(&conn, &settled)?;
+        let average = match clv.len() {
+            0 => None,
+            n => Some(clv.values().sum::<f64>() / n as f64),
+        };
+        let bets = bets
+            .into_iter()
+            .map(|bet| {
+                let legs = match bet.is_parlay() {
+                    true => ParlayLeg::query(
+                        &conn,
+                        &ParlayLegQuery {
+                            bet_id: Some(bet.id),
+                            event_id: None,
+                        },
+                    )?,
+                    false => Vec::new(),
+                };
+                let clv = clv.get(&bet.id).map(|v| format!("{:+.1}", v * 100.0));
+                Ok(json!({ "bet": bet, "legs": legs, "clv": clv }))
+            })
+            .collect::<Result<Vec<Value>, DieselError>>()?;
+        Ok::<_, DieselError>((bets, average))
+    })
+    .await
+    .map(|(bets, average)| {
+        let average = average.map(|v| format!("{:+.1}", v * 100.0));
+        let body = hb
+            .render("bets", &json!({ "bets": bets, "average_clv": average }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+    .map_err(|e| {
+        let body = hb
+            .render("bets", &json!({"message": e.to_string() }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+}
@@ -0,0 +1,166 @@
+//! Request handlers for markets
+use super::DbPool;
+use crate::auth::{perm, Authorized};
+use crate::db::{Creatable, Retrievable, Updatable};
+use crate::form::PolicyForm;
+use crate::model::market::Market;
+use crate::model::pricing::{PriceMove, PriceMoveQuery, PricingPolicy};
+use handlebars::Handlebars;
+
+use actix_web::{get, post, web, HttpResponse, Responder};
+use diesel::result::Error as DieselError;
+use serde_json::json;
+
+/// Request handler for viewing a market's selections, margin, liability and pricing policy
+#[get("/markets/{id}")]
+async fn get_market(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    path: web::Path<i32>,
+    _user: Authorized<perm::ViewBook>,
+) -> impl Responder {
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        let market = Market::get(&conn, path.0)?;
+        let margin = market.margin(&conn)?;
+        let liability = market.liability(&conn)?;
+        let policy = PricingPolicy::for_market(&conn, market.id)?;
+        let moves = PriceMove::query(
+            &conn,
+            &PriceMoveQuery {
+                market_id: Some(market.id),
+            },
+        )?;
+        Ok::<_, DieselError>((market, margin, liability, policy, moves))
+    })
+    .await
+    .map(|(market, margin, liability, policy, moves)| {
+        let body = hb
+            .render(
+                "market",
+                &json!({
+                    "market": market,
+                    "margin": format!("{:.2}%", margin * 100.0),
+                    "liability": liability,
+                    "policy": policy,
+                    "moves": moves
+                }),
+            )
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+    .map_err(|e| {
+        let body = hb
+            .render("market", &json!({"message": e.to_string() }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+}
+
+/// Suspend or resume betting on the market with the given id.
+async fn set_suspended(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    id: i32,
+    suspended: bool,
+) -> HttpResponse {
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        Market {
+            suspended,
+            voided: false,
+            ..Market::get(&conn, id)?
+        }
+        .update(&conn)
+    })
+    .await
+    .map(|market| {
+        let message = if market.suspended {
+            "market suspended"
+        } else {
+            "market resumed"
+        };
+        let body = hb
+            .render(
+                "success",
+                &json!({
+                    "message": message,
+                    "redirect": format!("/markets/{}", market.id)
+                }),
+            )
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+    .unwrap_or_else(|e| {
+        let body = hb
+            .render("market", &json!({"message": e.to_string() }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+}
+
+/// Request handler for setting a market's pricing policy
+#[post("/markets/{id}/policy")]
+async fn post_policy(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    path: web::Path<i32>,
+    form: web::Form<PolicyForm>,
+    _user: Authorized<perm::SetPrices>,
+) -> impl Responder {
+    let form = match form.0.validate() {
+        Ok(form) => form,
+        Err(e) => {
+            let body = hb
+                .render("market", &json!({"message": e.to_string() }))
+                .unwrap();
+            return HttpResponse::Ok().body(body);
+        }
+    };
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        let market = Market::get(&conn, path.0)?;
+        form.to_new_policy(market.id).create(&conn)
+    })
+    .await
+    .map(|policy| {
+        let body = hb
+            .render(
+                "success",
+                &json!({
+                    "message": "pricing policy saved",
+                    "redirect": format!("/markets/{}", policy.market_id)
+                }),
+            )
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+    .unwrap_or_else(|e| {
+        let body = hb
+            .render("market", &json!({"message": e.to_string() }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+}
+
+/// Request handler for suspending betting on a market
+#[post("/markets/{id}/suspend")]
+async fn suspend_market(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    path: web::Path<i32>,
+    _user: Authorized<perm::ManageMarkets>,
+) -> impl Responder {
+    set_suspended(pool, hb, path.0, true).await
+}
+
+/// Request handler for reopening a suspended market
+#[post("/markets/{id}/resume")]
+async fn resume_market(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    path: web::Path<i32>,
+    _user: Authorized<perm::ManageMarkets>,
+) -> impl Responder {
+    set_suspended(pool, hb, path.0, false).await
+}
@@ -1,12 +1,24 @@
 //! Request handlers for games and events
+pub mod admin;
+pub mod api;
+pub mod bet;
+pub mod market;
+pub mod report;
+pub mod token;
 pub mod user;
+pub mod wallet;
 
-use super::form::GameForm;
-use super::model::{Event, Game, GameQuery, League, NewEvent, NewGame};
+use super::form::{GameForm, MarketForm, OddsForm, ScoreForm, StatusForm};
+use super::model::game_result::GameResult;
+use super::model::league::League;
+use super::model::market::Market;
+use super::model::team::{Team, TeamQuery};
+use super::model::{Event, Game, GameQuery, LineHistory, NewGame};
 use super::DbPool;
-use super::{NBA_TEAMS, NFL_TEAMS};
+use crate::auth::{perm, Authorized};
 use crate::db::{Creatable, Retrievable};
 use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use diesel::result::Error as DieselError;
 use handlebars::Handlebars;
 use serde_json::json;
 
@@ -35,13 +47,15 @@ async fn get_games(
     })
 }
 
-/// Request handler for posting a new Game from a form
+/// Request handler for posting a new Game from a form. The league's default markets are opened
+/// on the new game.
 #[post("/games/{league}/form")]
 async fn post_game(
     pool: web::Data<DbPool>,
     hb: web::Data<Handlebars<'_>>,
     form: web::Form<GameForm>,
-    path: web::Path<League>,
+    path: web::Path<String>,
+    _user: Authorized<perm::ManageGames>,
 ) -> impl Responder {
     web::block(move || {
         let conn = pool.get().expect("Could not establish connection.");
@@ -49,9 +63,9 @@ async fn post_game(
             league: path.0,
             home: form.home.to_string(),
             away: form.away.to_owned(),
-            start: form.start_to_naive(),
+            start: form.start_to_naive().map_err(|e| e.to_string())?,
         };
-        new.create(&conn)
+        new.schedule(&conn).map_err(|e| e.to_string())
     })
     .await
     .map(|_| {
@@ -71,19 +85,146 @@ async fn post_game(
     })
 }
 
-/// Request handler for retrieving the form to create a new Game
+/// Request handler for retrieving the form to create a new Game in any configured league, between
+/// its active teams
 #[get("/games/{league}/form")]
 async fn games_form(
+    pool: web::Data<DbPool>,
     hb: web::Data<Handlebars<'_>>,
     _req: HttpRequest,
-    path: web::Path<League>,
+    path: web::Path<String>,
+    _user: Authorized<perm::ManageGames>,
 ) -> impl Responder {
-    let teams: Vec<(&str, &str)> = match path.0 {
-        League::NBA => NBA_TEAMS.to_vec(),
-        League::NFL => NFL_TEAMS.to_vec(),
-    };
-    let body = hb.render("game_form", &json!({ "teams": teams })).unwrap();
-    HttpResponse::Ok().body(body)
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        let league = League::get(&conn, &path.0)?;
+        let teams = Team::query(
+            &conn,
+            &TeamQuery {
+                league: Some(league.code.clone()),
+                active: Some(true),
+            },
+        )?;
+        Ok::<_, DieselError>((league, teams))
+    })
+    .await
+    .map(|(league, teams)| {
+        let body = hb
+            .render("game_form", &json!({ "league": league, "teams": teams }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+    .map_err(|e| {
+        let body = hb
+            .render("game_form", &json!({"message": e.to_string() }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+}
+
+/// Request handler for the form to enter, or correct, a Game's final score
+#[get("/games/{id}/result")]
+async fn result_form(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    path: web::Path<i32>,
+    _user: Authorized<perm::ManageGames>,
+) -> impl Responder {
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        let game = Game::get(&conn, path.0)?;
+        let result = GameResult::for_game(&conn, game.id)?;
+        let corrections = match result {
+            Some(result) => result.corrections(&conn)?,
+            None => Vec::new(),
+        };
+        Ok::<_, DieselError>((game, result, corrections))
+    })
+    .await
+    .map(|(game, result, corrections)| {
+        let body = hb
+            .render(
+                "result_form",
+                &json!({ "game": game, "result": result, "corrections": corrections }),
+            )
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+    .map_err(|e| {
+        let body = hb
+            .render("result_form", &json!({"message": e.to_string() }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+}
+
+/// Request handler for entering a Game's final score. Entering a score for a game that already
+/// has one corrects it. Either way the game's bets are settled.
+#[post("/games/{id}/result")]
+async fn post_result(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    form: web::Form<ScoreForm>,
+    path: web::Path<i32>,
+    user: Authorized<perm::ManageGames>,
+) -> impl Responder {
+    let redirect = format!("/games/{}/result", path.0);
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        let game = Game::get(&conn, path.0)?;
+        match GameResult::for_game(&conn, game.id)? {
+            Some(result) => result.correct(&conn, form.home, form.away, user.current.user.id),
+            None => form.to_new_result(game.id).create(&conn),
+        }
+    })
+    .await
+    .map(|_| {
+        let body = hb
+            .render(
+                "success",
+                &json!({"message": "result recorded", "redirect": redirect }),
+            )
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+    .map_err(|e| {
+        let body = hb
+            .render("result_form", &json!({"message": e.to_string() }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+}
+
+/// Request handler for moving a Game to a new status. Postponing or cancelling a game voids
+/// every bet on it.
+#[post("/games/{id}/status")]
+async fn post_game_status(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    form: web::Form<StatusForm>,
+    path: web::Path<i32>,
+    user: Authorized<perm::ManageGames>,
+) -> impl Responder {
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        Game::get(&conn, path.0)?.transition(&conn, form.status, Some(user.current.user.id))
+    })
+    .await
+    .map(|game| {
+        let body = hb
+            .render(
+                "success",
+                &json!({"message": format!("game is now {:?}", game.status), "redirect": "/games" }),
+            )
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+    .map_err(|e| {
+        let body = hb
+            .render("games", &json!({"message": e.to_string() }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
 }
 
 /// Request handler for retrieving all Events
@@ -112,6 +253,7 @@ async fn event_form(
     pool: web::Data<DbPool>,
     hb: web::Data<Handlebars<'_>>,
     _req: HttpRequest,
+    _user: Authorized<perm::ManageMarkets>,
 ) -> impl Responder {
     let conn = pool.get().expect("Could not get connection.");
     web::block(move || Game::all(&conn))
@@ -128,17 +270,20 @@ async fn event_form(
         })
 }
 
-/// Request handler for posting event forms.
+/// Request handler for posting event forms. Opens a market with both of its selections.
 #[post("/events/form")]
 async fn post_event(
     pool: web::Data<DbPool>,
     hb: web::Data<Handlebars<'_>>,
-    form: web::Form<NewEvent>,
+    form: web::Form<MarketForm>,
     _req: HttpRequest,
+    _user: Authorized<perm::ManageMarkets>,
 ) -> impl Responder {
     web::block(move || {
         let conn = pool.get().expect("Could not establish connection.");
-        form.0.create(&conn)
+        let game = Game::get(&conn, form.game_id).map_err(|e| e.to_string())?;
+        let new = form.to_new_market().map_err(|e| e.to_string())?;
+        Market::open(&conn, &game, &new, form.prices()).map_err(|e| e.to_string())
     })
     .await
     .map(|_| {
@@ -158,6 +303,56 @@ async fn post_event(
     })
 }
 
+/// Request handler for an Event's line movement as JSON
+#[get("/events/{id}/history")]
+async fn get_event_history(pool: web::Data<DbPool>, path: web::Path<i32>) -> impl Responder {
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        Event::history(&conn, path.0)
+    })
+    .await
+    .map(|versions| match LineHistory::new(&versions) {
+        Some(history) => HttpResponse::Ok().json(history),
+        None => HttpResponse::NotFound().json(json!({"message": "event not found" })),
+    })
+    .map_err(|e| HttpResponse::InternalServerError().json(json!({"message": e.to_string() })))
+}
+
+/// Request handler for repricing an Event. The new price is written as a new version.
+#[post("/events/{id}/odds")]
+async fn post_odds(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    form: web::Form<OddsForm>,
+    path: web::Path<i32>,
+    _user: Authorized<perm::SetPrices>,
+) -> impl Responder {
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        Event::latest(&conn, path.0)?.reprice(&conn, form.odds)
+    })
+    .await
+    .map(|event| {
+        let redirect = match event.market_id {
+            Some(market_id) => format!("/markets/{}", market_id),
+            None => "/events".to_owned(),
+        };
+        let body = hb
+            .render(
+                "success",
+                &json!({"message": "odds updated", "redirect": redirect }),
+            )
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+    .map_err(|e| {
+        let body = hb
+            .render("events", &json!({"message": e.to_string() }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+}
+
 /// Request handler for index page
 #[get("/")]
 async fn index(hb: web::Data<Handlebars<'_>>) -> impl Responder {
@@ -0,0 +1,89 @@
+//! Request handlers for bookie reports
+use super::DbPool;
+use crate::auth::{perm, Authorized};
+use crate::model::closing::ClvReport;
+use crate::model::exposure::{Exposure, ExposureQuery};
+use handlebars::Handlebars;
+
+use actix_web::{get, web, HttpResponse, Responder};
+use serde_json::json;
+
+/// Request handler for the closing-line value of every punter, best first
+#[get("/reports/clv")]
+async fn get_clv_report(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    _user: Authorized<perm::ViewBook>,
+) -> impl Responder {
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        ClvReport::all(&conn)
+    })
+    .await
+    .map(|reports| {
+        let reports: Vec<_> = reports
+            .iter()
+            .map(|r| {
+                json!({
+                    "user_id": r.user_id,
+                    "username": r.username,
+                    "bets": r.bets,
+                    "average": format!("{:+.1}", r.average * 100.0)
+                })
+            })
+            .collect();
+        let body = hb
+            .render("clv_report", &json!({ "reports": reports }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+    .map_err(|e| {
+        let body = hb
+            .render("clv_report", &json!({"message": e.to_string() }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+}
+
+/// Request handler for the exposure dashboard
+#[get("/exposure")]
+async fn get_exposure(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    query: web::Query<ExposureQuery>,
+    _user: Authorized<perm::ViewExposure>,
+) -> impl Responder {
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        Exposure::current(&conn, query.0.league)
+    })
+    .await
+    .map(|exposure| {
+        let body = hb
+            .render("exposure", &json!({ "exposure": exposure }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+    .map_err(|e| {
+        let body = hb
+            .render("exposure", &json!({"message": e.to_string() }))
+            .unwrap();
+        HttpResponse::InternalServerError().body(body)
+    })
+}
+
+/// Request handler for current exposure as JSON
+#[get("/exposure.json")]
+async fn get_exposure_json(
+    pool: web::Data<DbPool>,
+    query: web::Query<ExposureQuery>,
+    _user: Authorized<perm::ViewExposure>,
+) -> impl Responder {
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        Exposure::current(&conn, query.0.league)
+    })
+    .await
+    .map(|exposure| HttpResponse::Ok().json(exposure))
+    .map_err(|e| HttpResponse::InternalServerError().json(json!({"message": e.to_string() })))
+}
@@ -0,0 +1,92 @@
+//! Request handlers for API access tokens
+use super::api::ApiError;
+use super::DbPool;
+use crate::auth::{RefreshForm, TokenError, TokenPair};
+use crate::form::LoginForm;
+use crate::model::token::RefreshToken;
+
+use actix_web::error::BlockingError;
+use actix_web::http::StatusCode;
+use actix_web::{post, web, HttpResponse, Responder, ResponseError};
+use serde_json::json;
+
+/// Respond to a failed token request, hiding database errors behind a 500.
+fn token_error(e: BlockingError<TokenError>) -> HttpResponse {
+    match e {
+        BlockingError::Error(TokenError::Auth(e)) => ApiError::from(e),
+        BlockingError::Error(TokenError::InvalidToken) => ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "InvalidToken",
+            "Refresh token is invalid, expired or revoked",
+        ),
+        _ => ApiError::internal(),
+    }
+    .error_response()
+}
+
+/// Request handler for exchanging credentials for an access token and refresh token
+#[utoipa::path(
+    post,
+    path = "/api/token",
+    request_body = LoginForm,
+    responses(
+        (status = 200, body = TokenPair),
+        (status = 401, body = ApiError),
+    )
+)]
+#[post("/api/token")]
+async fn post_token(pool: web::Data<DbPool>, form: web::Json<LoginForm>) -> impl Responder {
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        let user = form.0.authenticate(&conn)?;
+        TokenPair::issue(&conn, &user)
+    })
+    .await
+    .map(|pair| HttpResponse::Ok().json(pair))
+    .unwrap_or_else(token_error)
+}
+
+/// Request handler for exchanging a refresh token for a new pair of tokens
+#[utoipa::path(
+    post,
+    path = "/api/token/refresh",
+    request_body = RefreshForm,
+    responses(
+        (status = 200, body = TokenPair),
+        (status = 401, body = ApiError),
+    )
+)]
+#[post("/api/token/refresh")]
+async fn refresh_token(pool: web::Data<DbPool>, form: web::Json<RefreshForm>) -> impl Responder {
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        TokenPair::refresh(&conn, &form.refresh_token)
+    })
+    .await
+    .map(|pair| HttpResponse::Ok().json(pair))
+    .unwrap_or_else(token_error)
+}
+
+/// Request handler for revoking a refresh token
+#[utoipa::path(
+    post,
+    path = "/api/token/revoke",
+    request_body = RefreshForm,
+    responses(
+        (status = 200, description = "Token revoked"),
+        (status = 401, body = ApiError),
+    )
+)]
+#[post("/api/token/revoke")]
+async fn revoke_token(pool: web::Data<DbPool>, form: web::Json<RefreshForm>) -> impl Responder {
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        RefreshToken::find_active(&conn, &form.refresh_token)?
+            .ok_or(TokenError::InvalidToken)?
+            .revoke(&conn)
+            .map_err(TokenError::from)
+    })
+    .await
+    .map(|_| HttpResponse::Ok().json(json!({ "message": "token revoked" })))
+    .unwrap_or_else(token_error)
+}
@@ -1,51 +1,55 @@
 //! Request handlers for user authentication
 use super::DbPool;
-use crate::db::Creatable;
+use crate::auth::{CurrentUser, SESSION_ID};
+use crate::db::{Creatable, Updatable};
 use crate::form::{LoginForm, SignupForm};
+use crate::model::session::Session;
 use handlebars::Handlebars;
 
 use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use chrono::Utc;
 use serde_json::json;
 
-/// Request handler for creating a new account from form data
+/// Request handler for creating a new account from form data. The new user is logged in.
 #[post("/signup")]
 async fn signup(
     pool: web::Data<DbPool>,
     hb: web::Data<Handlebars<'_>>,
     form: web::Form<SignupForm>,
-    _req: HttpRequest,
+    cookie: actix_session::Session,
 ) -> impl Responder {
     web::block(move || {
         let conn = pool.get().expect("Could not establish connection.");
-        form.0.validate().map(|f| {
-            f.authenticate(&conn)
-                .map(|nu| nu.create(&conn).map(|u| u.login(&conn)))
-        })
+        let form = form.0.validate().map_err(|e| e.to_string())?;
+        let new = form.authenticate(&conn).map_err(|e| e.to_string())?;
+        new.create(&conn)
+            .and_then(|u| u.login(&conn))
+            .map_err(|e| e.to_string())
     })
     .await
-    .map(|f| match f {
-        Ok(_) => {
-            let body = hb
-                .render(
-                    "success",
-                    &json!({"message": "successfuly created", "redirect": "/"}),
-                )
-                .unwrap();
-            HttpResponse::Ok().body(body)
-        }
-        Err(e) => {
-            let body = hb
-                .render("signup", &json!({"message": e.to_string() }))
-                .unwrap();
-            HttpResponse::Ok().body(body)
-        }
-    })
-    .map_err(|e| {
+    .map_err(|e| e.to_string())
+    .and_then(|session| start_session(&cookie, &session))
+    .map(|_| {
         let body = hb
-            .render("signup", &json!({"message": e.to_string() }))
+            .render(
+                "success",
+                &json!({"message": "successfuly created", "redirect": "/"}),
+            )
             .unwrap();
         HttpResponse::Ok().body(body)
     })
+    .map_err(|e| {
+        let body = hb.render("signup", &json!({ "message": e })).unwrap();
+        HttpResponse::Ok().body(body)
+    })
+}
+
+/// Tie `session` to the browser, replacing any session cookie it already had.
+fn start_session(cookie: &actix_session::Session, session: &Session) -> Result<(), String> {
+    cookie.renew();
+    cookie
+        .set(SESSION_ID, session.id)
+        .map_err(|e| e.to_string())
 }
 
 /// Retrieve signup form
@@ -61,12 +65,16 @@ async fn login(
     pool: web::Data<DbPool>,
     form: web::Form<LoginForm>,
     hb: web::Data<Handlebars<'_>>,
+    cookie: actix_session::Session,
 ) -> impl Responder {
     web::block(move || {
         let conn = pool.get().expect("Could not establish connection.");
-        form.0.authenticate(&conn).map(|u| u.login(&conn))
+        let user = form.0.authenticate(&conn).map_err(|e| e.to_string())?;
+        user.login(&conn).map_err(|e| e.to_string())
     })
     .await
+    .map_err(|e| e.to_string())
+    .and_then(|session| start_session(&cookie, &session))
     .map(|_| {
         let body = hb
             .render(
@@ -76,6 +84,41 @@ async fn login(
             .unwrap();
         HttpResponse::Ok().body(body)
     })
+    .map_err(|e| {
+        let body = hb.render("login", &json!({ "message": e })).unwrap();
+        HttpResponse::Ok().body(body)
+    })
+}
+
+/// Request handler for logging the current user out
+#[post("/logout")]
+async fn logout(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    current: CurrentUser,
+    cookie: actix_session::Session,
+) -> impl Responder {
+    cookie.purge();
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        let session = current.session.ok_or_else(|| "NotLoggedIn".to_string())?;
+        Session {
+            logout_date: Some(Utc::now().naive_utc()),
+            ..session
+        }
+        .update(&conn)
+        .map_err(|e| e.to_string())
+    })
+    .await
+    .map(|_| {
+        let body = hb
+            .render(
+                "success",
+                &json!({"message": "logged out", "redirect": "/login" }),
+            )
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
     .map_err(|e| {
         let body = hb
             .render("login", &json!({"message": e.to_string() }))
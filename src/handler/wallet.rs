@@ -0,0 +1,108 @@
+//! Request handlers for punter wallets
+use super::DbPool;
+use crate::auth::{deny, perm, Authorized, CurrentUser};
+use crate::db::Retrievable;
+use crate::form::DepositForm;
+use crate::model::ledger::{Account, Entry, EntryQuery, WalletQuery};
+use crate::model::permission::Permission;
+use handlebars::Handlebars;
+
+use actix_web::http::StatusCode;
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use diesel::result::Error as DieselError;
+use serde_json::json;
+
+/// Request handler for viewing a wallet's balance and ledger entries. Users see their own wallet
+/// unless they hold `ViewAccounts` and ask for someone else's.
+#[get("/wallet")]
+async fn get_wallet(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    query: web::Query<WalletQuery>,
+    current: CurrentUser,
+    req: HttpRequest,
+) -> impl Responder {
+    let user_id = query.user_id.unwrap_or(current.user.id);
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        if user_id != current.user.id && !current.can(&conn, Permission::ViewAccounts)? {
+            return Ok(None);
+        }
+        let wallet = Account::wallet(&conn, user_id)?;
+        let entries = Entry::query(
+            &conn,
+            &EntryQuery {
+                account_id: Some(wallet.id),
+                bet_id: None,
+            },
+        )?;
+        let can_deposit = current.can(&conn, Permission::MoveMoney)?;
+        Ok::<_, DieselError>(Some((wallet, wallet.balance(&conn)?, entries, can_deposit)))
+    })
+    .await
+    .map(|wallet| match wallet {
+        Some((wallet, balance, entries, can_deposit)) => {
+            let body = hb
+                .render(
+                    "wallet",
+                    &json!({
+                        "wallet": wallet,
+                        "balance": balance,
+                        "entries": entries,
+                        "user_id": query.user_id,
+                        "can_deposit": can_deposit
+                    }),
+                )
+                .unwrap();
+            HttpResponse::Ok().body(body)
+        }
+        None => HttpResponse::from_error(deny(&req, StatusCode::FORBIDDEN, "Forbidden")),
+    })
+    .map_err(|e| {
+        let body = hb
+            .render("wallet", &json!({"message": e.to_string() }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+}
+
+/// Request handler for depositing funds from outside the book into a wallet, the current user's
+/// unless another is named. Deposits create money, so they need `MoveMoney` even into one's own
+/// wallet.
+#[post("/wallet/deposit")]
+async fn post_deposit(
+    pool: web::Data<DbPool>,
+    hb: web::Data<Handlebars<'_>>,
+    form: web::Form<DepositForm>,
+    authorized: Authorized<perm::MoveMoney>,
+) -> impl Responder {
+    let user_id = form.user_id.unwrap_or(authorized.current.user.id);
+    let redirect = match form.user_id {
+        Some(user_id) => format!("/wallet?user_id={}", user_id),
+        None => "/wallet".to_owned(),
+    };
+    web::block(move || {
+        let conn = pool.get().expect("Could not establish connection.");
+        let form = form.0.validate().map_err(|e| e.to_string())?;
+        Account::deposit(&conn, user_id, form.amount_to_cents()).map_err(|e| e.to_string())
+    })
+    .await
+    .map(|_| {
+        let body = hb
+            .render(
+                "success",
+                &json!({
+                    "message": "deposit successful",
+                    "redirect": redirect
+                }),
+            )
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+    .map_err(|e| {
+        let body = hb
+            .render("wallet", &json!({"message": e.to_string() }))
+            .unwrap();
+        HttpResponse::Ok().body(body)
+    })
+}
@@ -1,97 +1,76 @@
 #[macro_use]
 extern crate diesel;
 
+pub mod auth;
+pub mod cli;
 pub mod db;
+pub mod feed;
 pub mod form;
 pub mod handler;
 pub mod model;
+pub mod odds;
+pub mod password;
 pub mod schema;
 pub mod test;
 
 use actix_files::Files;
-use actix_web::{web, App, HttpServer};
+use actix_web::{rt, web, App, HttpServer};
+use chrono::Utc;
 use handler::*;
+use model::closing::ClosingLine;
+use odds::Odds;
 
 use diesel::pg::PgConnection;
 use diesel::r2d2::{self, ConnectionManager};
 use dotenv::dotenv;
-use handlebars::Handlebars;
+use handlebars::{handlebars_helper, Handlebars};
 
 use std::env;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
 
 pub mod exports {
+    pub use crate::model::bet::BetStatusMapping as BetStatus;
+    pub use crate::model::ledger::AccountKindMapping as AccountKind;
+    pub use crate::model::ledger::EntryKindMapping as EntryKind;
+    pub use crate::model::market::MarketKindMapping as MarketKind;
+    pub use crate::model::market::TeamSideMapping as TeamSide;
+    pub use crate::model::market::TotalSideMapping as TotalSide;
+    pub use crate::model::permission::PermissionMapping as Permission;
     pub use crate::model::user::RoleMapping as Role;
-    pub use crate::model::LeagueMapping as League;
+    pub use crate::model::GameStatusMapping as GameStatus;
 }
 pub type DbPool = r2d2::Pool<ConnectionManager<PgConnection>>;
 
-static NFL_TEAMS: [(&'static str, &'static str); 31] = [
-    ("ATL", "Atlanta Falcons"),
-    ("ARI", "Arizona Cardinals"),
-    ("BAL", "Baltimore Ravens"),
-    ("BUF", "Buffalo Bills"),
-    ("CAR", "Caronlina Panthers"),
-    ("CHI", "Chicago Bears"),
-    ("CIN", "Cincinnati Bengals"),
-    ("CLE", "Cleveland Browns"),
-    ("DAL", "Dallas Cowboys"),
-    ("DEN", "Denver Broncos"),
-    ("DET", "Detroit Lions"),
-    ("GB", "Green Bay Packers"),
-    ("HOU", "Houston Texans"),
-    ("IND", "Indianapolis Colts"),
-    ("JAX", "Jacksonville Jaguars"),
-    ("KC", "Kansas City Chiefs"),
-    ("LAC", "Los Angeles Chargers"),
-    ("LAR", "Los Angeles Rams"),
-    ("MIA", "Miami Dolphins"),
-    ("MIN", "Minnesota Vikings"),
-    ("LV", "Las Vegas Raiders"),
-    ("NE", "New England Patriots"),
-    ("NO", "New Orleans Saints"),
-    ("NYG", "New York Giants"),
-    ("NYJ", "New York Jets"),
-    ("PIT", "Pittsburgh Steelers"),
-    ("SEA", "Seattle Seahawks"),
-    ("SF", "San Francisco 49ers"),
-    ("TB", "Tampa Bay Buccaneers"),
-    ("TEN", "Tennessee Titans"),
-    ("WAS", "Washington Commanders"),
-];
-
-static NBA_TEAMS: [(&'static str, &'static str); 30] = [
-    ("ATL", "Atlanta Hawks"),
-    ("BOS", "Boston Celtics"),
-    ("BKN", "Brooklyn Nets"),
-    ("CHA", "Charlotte Hornets"),
-    ("CHI", "Chicago Bulls"),
-    ("CLE", "Cleveland Cavaliers"),
-    ("DAL", "Dallas Mavericks"),
-    ("DEN", "Denver Nuggets"),
-    ("DET", "Detroit Pistons"),
-    ("GSW", "Golden State Warriors"),
-    ("HOU", "Houston Rockets"),
-    ("IND", "Indiana Pacers"),
-    ("LAC", "Los Angeles Clippers"),
-    ("LAL", "Los Angeles Lakers"),
-    ("MEM", "Memphis Grizzlies"),
-    ("MIA", "Miami Heat"),
-    ("MIL", "Milwaukee Bucks"),
-    ("MIN", "Minnesota Timberwolves"),
-    ("NOP", "New Orleans Pelicans"),
-    ("NYK", "New York Knicks"),
-    ("OKC", "Oklahoma City Thunder"),
-    ("ORL", "Orlando Magic"),
-    ("PHI", "Philadelphia 76ers"),
-    ("PHX", "Phoenix Suns"),
-    ("POR", "Portland Trailblazers"),
-    ("SAC", "Sacramento Kings"),
-    ("SAS", "San Antonio Spurs"),
-    ("TOR", "Toronto Raptors"),
-    ("UTA", "Utah Jazz"),
-    ("WAS", "Washington Wizards"),
-];
+/// The page templates with the partials and helpers they use
+pub fn templates() -> Handlebars<'static> {
+    let mut handlebars = Handlebars::new();
+    handlebars
+        .register_templates_directory(".html", "./static/templates")
+        .unwrap();
+    let styles = r#"
+        <link rel="preconnect" href="https://fonts.googleapis.com">
+        <link rel="preconnect" href="https://fonts.gstatic.com" crossorigin>
+        <link href="https://fonts.googleapis.com/css2?family=Anonymous+Pro&family=Anybody:wght@300;400&family=Arimo&family=Bebas+Neue&family=Fira+Code:wght@300&family=Rubik+Moonrocks&family=Titan+One&display=swap" rel="stylesheet">
+        <link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/bulma@0.9.4/css/bulma.min.css">
+    "#;
+    handlebars.register_partial("styles", styles).unwrap();
+    handlebars_helper!(cents: |v: i64| format!("{:.2}", v as f64 / 100.0));
+    handlebars.register_helper("cents", Box::new(cents));
+    handlebars_helper!(decimal_odds: |v: i64| Odds::american(v as i32)
+        .map(|o| format!("{:.2}", o.to_decimal()))
+        .unwrap_or_default());
+    handlebars.register_helper("decimal_odds", Box::new(decimal_odds));
+    handlebars_helper!(fractional_odds: |v: i64| Odds::american(v as i32)
+        .map(|o| format!("{}/{}", o.to_fractional().0, o.to_fractional().1))
+        .unwrap_or_default());
+    handlebars.register_helper("fractional_odds", Box::new(fractional_odds));
+    handlebars_helper!(implied_probability: |v: i64| Odds::american(v as i32)
+        .map(|o| format!("{:.1}%", o.implied_probability() * 100.0))
+        .unwrap_or_default());
+    handlebars.register_helper("implied_probability", Box::new(implied_probability));
+    handlebars
+}
 
 #[actix_web::main]
 pub async fn main() -> std::io::Result<()> {
@@ -106,24 +85,45 @@ pub async fn main() -> std::io::Result<()> {
         .build(manager)
         .expect("Could not create pool.");
 
-    let mut handlebars = Handlebars::new();
-    handlebars
-        .register_templates_directory(".html", "./static/templates")
-        .unwrap();
-    let styles = r#"
-        <link rel="preconnect" href="https://fonts.googleapis.com">
-        <link rel="preconnect" href="https://fonts.gstatic.com" crossorigin>
-        <link href="https://fonts.googleapis.com/css2?family=Anonymous+Pro&family=Anybody:wght@300;400&family=Arimo&family=Bebas+Neue&family=Fira+Code:wght@300&family=Rubik+Moonrocks&family=Titan+One&display=swap" rel="stylesheet">
-        <link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/bulma@0.9.4/css/bulma.min.css">
-    "#;
-    handlebars.register_partial("styles", styles).unwrap();
-    let handlebars_ref = web::Data::new(handlebars);
+    // Run a subcommand instead of the server if one is given
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        let conn = pool.get().expect("Could not establish connection.");
+        return cli::run(&conn, &args)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e));
+    }
+
+    let handlebars_ref = web::Data::new(templates());
+
+    // Snapshot closing lines as games start
+    let capture_pool = pool.clone();
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            let pool = capture_pool.clone();
+            let captured = web::block(move || {
+                let conn = pool.get().expect("Could not establish connection.");
+                ClosingLine::capture_started(&conn, Utc::now().naive_utc())
+            })
+            .await;
+            if let Err(e) = captured {
+                eprintln!("Could not capture closing lines: {}", e);
+            }
+        }
+    });
+
+    // Mirror prices from the odds feed, if one is configured
+    if let Some(config) = feed::FeedConfig::from_env() {
+        rt::spawn(feed::poll(pool.clone(), config));
+    }
 
     let addrress = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 8008);
     println!("🚀 ⛽🌬️🌬️ Serving at {:?}", addrress);
 
     HttpServer::new(move || {
         App::new()
+            .wrap(auth::session_middleware())
             .app_data(handlebars_ref.clone())
             .data(pool.clone())
             .service(Files::new("/static", "./static"))
@@ -131,13 +131,41 @@ pub async fn main() -> std::io::Result<()> {
             .service(get_events)
             .service(event_form)
             .service(post_event)
+            .service(get_event_history)
+            .service(post_odds)
             .service(games_form)
             .service(post_game)
             .service(get_games)
+            .service(result_form)
+            .service(post_result)
+            .service(post_game_status)
             .service(user::login_form)
             .service(user::login)
             .service(user::signup_form)
             .service(user::signup)
+            .service(user::logout)
+            .service(token::post_token)
+            .service(token::refresh_token)
+            .service(token::revoke_token)
+            .service(bet::post_bet)
+            .service(bet::get_bets)
+            .service(bet::post_parlay)
+            .service(wallet::get_wallet)
+            .service(wallet::post_deposit)
+            .service(market::get_market)
+            .service(market::suspend_market)
+            .service(market::resume_market)
+            .service(market::post_policy)
+            .service(report::get_clv_report)
+            .service(report::get_exposure)
+            .service(report::get_exposure_json)
+            .service(admin::get_roles)
+            .service(admin::post_permissions)
+            .service(admin::post_user_role)
+            .service(admin::schedule_form)
+            .service(admin::post_schedule)
+            .service(api::get_openapi)
+            .service(api::scope())
             .service(get_events)
             .service(event_form)
     })
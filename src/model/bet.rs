@@ -0,0 +1,232 @@
+use crate::db::{Creatable, Deletable, Retrievable, Updatable};
+use crate::model::ledger::{Account, AccountKind, EntryKind, NewEntry};
+use crate::model::market::Market;
+use crate::model::pricing::PricingPolicy;
+use crate::model::{Event, Game, GameStatus};
+use crate::odds::Odds;
+use crate::schema::bets::{self, dsl as bets_dsl};
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+use diesel::{Connection, ExpressionMethods, Insertable, QueryDsl, Queryable, RunQueryDsl};
+use diesel_derive_enum::DbEnum;
+use serde::{Deserialize, Serialize};
+use std::{error, fmt};
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Clone, Copy, Debug, DbEnum, Deserialize, Serialize, PartialEq, ToSchema)]
+pub enum BetStatus {
+    Open,
+    Won,
+    Lost,
+    Push,
+    Void,
+}
+
+#[derive(Debug)]
+pub enum BetError {
+    InsufficientFunds,
+    MarketSuspended,
+    /// The game has started and isn't taking in-play bets
+    GameStarted,
+    /// The event has been repriced since the bet was priced
+    PriceChanged,
+    /// A parlay needs at least two legs
+    TooFewLegs,
+    /// A parlay can't combine two selections from the same market
+    SameMarket,
+    Db(DieselError),
+}
+
+/// A wager placed by a user on a single `Event`, or a parlay when `event_id` is `None`. `stake` is
+/// stored in cents and `odds` are the American odds the bet was priced at when it was placed.
+/// `event_timestamp` identifies the version of the event the bet was placed against.
+#[derive(Clone, Debug, Deserialize, Serialize, Queryable, ToSchema)]
+pub struct Bet {
+    pub id: i32,
+    pub user_id: i32,
+    pub event_id: Option<i32>,
+    pub stake: i64,
+    pub odds: Odds,
+    pub placed_at: NaiveDateTime,
+    pub status: BetStatus,
+    pub settled_at: Option<NaiveDateTime>,
+    pub event_timestamp: Option<NaiveDateTime>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Insertable)]
+#[table_name = "bets"]
+pub struct NewBet {
+    pub user_id: i32,
+    pub event_id: Option<i32>,
+    pub stake: i64,
+    pub odds: Odds,
+    pub event_timestamp: Option<NaiveDateTime>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BetQuery {
+    pub user_id: Option<i32>,
+    pub event_id: Option<i32>,
+}
+
+impl fmt::Display for BetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BetError::InsufficientFunds => write!(f, "InsufficientFunds"),
+            BetError::MarketSuspended => write!(f, "MarketSuspended"),
+            BetError::GameStarted => write!(f, "GameStarted"),
+            BetError::PriceChanged => write!(f, "PriceChanged"),
+            BetError::TooFewLegs => write!(f, "TooFewLegs"),
+            BetError::SameMarket => write!(f, "SameMarket"),
+            BetError::Db(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for BetError {}
+
+impl From<DieselError> for BetError {
+    fn from(e: DieselError) -> Self {
+        BetError::Db(e)
+    }
+}
+
+impl Bet {
+    /// Retrieve the bet with the given `id`.
+    pub fn get(conn: &PgConnection, id: i32) -> Result<Bet, DieselError> {
+        bets_dsl::bets.find(id).first(conn)
+    }
+
+    /// Amount returned to the punter, stake included, if the bet wins.
+    pub fn payout(&self) -> i64 {
+        self.odds.payout(self.stake)
+    }
+
+    pub fn is_parlay(&self) -> bool {
+        self.event_id.is_none()
+    }
+}
+
+/// Refuse bets on `event` while its market is suspended or its game isn't taking bets: it has
+/// started without being marked live, or is no longer being played.
+pub(crate) fn ensure_open(conn: &PgConnection, event: &Event) -> Result<(), BetError> {
+    if let Some(game_id) = event.game_id {
+        let game = Game::get(conn, game_id)?;
+        if !game.accepts_bets(Utc::now().naive_utc()) {
+            return Err(match game.status {
+                GameStatus::Scheduled => BetError::GameStarted,
+                _ => BetError::MarketSuspended,
+            });
+        }
+    }
+    if let Some(market_id) = event.market_id {
+        if Market::get(conn, market_id)?.suspended {
+            return Err(BetError::MarketSuspended);
+        }
+    }
+    Ok(())
+}
+
+impl NewBet {
+    /// Create a new bet on this version of `event`, locking in its odds.
+    pub fn new(user_id: i32, event: &Event, stake: i64) -> Self {
+        NewBet {
+            user_id,
+            event_id: Some(event.id),
+            stake,
+            odds: event.odds,
+            event_timestamp: Some(event.timestamp),
+        }
+    }
+
+    /// Place the bet. The stake is moved from the punter's wallet into escrow in the same
+    /// transaction that records the bet, so a bet can never exist without its debit. Bets on
+    /// suspended markets, or priced against a version of the event that has since been replaced,
+    /// are refused. A single on a market with a pricing policy may move the market's prices.
+    pub fn place(&self, conn: &PgConnection) -> Result<Bet, BetError> {
+        conn.transaction::<Bet, BetError, _>(|| {
+            if let Some(event_id) = self.event_id {
+                let event = Event::latest(conn, event_id)?;
+                ensure_open(conn, &event)?;
+                if self.event_timestamp != Some(event.timestamp) {
+                    return Err(BetError::PriceChanged);
+                }
+            }
+            let wallet = Account::wallet(conn, self.user_id)?.lock(conn)?;
+            if wallet.balance(conn)? < self.stake {
+                return Err(BetError::InsufficientFunds);
+            }
+            let bet = self.create(conn)?;
+            let escrow = Account::system(conn, AccountKind::Escrow)?;
+            NewEntry::new(EntryKind::Stake, &wallet, &escrow, bet.stake)
+                .bet(bet.id)
+                .create(conn)?;
+            if let Some(event_id) = bet.event_id {
+                if let Some(market_id) = Event::latest(conn, event_id)?.market_id {
+                    PricingPolicy::apply(conn, &Market::get(conn, market_id)?)?;
+                }
+            }
+            Ok(bet)
+        })
+    }
+}
+
+impl Default for BetQuery {
+    fn default() -> Self {
+        BetQuery {
+            user_id: None,
+            event_id: None,
+        }
+    }
+}
+
+impl Creatable for NewBet {
+    type Output = Bet;
+    fn create(&self, conn: &PgConnection) -> Result<Bet, DieselError> {
+        diesel::insert_into(bets_dsl::bets)
+            .values(self)
+            .get_result(conn)
+    }
+}
+
+impl Deletable for Bet {
+    fn delete(&self, conn: &PgConnection) -> Result<Bet, DieselError> {
+        diesel::delete(bets_dsl::bets.filter(bets_dsl::id.eq(&self.id))).get_result(conn)
+    }
+}
+
+impl Updatable for Bet {
+    fn update(&self, conn: &PgConnection) -> Result<Bet, DieselError> {
+        diesel::update(bets_dsl::bets)
+            .filter(bets_dsl::id.eq(&self.id))
+            .set((
+                bets_dsl::odds.eq(&self.odds),
+                bets_dsl::status.eq(&self.status),
+                bets_dsl::settled_at.eq(&self.settled_at),
+            ))
+            .get_result(conn)
+    }
+}
+
+impl Retrievable<BetQuery> for Bet {
+    fn query(conn: &PgConnection, data: &BetQuery) -> Result<Vec<Bet>, DieselError> {
+        let mut query = bets_dsl::bets.into_boxed();
+        if let Some(_user_id) = data.user_id {
+            query = query.filter(bets_dsl::user_id.eq(_user_id));
+        }
+        if let Some(_event_id) = data.event_id {
+            query = query.filter(bets_dsl::event_id.eq(_event_id));
+        }
+        query.order_by(bets_dsl::placed_at.desc()).load(conn)
+    }
+
+    fn all(conn: &PgConnection) -> Result<Vec<Bet>, DieselError> {
+        bets_dsl::bets
+            .limit(100)
+            .order_by(bets_dsl::placed_at.desc())
+            .load(conn)
+    }
+}
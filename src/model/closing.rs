@@ -0,0 +1,200 @@
+//! Closing lines
+//!
+//! Once a game starts, the version of each of its events that was live at the start is recorded
+//! as that event's closing line. Bets are measured against it: a bet that was priced better than
+//! the close has positive closing-line value (CLV), which over enough bets is the most reliable
+//! sign of a sharp punter.
+use crate::model::bet::{Bet, BetStatus};
+use crate::model::parlay::ParlayLeg;
+use crate::model::user::User;
+use crate::model::{Event, Game};
+use crate::odds::{self, Odds};
+use crate::schema::bets::dsl as bets_dsl;
+use crate::schema::closing_lines::{self, dsl as closing_dsl};
+use crate::schema::events::dsl as events_dsl;
+use crate::schema::games::dsl as games_dsl;
+use crate::schema::parlay_legs::dsl as legs_dsl;
+use crate::schema::users::dsl as users_dsl;
+
+use chrono::NaiveDateTime;
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+use diesel::{
+    ExpressionMethods, Insertable, NullableExpressionMethods, QueryDsl, Queryable, RunQueryDsl,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Queryable)]
+pub struct ClosingLine {
+    pub id: i32,
+    pub event_id: i32,
+    pub event_timestamp: NaiveDateTime,
+    pub odds: Odds,
+    pub captured_at: NaiveDateTime,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Insertable)]
+#[table_name = "closing_lines"]
+pub struct NewClosingLine {
+    pub event_id: i32,
+    pub event_timestamp: NaiveDateTime,
+    pub odds: Odds,
+}
+
+/// A punter's average closing-line value over their settled bets, in implied-probability points
+#[derive(Clone, Debug, Serialize)]
+pub struct ClvReport {
+    pub user_id: i32,
+    pub username: String,
+    pub bets: usize,
+    pub average: f64,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+/////// Implementations ///////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl ClosingLine {
+    /// Record the closing line of every event on `game`, which is the last version created at or
+    /// before the game's start. Events that already have a closing line, or that were only
+    /// offered after the start, are skipped.
+    pub fn capture(conn: &PgConnection, game: &Game) -> Result<Vec<ClosingLine>, DieselError> {
+        let versions: Vec<Event> = events_dsl::events
+            .filter(events_dsl::game_id.eq(game.id))
+            .filter(events_dsl::timestamp.le(game.start))
+            .order_by((events_dsl::id.asc(), events_dsl::timestamp.desc()))
+            .load(conn)?;
+        let mut closing: Vec<NewClosingLine> = Vec::new();
+        for event in versions {
+            if closing.last().map(|c| c.event_id) != Some(event.id) {
+                closing.push(NewClosingLine {
+                    event_id: event.id,
+                    event_timestamp: event.timestamp,
+                    odds: event.odds,
+                });
+            }
+        }
+        if closing.is_empty() {
+            return Ok(Vec::new());
+        }
+        diesel::insert_into(closing_dsl::closing_lines)
+            .values(&closing)
+            .on_conflict_do_nothing()
+            .get_results(conn)
+    }
+
+    /// Capture the closing lines of every game that started at or before `now` and has events
+    /// but no closing lines yet. Games are picked by what they're missing rather than by when
+    /// they started, so games added late or moved earlier are still caught. Returns the closing
+    /// lines that were recorded.
+    pub fn capture_started(
+        conn: &PgConnection,
+        now: NaiveDateTime,
+    ) -> Result<Vec<ClosingLine>, DieselError> {
+        let priced = events_dsl::events
+            .filter(events_dsl::game_id.is_not_null())
+            .select(events_dsl::game_id);
+        let closed = events_dsl::events
+            .filter(events_dsl::game_id.is_not_null())
+            .filter(events_dsl::id.eq_any(closing_dsl::closing_lines.select(closing_dsl::event_id)))
+            .select(events_dsl::game_id);
+        let games: Vec<Game> = games_dsl::games
+            .filter(games_dsl::start.le(now))
+            .filter(games_dsl::id.nullable().eq_any(priced))
+            .filter(diesel::dsl::not(games_dsl::id.nullable().eq_any(closed)))
+            .load(conn)?;
+        let mut captured = Vec::new();
+        for game in &games {
+            captured.extend(ClosingLine::capture(conn, game)?);
+        }
+        Ok(captured)
+    }
+}
+
+/// The closing-line value of each of `bets` whose selections have closed, keyed by bet id.
+/// Parlays are compared on their original combined price against the combined closing price
+/// of their legs, and only once every leg has closed.
+pub fn closing_line_values(
+    conn: &PgConnection,
+    bets: &[Bet],
+) -> Result<HashMap<i32, f64>, DieselError> {
+    let parlay_ids: Vec<i32> = bets
+        .iter()
+        .filter(|b| b.is_parlay())
+        .map(|b| b.id)
+        .collect();
+    let legs: Vec<ParlayLeg> = legs_dsl::parlay_legs
+        .filter(legs_dsl::bet_id.eq_any(parlay_ids))
+        .load(conn)?;
+    let mut event_ids: Vec<i32> = bets.iter().filter_map(|b| b.event_id).collect();
+    event_ids.extend(legs.iter().map(|l| l.event_id));
+    let closing: HashMap<i32, Odds> = closing_dsl::closing_lines
+        .filter(closing_dsl::event_id.eq_any(event_ids))
+        .load::<ClosingLine>(conn)?
+        .into_iter()
+        .map(|c| (c.event_id, c.odds))
+        .collect();
+
+    let mut values = HashMap::new();
+    for bet in bets {
+        let value = match bet.event_id {
+            Some(event_id) => closing
+                .get(&event_id)
+                .map(|close| odds::closing_line_value(bet.odds, *close)),
+            None => {
+                let legs: Vec<&ParlayLeg> = legs.iter().filter(|l| l.bet_id == bet.id).collect();
+                let placed: Vec<Odds> = legs.iter().map(|l| l.odds).collect();
+                let closes: Option<Vec<Odds>> = legs
+                    .iter()
+                    .map(|l| closing.get(&l.event_id).cloned())
+                    .collect();
+                match (odds::parlay(&placed), closes.and_then(|c| odds::parlay(&c))) {
+                    (Some(placed), Some(close)) => Some(odds::closing_line_value(placed, close)),
+                    _ => None,
+                }
+            }
+        };
+        if let Some(value) = value {
+            values.insert(bet.id, value);
+        }
+    }
+    Ok(values)
+}
+
+impl ClvReport {
+    /// Average closing-line value of every punter with settled bets that have closed, best
+    /// first.
+    pub fn all(conn: &PgConnection) -> Result<Vec<ClvReport>, DieselError> {
+        let bets: Vec<Bet> = bets_dsl::bets
+            .filter(bets_dsl::status.ne(BetStatus::Open))
+            .load(conn)?;
+        let values = closing_line_values(conn, &bets)?;
+        let mut totals: HashMap<i32, (usize, f64)> = HashMap::new();
+        for bet in &bets {
+            if let Some(value) = values.get(&bet.id) {
+                let total = totals.entry(bet.user_id).or_insert((0, 0.0));
+                total.0 += 1;
+                total.1 += value;
+            }
+        }
+        let users: Vec<User> = users_dsl::users
+            .filter(users_dsl::id.eq_any(totals.keys().cloned().collect::<Vec<i32>>()))
+            .load(conn)?;
+        let mut reports: Vec<ClvReport> = users
+            .into_iter()
+            .map(|user| {
+                let (count, sum) = totals[&user.id];
+                ClvReport {
+                    user_id: user.id,
+                    username: user.username,
+                    bets: count,
+                    average: sum / count as f64,
+                }
+            })
+            .collect();
+        reports.sort_by(|a, b| b.average.partial_cmp(&a.average).unwrap());
+        Ok(reports)
+    }
+}
@@ -0,0 +1,174 @@
+//! Bookie exposure
+//!
+//! What the book stands to win or lose on open bets, per market and per game, rolled up by
+//! league and by day. A market's worst case is the house result if its most expensive selection
+//! wins. Rollups add up worst cases, which is conservative because markets on the same game are
+//! correlated. Parlays span games, so they are reported on their own.
+use crate::db::Retrievable;
+use crate::model::bet::{Bet, BetStatus};
+use crate::model::market::{Market, MarketKind, MarketQuery, SelectionLiability, TeamSide};
+use crate::model::{Game, GameQuery};
+use crate::schema::bets::dsl as bets_dsl;
+
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MarketExposure {
+    pub market_id: i32,
+    pub kind: MarketKind,
+    pub team: Option<TeamSide>,
+    pub line: Option<i32>,
+    pub suspended: bool,
+    pub stake: i64,
+    pub worst_case: i64,
+    pub selections: Vec<SelectionLiability>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct GameExposure {
+    pub game: Game,
+    pub stake: i64,
+    pub worst_case: i64,
+    pub markets: Vec<MarketExposure>,
+}
+
+/// Exposure summed over a group of games, e.g. a league or a day
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct Rollup {
+    pub key: String,
+    pub games: usize,
+    pub stake: i64,
+    pub worst_case: i64,
+}
+
+/// Open parlays, which lose the house their potential payout minus the stake if every leg wins
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct ParlayExposure {
+    pub tickets: usize,
+    pub stake: i64,
+    pub payout: i64,
+    pub worst_case: i64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ExposureQuery {
+    pub league: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Exposure {
+    pub stake: i64,
+    pub worst_case: i64,
+    pub games: Vec<GameExposure>,
+    pub leagues: Vec<Rollup>,
+    pub days: Vec<Rollup>,
+    pub parlays: ParlayExposure,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+/////// Implementations ///////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl MarketExposure {
+    pub fn new(market: &Market, selections: Vec<SelectionLiability>) -> Self {
+        MarketExposure {
+            market_id: market.id,
+            kind: market.kind,
+            team: market.team,
+            line: market.line,
+            suspended: market.suspended,
+            stake: selections.iter().map(|s| s.stake).sum(),
+            worst_case: selections.iter().map(|s| s.house_result).min().unwrap_or(0),
+            selections,
+        }
+    }
+}
+
+impl GameExposure {
+    /// Exposure on every market on `game`.
+    pub fn new(conn: &PgConnection, game: Game) -> Result<GameExposure, DieselError> {
+        let markets = Market::query(
+            conn,
+            &MarketQuery {
+                game_id: Some(game.id),
+            },
+        )?;
+        let markets = markets
+            .iter()
+            .map(|m| Ok(MarketExposure::new(m, m.liability(conn)?)))
+            .collect::<Result<Vec<MarketExposure>, DieselError>>()?;
+        Ok(GameExposure {
+            stake: markets.iter().map(|m| m.stake).sum(),
+            worst_case: markets.iter().map(|m| m.worst_case).sum(),
+            game,
+            markets,
+        })
+    }
+}
+
+/// Sum up `games` into one rollup per distinct `key`, in key order.
+pub fn rollup<F: Fn(&Game) -> String>(games: &[GameExposure], key: F) -> Vec<Rollup> {
+    let mut rollups: BTreeMap<String, Rollup> = BTreeMap::new();
+    for exposure in games {
+        let k = key(&exposure.game);
+        let rollup = rollups.entry(k.clone()).or_insert(Rollup {
+            key: k,
+            games: 0,
+            stake: 0,
+            worst_case: 0,
+        });
+        rollup.games += 1;
+        rollup.stake += exposure.stake;
+        rollup.worst_case += exposure.worst_case;
+    }
+    rollups.into_iter().map(|(_, r)| r).collect()
+}
+
+impl ParlayExposure {
+    /// Exposure on every open parlay.
+    pub fn open(conn: &PgConnection) -> Result<ParlayExposure, DieselError> {
+        let bets: Vec<Bet> = bets_dsl::bets
+            .filter(bets_dsl::event_id.is_null())
+            .filter(bets_dsl::status.eq(BetStatus::Open))
+            .load(conn)?;
+        let stake: i64 = bets.iter().map(|b| b.stake).sum();
+        let payout: i64 = bets.iter().map(Bet::payout).sum();
+        Ok(ParlayExposure {
+            tickets: bets.len(),
+            stake,
+            payout,
+            worst_case: stake - payout,
+        })
+    }
+}
+
+impl Exposure {
+    /// Exposure on every game that hasn't finished or been cancelled, optionally in one `league`
+    /// only.
+    pub fn current(conn: &PgConnection, league: Option<String>) -> Result<Exposure, DieselError> {
+        let games = Game::query(
+            conn,
+            &GameQuery {
+                league,
+                status: None,
+            },
+        )?
+        .into_iter()
+        .map(|game| GameExposure::new(conn, game))
+        .collect::<Result<Vec<GameExposure>, DieselError>>()?;
+        let parlays = ParlayExposure::open(conn)?;
+        Ok(Exposure {
+            stake: games.iter().map(|g| g.stake).sum::<i64>() + parlays.stake,
+            worst_case: games.iter().map(|g| g.worst_case).sum::<i64>() + parlays.worst_case,
+            leagues: rollup(&games, |g| g.league.clone()),
+            days: rollup(&games, |g| g.start.date().to_string()),
+            games,
+            parlays,
+        })
+    }
+}
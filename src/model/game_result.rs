@@ -0,0 +1,250 @@
+//! Final scores
+//!
+//! Recording a `GameResult` settles every bet on the game. Scores are validated against the game
+//! before they are recorded, and corrections made with `GameResult::correct` leave a
+//! `ResultCorrection` behind saying what the score was and who changed it. Recording a result
+//! makes the game final.
+use crate::db::{Creatable, Retrievable, Updatable};
+use crate::model::league::League;
+use crate::model::settlement::{settle_game, SettlementError};
+use crate::model::{Game, GameStatus};
+use crate::schema::game_results::{self, dsl as results_dsl};
+use crate::schema::result_corrections::{self, dsl as corrections_dsl};
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+use diesel::{
+    Connection, ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable, RunQueryDsl,
+};
+use serde::{Deserialize, Serialize};
+use std::{error, fmt};
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Debug)]
+pub enum ResultError {
+    NegativeScore,
+    /// The game's league doesn't allow draws
+    Tie,
+    /// The game hasn't started yet
+    NotStarted,
+    /// The game was postponed or cancelled
+    NotPlayed,
+    Settlement(SettlementError),
+}
+
+/// Final score of a `Game`
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Queryable, ToSchema)]
+pub struct GameResult {
+    pub id: i32,
+    pub home: i32,
+    pub away: i32,
+    pub game_id: i32,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Insertable, ToSchema)]
+#[table_name = "game_results"]
+pub struct NewGameResult {
+    pub home: i32,
+    pub away: i32,
+    pub game_id: i32,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GameResultQuery {
+    pub game_id: Option<i32>,
+}
+
+/// A change to a recorded final score
+#[derive(Clone, Debug, Deserialize, Serialize, Queryable, ToSchema)]
+pub struct ResultCorrection {
+    pub id: i32,
+    pub result_id: i32,
+    /// The user who made the correction
+    pub user_id: i32,
+    pub old_home: i32,
+    pub old_away: i32,
+    pub new_home: i32,
+    pub new_away: i32,
+    pub corrected_at: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Insertable)]
+#[table_name = "result_corrections"]
+struct NewResultCorrection {
+    result_id: i32,
+    user_id: i32,
+    old_home: i32,
+    old_away: i32,
+    new_home: i32,
+    new_away: i32,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+/////// Implementations ///////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl fmt::Display for ResultError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResultError::NegativeScore => write!(f, "NegativeScore"),
+            ResultError::Tie => write!(f, "Tie"),
+            ResultError::NotStarted => write!(f, "NotStarted"),
+            ResultError::NotPlayed => write!(f, "NotPlayed"),
+            ResultError::Settlement(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for ResultError {}
+
+impl From<SettlementError> for ResultError {
+    fn from(e: SettlementError) -> Self {
+        ResultError::Settlement(e)
+    }
+}
+
+impl From<DieselError> for ResultError {
+    fn from(e: DieselError) -> Self {
+        ResultError::Settlement(SettlementError::Db(e))
+    }
+}
+
+/// Check that `home`-`away` is a possible final score for `game`, played in `league`, at `now`:
+/// neither score is negative, the game is only level if the league allows draws and the game has
+/// started and wasn't called off.
+pub fn validate_score(
+    game: &Game,
+    league: &League,
+    home: i32,
+    away: i32,
+    now: NaiveDateTime,
+) -> Result<(), ResultError> {
+    if home < 0 || away < 0 {
+        return Err(ResultError::NegativeScore);
+    }
+    if !league.draws_allowed && home == away {
+        return Err(ResultError::Tie);
+    }
+    if now < game.start {
+        return Err(ResultError::NotStarted);
+    }
+    if matches!(game.status, GameStatus::Postponed | GameStatus::Cancelled) {
+        return Err(ResultError::NotPlayed);
+    }
+    Ok(())
+}
+
+impl GameResult {
+    /// Retrieve the result with the given `id`.
+    pub fn get(conn: &PgConnection, id: i32) -> Result<GameResult, DieselError> {
+        results_dsl::game_results.find(id).first(conn)
+    }
+
+    /// The result of the game with the given `game_id`, if it has one.
+    pub fn for_game(conn: &PgConnection, game_id: i32) -> Result<Option<GameResult>, DieselError> {
+        results_dsl::game_results
+            .filter(results_dsl::game_id.eq(game_id))
+            .first(conn)
+            .optional()
+    }
+
+    /// Correct the final score to `home`-`away` on behalf of `user_id`, recording the change, and
+    /// re-settle the game.
+    pub fn correct(
+        &self,
+        conn: &PgConnection,
+        home: i32,
+        away: i32,
+        user_id: i32,
+    ) -> Result<GameResult, ResultError> {
+        conn.transaction(|| {
+            let current = GameResult::get(conn, self.id)?;
+            diesel::insert_into(corrections_dsl::result_corrections)
+                .values(&NewResultCorrection {
+                    result_id: current.id,
+                    user_id,
+                    old_home: current.home,
+                    old_away: current.away,
+                    new_home: home,
+                    new_away: away,
+                })
+                .execute(conn)?;
+            GameResult {
+                home,
+                away,
+                ..current
+            }
+            .update(conn)
+        })
+    }
+
+    /// Every correction made to the result, oldest first.
+    pub fn corrections(&self, conn: &PgConnection) -> Result<Vec<ResultCorrection>, DieselError> {
+        corrections_dsl::result_corrections
+            .filter(corrections_dsl::result_id.eq(self.id))
+            .order_by(corrections_dsl::corrected_at.asc())
+            .load(conn)
+    }
+}
+
+impl Creatable<ResultError> for NewGameResult {
+    type Output = GameResult;
+    /// Record the final score, mark the game final and settle every bet on it in the same
+    /// transaction.
+    fn create(&self, conn: &PgConnection) -> Result<GameResult, ResultError> {
+        conn.transaction(|| {
+            let game = Game::get(conn, self.game_id)?;
+            let league = League::get(conn, &game.league)?;
+            validate_score(&game, &league, self.home, self.away, Utc::now().naive_utc())?;
+            let result: GameResult = diesel::insert_into(results_dsl::game_results)
+                .values(self)
+                .get_result(conn)?;
+            if game.status != GameStatus::Final {
+                game.record_transition(conn, GameStatus::Final, None)?;
+            }
+            settle_game(conn, result.game_id)?;
+            Ok(result)
+        })
+    }
+}
+
+impl Updatable<PgConnection, GameResult, ResultError> for GameResult {
+    /// Change the final score and re-settle the game against it. This leaves no audit trail;
+    /// corrections should go through `GameResult::correct`.
+    fn update(&self, conn: &PgConnection) -> Result<GameResult, ResultError> {
+        conn.transaction(|| {
+            let game = Game::get(conn, self.game_id)?;
+            let league = League::get(conn, &game.league)?;
+            validate_score(&game, &league, self.home, self.away, Utc::now().naive_utc())?;
+            let result: GameResult = diesel::update(results_dsl::game_results)
+                .filter(results_dsl::id.eq(&self.id))
+                .set((
+                    results_dsl::home.eq(&self.home),
+                    results_dsl::away.eq(&self.away),
+                ))
+                .get_result(conn)?;
+            settle_game(conn, result.game_id)?;
+            Ok(result)
+        })
+    }
+}
+
+impl Retrievable<GameResultQuery> for GameResult {
+    fn query(conn: &PgConnection, data: &GameResultQuery) -> Result<Vec<GameResult>, DieselError> {
+        match data.game_id {
+            Some(_game_id) => results_dsl::game_results
+                .filter(results_dsl::game_id.eq(_game_id))
+                .load(conn),
+            None => results_dsl::game_results.load(conn),
+        }
+    }
+
+    fn all(conn: &PgConnection) -> Result<Vec<GameResult>, DieselError> {
+        results_dsl::game_results
+            .order_by(results_dsl::id.desc())
+            .load(conn)
+    }
+}
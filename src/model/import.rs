@@ -0,0 +1,300 @@
+//! Schedule import
+//!
+//! Reads a league's schedule from CSV or JSON and turns it into games. Each row names the home and
+//! away teams by abbreviation, full name or alias, and a start time in the row's `timezone` (or
+//! the file's, or UTC). CSV files have a `home,away,start,timezone` header; JSON files are an
+//! array of objects with the same fields.
+//!
+//! Importing is two steps. `ImportPlan::new` is a dry run: it resolves every row and says whether
+//! it would create a game, skip a duplicate of a game already scheduled (or of an earlier row), or
+//! reject the row. `commit` plans again and schedules the new games in a single transaction, and
+//! refuses to write anything if any row is rejected.
+use crate::db::Retrievable;
+use crate::model::league::League;
+use crate::model::market::MarketError;
+use crate::model::team::{Team, TeamQuery};
+use crate::model::{Game, GameStatus, NewGame};
+use crate::schema::games::dsl as games_dsl;
+
+use chrono::{DateTime, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+use diesel::{Connection, ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use std::{error, fmt};
+
+/// Formats a start time can be given in when it has no UTC offset
+const START_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%dT%H:%M:%S",
+];
+
+#[derive(Debug)]
+pub enum ImportError {
+    /// The file isn't CSV or JSON in the expected shape
+    Parse(String),
+    /// Some rows can't be imported, so nothing was
+    Rejected(usize),
+    Market(MarketError),
+    Db(DieselError),
+}
+
+/// One game in a schedule file
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ScheduleRow {
+    pub home: String,
+    pub away: String,
+    /// Local start time, or an RFC 3339 time with its own offset
+    pub start: String,
+    /// IANA timezone `start` is in, e.g. `America/New_York`
+    pub timezone: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub enum Action {
+    Create,
+    Skip,
+    Reject,
+}
+
+/// What importing a row would do. `home`, `away` and `start` are resolved to abbreviations and
+/// UTC where possible; `reason` says why a row is skipped or rejected.
+#[derive(Clone, Debug, Serialize)]
+pub struct PlannedGame {
+    /// The row's position in the file, counting from 1
+    pub row: usize,
+    pub home: String,
+    pub away: String,
+    pub start: Option<NaiveDateTime>,
+    pub action: Action,
+    pub reason: Option<String>,
+}
+
+/// The result of a dry run: every row of a schedule and what importing it would do
+#[derive(Clone, Debug, Serialize)]
+pub struct ImportPlan {
+    pub league: String,
+    pub games: Vec<PlannedGame>,
+    pub created: usize,
+    pub skipped: usize,
+    pub rejected: usize,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+/////// Implementations ///////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::Parse(e) => write!(f, "Parse: {}", e),
+            ImportError::Rejected(n) => write!(f, "Rejected: {} rows", n),
+            ImportError::Market(e) => write!(f, "{}", e),
+            ImportError::Db(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for ImportError {}
+
+impl From<DieselError> for ImportError {
+    fn from(e: DieselError) -> Self {
+        ImportError::Db(e)
+    }
+}
+
+impl From<MarketError> for ImportError {
+    fn from(e: MarketError) -> Self {
+        match e {
+            MarketError::Db(e) => ImportError::Db(e),
+            e => ImportError::Market(e),
+        }
+    }
+}
+
+/// Read the rows of a schedule file. Files whose first character is `[` are read as JSON, any
+/// other file as CSV.
+pub fn parse(data: &[u8]) -> Result<Vec<ScheduleRow>, ImportError> {
+    let is_json = data
+        .iter()
+        .find(|b| !b.is_ascii_whitespace())
+        .map_or(false, |&b| b == b'[');
+    if is_json {
+        return serde_json::from_slice(data).map_err(|e| ImportError::Parse(e.to_string()));
+    }
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data)
+        .deserialize()
+        .collect::<Result<Vec<ScheduleRow>, csv::Error>>()
+        .map_err(|e| ImportError::Parse(e.to_string()))
+}
+
+/// Convert `start` to UTC. Times with a UTC offset keep it; local times are read in `timezone`.
+pub fn to_utc(start: &str, timezone: &str) -> Result<NaiveDateTime, String> {
+    let start = start.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(start) {
+        return Ok(dt.naive_utc());
+    }
+    let tz: Tz = timezone
+        .trim()
+        .parse()
+        .map_err(|_| format!("unknown timezone '{}'", timezone))?;
+    let local = START_FORMATS
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(start, f).ok())
+        .ok_or_else(|| format!("unreadable start time '{}'", start))?;
+    tz.from_local_datetime(&local)
+        .single()
+        .map(|dt| dt.naive_utc())
+        .ok_or_else(|| format!("{} is ambiguous or skipped in {}", start, tz))
+}
+
+/// Whether `planned` is the game between `home` and `away` at `start`, give or take a day
+fn same_fixture(planned: &PlannedGame, home: &str, away: &str, start: NaiveDateTime) -> bool {
+    planned.home == home
+        && planned.away == away
+        && planned
+            .start
+            .map_or(false, |s| (s - start).num_hours().abs() < 24)
+}
+
+impl ImportPlan {
+    /// Dry-run importing `rows` into `league`, reading local start times in `timezone` unless a
+    /// row gives its own. Cancelled games don't count as duplicates, so they can be rescheduled.
+    pub fn new(
+        conn: &PgConnection,
+        league: &str,
+        rows: &[ScheduleRow],
+        timezone: &str,
+    ) -> Result<ImportPlan, DieselError> {
+        let league = League::get(conn, league)?;
+        let teams = Team::query(
+            conn,
+            &TeamQuery {
+                league: Some(league.code.clone()),
+                active: None,
+            },
+        )?;
+        let existing: Vec<Game> = games_dsl::games
+            .filter(games_dsl::league.eq(&league.code))
+            .filter(games_dsl::status.ne(GameStatus::Cancelled))
+            .load(conn)?;
+        Ok(ImportPlan::build(
+            &league.code,
+            rows,
+            &teams,
+            &existing,
+            timezone,
+        ))
+    }
+
+    /// Plan importing `rows` into `league` against the league's `teams` and `existing` games.
+    /// Two games between the same teams starting within a day of each other are duplicates.
+    pub fn build(
+        league: &str,
+        rows: &[ScheduleRow],
+        teams: &[Team],
+        existing: &[Game],
+        timezone: &str,
+    ) -> ImportPlan {
+        // Active teams win over retired teams that went by the same name
+        let resolve = |name: &str| {
+            teams
+                .iter()
+                .filter(|t| t.goes_by(name))
+                .max_by_key(|t| t.active)
+                .map(|t| t.abbreviation.clone())
+                .ok_or_else(|| format!("unknown team '{}'", name))
+        };
+        let mut games: Vec<PlannedGame> = Vec::new();
+        for (i, row) in rows.iter().enumerate() {
+            let home = resolve(&row.home);
+            let away = resolve(&row.away);
+            let start = to_utc(&row.start, row.timezone.as_deref().unwrap_or(timezone));
+            let mut planned = PlannedGame {
+                row: i + 1,
+                home: home.clone().unwrap_or_else(|_| row.home.clone()),
+                away: away.clone().unwrap_or_else(|_| row.away.clone()),
+                start: start.clone().ok(),
+                action: Action::Create,
+                reason: None,
+            };
+            let problem = [home.err(), away.err(), start.err()]
+                .into_iter()
+                .flatten()
+                .next();
+            if let Some(problem) = problem {
+                planned.action = Action::Reject;
+                planned.reason = Some(problem);
+            } else if planned.home == planned.away {
+                planned.action = Action::Reject;
+                planned.reason = Some(format!("{} can't play itself", planned.home));
+            } else if let Some(game) = existing
+                .iter()
+                .find(|g| same_fixture(&planned, &g.home, &g.away, g.start))
+            {
+                planned.action = Action::Skip;
+                planned.reason = Some(format!("already scheduled as game {}", game.id));
+            } else if let Some(earlier) = games.iter().find(|g| {
+                g.action == Action::Create
+                    && g.start
+                        .map_or(false, |s| same_fixture(&planned, &g.home, &g.away, s))
+            }) {
+                planned.action = Action::Skip;
+                planned.reason = Some(format!("duplicate of row {}", earlier.row));
+            }
+            games.push(planned);
+        }
+
+        let count = |action| games.iter().filter(|g| g.action == action).count();
+        ImportPlan {
+            league: league.to_string(),
+            created: count(Action::Create),
+            skipped: count(Action::Skip),
+            rejected: count(Action::Reject),
+            games,
+        }
+    }
+
+    /// The games the plan would create
+    pub fn new_games(&self) -> Vec<NewGame> {
+        self.games
+            .iter()
+            .filter(|g| g.action == Action::Create)
+            .filter_map(|g| {
+                Some(NewGame {
+                    league: self.league.clone(),
+                    home: g.home.clone(),
+                    away: g.away.clone(),
+                    start: g.start?,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Import `rows` into `league`, reading local start times in `timezone` unless a row gives its
+/// own. Every new game is created, and its league's default markets opened, in one transaction;
+/// duplicates are skipped, and if any row is rejected nothing is imported.
+pub fn commit(
+    conn: &PgConnection,
+    league: &str,
+    rows: &[ScheduleRow],
+    timezone: &str,
+) -> Result<Vec<Game>, ImportError> {
+    conn.transaction(|| {
+        let plan = ImportPlan::new(conn, league, rows, timezone)?;
+        if plan.rejected > 0 {
+            return Err(ImportError::Rejected(plan.rejected));
+        }
+        plan.new_games()
+            .iter()
+            .map(|game| Ok(game.schedule(conn)?))
+            .collect()
+    })
+}
@@ -0,0 +1,177 @@
+//! Leagues
+//!
+//! Leagues are configured in the database rather than in code, so a new competition is a new row.
+//! Each league carries the settings that differ between competitions: whether a game can end in a
+//! draw, how many periods a game has and the `MarketTemplate`s opened on every new game.
+use crate::db::{Creatable, Deletable, Retrievable, Updatable};
+use crate::model::market::{Market, MarketError, MarketKind, NewMarket, TeamSide};
+use crate::model::Game;
+use crate::odds::Odds;
+use crate::schema::leagues::{self, dsl as leagues_dsl};
+use crate::schema::market_templates::{self, dsl as templates_dsl};
+
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+use diesel::{Connection, ExpressionMethods, Insertable, QueryDsl, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// A competition games are played in, such as the NBA or the Premier League
+#[derive(Clone, Debug, Deserialize, Serialize, Queryable, Insertable, ToSchema)]
+#[table_name = "leagues"]
+pub struct League {
+    /// Short code used in URLs and on games, e.g. `NBA`
+    pub code: String,
+    pub name: String,
+    pub sport: String,
+    /// Whether a game can end level
+    pub draws_allowed: bool,
+    /// Quarters, halves, periods or innings in a game
+    pub periods: i32,
+    pub active: bool,
+}
+
+/// A market opened on every new game in `league`, with both selections priced at `odds`. For
+/// spreads and totals `line` is in half-points, as on `Market`.
+#[derive(Clone, Debug, Deserialize, Serialize, Queryable, ToSchema)]
+pub struct MarketTemplate {
+    pub id: i32,
+    pub league: String,
+    pub kind: MarketKind,
+    pub team: Option<TeamSide>,
+    pub line: Option<i32>,
+    pub odds: Odds,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Insertable, ToSchema)]
+#[table_name = "market_templates"]
+pub struct NewMarketTemplate {
+    pub league: String,
+    pub kind: MarketKind,
+    pub team: Option<TeamSide>,
+    pub line: Option<i32>,
+    pub odds: Odds,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LeagueQuery {
+    pub active: Option<bool>,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+/////// Implementations ///////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl League {
+    /// Retrieve the league with the given `code`.
+    pub fn get(conn: &PgConnection, code: &str) -> Result<League, DieselError> {
+        leagues_dsl::leagues.find(code).first(conn)
+    }
+
+    /// The markets opened on every new game in the league.
+    pub fn templates(&self, conn: &PgConnection) -> Result<Vec<MarketTemplate>, DieselError> {
+        templates_dsl::market_templates
+            .filter(templates_dsl::league.eq(&self.code))
+            .order_by(templates_dsl::id.asc())
+            .load(conn)
+    }
+
+    /// Open a market on `game` for each of the league's templates.
+    pub fn open_markets(
+        &self,
+        conn: &PgConnection,
+        game: &Game,
+    ) -> Result<Vec<Market>, MarketError> {
+        conn.transaction(|| {
+            self.templates(conn)?
+                .iter()
+                .map(|t| Market::open(conn, game, &t.to_new_market(game), [t.odds, t.odds]))
+                .collect()
+        })
+    }
+}
+
+impl MarketTemplate {
+    /// Retrieve the template with the given `id`.
+    pub fn get(conn: &PgConnection, id: i32) -> Result<MarketTemplate, DieselError> {
+        templates_dsl::market_templates.find(id).first(conn)
+    }
+
+    /// The market this template opens on `game`
+    pub fn to_new_market(&self, game: &Game) -> NewMarket {
+        NewMarket {
+            game_id: game.id,
+            kind: self.kind,
+            team: self.team,
+            line: self.line,
+        }
+    }
+}
+
+impl Creatable for League {
+    type Output = League;
+    fn create(&self, conn: &PgConnection) -> Result<League, DieselError> {
+        diesel::insert_into(leagues_dsl::leagues)
+            .values(self)
+            .get_result(conn)
+    }
+}
+
+impl Updatable for League {
+    /// Change the league's settings. Its code can't be changed.
+    fn update(&self, conn: &PgConnection) -> Result<League, DieselError> {
+        diesel::update(leagues_dsl::leagues.find(&self.code))
+            .set((
+                leagues_dsl::name.eq(&self.name),
+                leagues_dsl::sport.eq(&self.sport),
+                leagues_dsl::draws_allowed.eq(&self.draws_allowed),
+                leagues_dsl::periods.eq(&self.periods),
+                leagues_dsl::active.eq(&self.active),
+            ))
+            .get_result(conn)
+    }
+}
+
+impl Retrievable<LeagueQuery> for League {
+    fn query(conn: &PgConnection, data: &LeagueQuery) -> Result<Vec<League>, DieselError> {
+        match data.active {
+            Some(_active) => leagues_dsl::leagues
+                .filter(leagues_dsl::active.eq(_active))
+                .order_by(leagues_dsl::code.asc())
+                .load(conn),
+            None => leagues_dsl::leagues
+                .order_by(leagues_dsl::code.asc())
+                .load(conn),
+        }
+    }
+
+    /// Every active league
+    fn all(conn: &PgConnection) -> Result<Vec<League>, DieselError> {
+        League::query(conn, &LeagueQuery { active: Some(true) })
+    }
+}
+
+impl Creatable<MarketError> for NewMarketTemplate {
+    type Output = MarketTemplate;
+    /// Record the template, refusing ones that wouldn't open a valid market.
+    fn create(&self, conn: &PgConnection) -> Result<MarketTemplate, MarketError> {
+        NewMarket {
+            game_id: 0,
+            kind: self.kind,
+            team: self.team,
+            line: self.line,
+        }
+        .selections()?;
+        Ok(diesel::insert_into(templates_dsl::market_templates)
+            .values(self)
+            .get_result(conn)?)
+    }
+}
+
+impl Deletable for MarketTemplate {
+    fn delete(&self, conn: &PgConnection) -> Result<MarketTemplate, DieselError> {
+        diesel::delete(templates_dsl::market_templates.find(self.id)).get_result(conn)
+    }
+}
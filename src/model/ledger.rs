@@ -0,0 +1,213 @@
+//! Double-entry ledger for punter wallets
+//!
+//! Money is never stored as a balance. Every movement is an `Entry` that takes `amount` cents out
+//! of one `Account` and puts it into another, and an account's balance is derived from the entries
+//! that touch it. Money enters and leaves the book through the `External` account.
+use crate::db::{Creatable, Retrievable};
+use crate::schema::accounts::{self, dsl as accounts_dsl};
+use crate::schema::ledger_entries::{self, dsl as entries_dsl};
+
+use chrono::NaiveDateTime;
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+use diesel::{
+    BoolExpressionMethods, Connection, ExpressionMethods, Insertable, QueryDsl, Queryable,
+    RunQueryDsl,
+};
+use diesel_derive_enum::DbEnum;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, DbEnum, Deserialize, Serialize, PartialEq)]
+pub enum AccountKind {
+    Wallet,
+    House,
+    Escrow,
+    External,
+}
+
+#[derive(Clone, Copy, Debug, DbEnum, Deserialize, Serialize, PartialEq)]
+pub enum EntryKind {
+    Deposit,
+    Stake,
+    Payout,
+    Refund,
+    Adjustment,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Queryable)]
+pub struct Account {
+    pub id: i32,
+    pub kind: AccountKind,
+    pub user_id: Option<i32>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Insertable)]
+#[table_name = "accounts"]
+pub struct NewAccount {
+    pub kind: AccountKind,
+    pub user_id: Option<i32>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Queryable)]
+pub struct Entry {
+    pub id: i32,
+    pub kind: EntryKind,
+    pub from_account_id: i32,
+    pub to_account_id: i32,
+    pub amount: i64,
+    pub bet_id: Option<i32>,
+    pub memo: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Insertable)]
+#[table_name = "ledger_entries"]
+pub struct NewEntry {
+    pub kind: EntryKind,
+    pub from_account_id: i32,
+    pub to_account_id: i32,
+    pub amount: i64,
+    pub bet_id: Option<i32>,
+    pub memo: String,
+}
+
+/// Whose wallet to show; the caller's own when `user_id` is `None`
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct WalletQuery {
+    pub user_id: Option<i32>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct EntryQuery {
+    pub account_id: Option<i32>,
+    pub bet_id: Option<i32>,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+/////// Implementations ///////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl Account {
+    /// Retrieve the wallet belonging to `user_id`, opening one if the user doesn't have one yet.
+    /// Opening is a no-op when the wallet already exists, so concurrent callers can't race.
+    pub fn wallet(conn: &PgConnection, user_id: i32) -> Result<Account, DieselError> {
+        diesel::insert_into(accounts_dsl::accounts)
+            .values(&NewAccount {
+                kind: AccountKind::Wallet,
+                user_id: Some(user_id),
+            })
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+        accounts_dsl::accounts
+            .filter(accounts_dsl::user_id.eq(user_id))
+            .first(conn)
+    }
+
+    /// Retrieve one of the book's own accounts (house, escrow or external).
+    pub fn system(conn: &PgConnection, kind: AccountKind) -> Result<Account, DieselError> {
+        accounts_dsl::accounts
+            .filter(accounts_dsl::kind.eq(kind))
+            .filter(accounts_dsl::user_id.is_null())
+            .first(conn)
+    }
+
+    /// Lock the account's row until the end of the current transaction so that concurrent
+    /// transfers out of it are serialized.
+    pub fn lock(&self, conn: &PgConnection) -> Result<Account, DieselError> {
+        accounts_dsl::accounts
+            .find(self.id)
+            .for_update()
+            .first(conn)
+    }
+
+    /// Derive the account's balance in cents from every entry that moved money in or out of it.
+    pub fn balance(&self, conn: &PgConnection) -> Result<i64, DieselError> {
+        let credits: Vec<i64> = entries_dsl::ledger_entries
+            .filter(entries_dsl::to_account_id.eq(self.id))
+            .select(entries_dsl::amount)
+            .load(conn)?;
+        let debits: Vec<i64> = entries_dsl::ledger_entries
+            .filter(entries_dsl::from_account_id.eq(self.id))
+            .select(entries_dsl::amount)
+            .load(conn)?;
+        Ok(credits.iter().sum::<i64>() - debits.iter().sum::<i64>())
+    }
+
+    /// Move `amount` cents from the external account into `user_id`'s wallet.
+    pub fn deposit(conn: &PgConnection, user_id: i32, amount: i64) -> Result<Entry, DieselError> {
+        conn.transaction(|| {
+            let wallet = Account::wallet(conn, user_id)?;
+            let external = Account::system(conn, AccountKind::External)?;
+            NewEntry::new(EntryKind::Deposit, &external, &wallet, amount).create(conn)
+        })
+    }
+}
+
+impl NewEntry {
+    pub fn new(kind: EntryKind, from: &Account, to: &Account, amount: i64) -> Self {
+        NewEntry {
+            kind,
+            from_account_id: from.id,
+            to_account_id: to.id,
+            amount,
+            bet_id: None,
+            memo: String::new(),
+        }
+    }
+
+    /// Attach the entry to the bet it was made for.
+    pub fn bet(mut self, bet_id: i32) -> Self {
+        self.bet_id = Some(bet_id);
+        self
+    }
+
+    pub fn memo(mut self, memo: &str) -> Self {
+        self.memo = memo.to_owned();
+        self
+    }
+}
+
+impl Creatable for NewAccount {
+    type Output = Account;
+    fn create(&self, conn: &PgConnection) -> Result<Account, DieselError> {
+        diesel::insert_into(accounts_dsl::accounts)
+            .values(self)
+            .get_result(conn)
+    }
+}
+
+impl Creatable for NewEntry {
+    type Output = Entry;
+    fn create(&self, conn: &PgConnection) -> Result<Entry, DieselError> {
+        diesel::insert_into(entries_dsl::ledger_entries)
+            .values(self)
+            .get_result(conn)
+    }
+}
+
+impl Retrievable<EntryQuery> for Entry {
+    fn query(conn: &PgConnection, data: &EntryQuery) -> Result<Vec<Entry>, DieselError> {
+        let mut query = entries_dsl::ledger_entries.into_boxed();
+        if let Some(_account_id) = data.account_id {
+            query = query.filter(
+                entries_dsl::from_account_id
+                    .eq(_account_id)
+                    .or(entries_dsl::to_account_id.eq(_account_id)),
+            );
+        }
+        if let Some(_bet_id) = data.bet_id {
+            query = query.filter(entries_dsl::bet_id.eq(_bet_id));
+        }
+        query
+            .order_by((entries_dsl::created_at.desc(), entries_dsl::id.desc()))
+            .load(conn)
+    }
+
+    fn all(conn: &PgConnection) -> Result<Vec<Entry>, DieselError> {
+        entries_dsl::ledger_entries
+            .limit(100)
+            .order_by(entries_dsl::id.desc())
+            .load(conn)
+    }
+}
@@ -0,0 +1,456 @@
+//! Typed betting markets
+//!
+//! A `Market` is a two-way proposition on a `Game`, such as a point spread, and each of its
+//! mutually exclusive outcomes is an `Event` carrying a typed `Selection`. Selections describe
+//! exactly what they pay out on, which is what lets the settlement engine grade them and lets
+//! event descriptions be generated instead of typed in. Margin, liability and suspension are
+//! properties of the whole market.
+use crate::db::{Creatable, Retrievable, Updatable};
+use crate::model::bet::{Bet, BetStatus};
+use crate::model::game_result::GameResult;
+use crate::model::settlement::Grade;
+use crate::model::{Event, Game, NewEvent};
+use crate::odds::{self, Odds};
+use crate::schema::bets::dsl as bets_dsl;
+use crate::schema::events::dsl as events_dsl;
+use crate::schema::markets::{self, dsl as markets_dsl};
+
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+use diesel::{Connection, ExpressionMethods, Insertable, QueryDsl, Queryable, RunQueryDsl};
+use diesel_derive_enum::DbEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::{error, fmt};
+use utoipa::ToSchema;
+
+#[derive(Clone, Copy, Debug, DbEnum, Deserialize, Serialize, PartialEq, ToSchema)]
+pub enum MarketKind {
+    Moneyline,
+    Spread,
+    Total,
+    TeamTotal,
+}
+
+#[derive(Clone, Copy, Debug, DbEnum, Deserialize, Serialize, PartialEq, ToSchema)]
+pub enum TeamSide {
+    Home,
+    Away,
+}
+
+#[derive(Clone, Copy, Debug, DbEnum, Deserialize, Serialize, PartialEq, ToSchema)]
+pub enum TotalSide {
+    Over,
+    Under,
+}
+
+/// A spread or total line, stored as a whole number of half-points so that -5.5 is `Line(-11)`.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Line(pub i32);
+
+/// A single selection in a market on a game
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub enum Selection {
+    Moneyline(TeamSide),
+    Spread(TeamSide, Line),
+    Total(TotalSide, Line),
+    TeamTotal(TeamSide, TotalSide, Line),
+}
+
+#[derive(Debug)]
+pub enum MarketError {
+    MissingLine,
+    MissingSide,
+    InvalidLine(String),
+    Db(DieselError),
+}
+
+/// A market on a game. For spreads `line` is the home team's line; for game and team totals it
+/// is the total, and `team` is set for team totals only. Lines are in half-points.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Queryable)]
+pub struct Market {
+    pub id: i32,
+    pub game_id: i32,
+    pub kind: MarketKind,
+    pub team: Option<TeamSide>,
+    pub line: Option<i32>,
+    pub suspended: bool,
+    /// Whether the market was suspended because its game's bets were voided
+    pub voided: bool,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Insertable)]
+#[table_name = "markets"]
+pub struct NewMarket {
+    pub game_id: i32,
+    pub kind: MarketKind,
+    pub team: Option<TeamSide>,
+    pub line: Option<i32>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct MarketQuery {
+    pub game_id: Option<i32>,
+}
+
+/// What the house stands to win or lose on a market if one of its selections wins. Amounts are in
+/// cents and only count bets that are still open.
+#[derive(Clone, Debug, Serialize)]
+pub struct SelectionLiability {
+    pub event_id: i32,
+    pub description: String,
+    pub odds: Odds,
+    pub stake: i64,
+    pub payout: i64,
+    pub house_result: i64,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+/////// Implementations ///////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl fmt::Display for MarketError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MarketError::MissingLine => write!(f, "MissingLine"),
+            MarketError::MissingSide => write!(f, "MissingSide"),
+            MarketError::InvalidLine(l) => write!(f, "InvalidLine: {}", l),
+            MarketError::Db(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for MarketError {}
+
+impl From<DieselError> for MarketError {
+    fn from(e: DieselError) -> Self {
+        MarketError::Db(e)
+    }
+}
+
+impl Line {
+    /// Create a line from a number of points, which must be a multiple of 0.5.
+    pub fn from_points(points: f64) -> Result<Line, MarketError> {
+        let halves = points * 2.0;
+        if halves.is_finite() && halves.fract() == 0.0 {
+            Ok(Line(halves as i32))
+        } else {
+            Err(MarketError::InvalidLine(points.to_string()))
+        }
+    }
+
+    pub fn points(&self) -> f64 {
+        self.0 as f64 / 2.0
+    }
+}
+
+impl std::str::FromStr for Line {
+    type Err = MarketError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("pk") {
+            return Ok(Line(0));
+        }
+        s.parse::<f64>()
+            .map_err(|_| MarketError::InvalidLine(s.to_owned()))
+            .and_then(Line::from_points)
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.points())
+    }
+}
+
+impl Selection {
+    /// Assemble a market from its stored columns. Sides that the market kind doesn't use are
+    /// ignored; missing ones are an error.
+    pub fn from_parts(
+        kind: MarketKind,
+        team: Option<TeamSide>,
+        total: Option<TotalSide>,
+        line: Option<Line>,
+    ) -> Result<Selection, MarketError> {
+        let need_team = || team.ok_or(MarketError::MissingSide);
+        let need_total = || total.ok_or(MarketError::MissingSide);
+        let need_line = || line.ok_or(MarketError::MissingLine);
+        match kind {
+            MarketKind::Moneyline => Ok(Selection::Moneyline(need_team()?)),
+            MarketKind::Spread => Ok(Selection::Spread(need_team()?, need_line()?)),
+            MarketKind::Total => Ok(Selection::Total(need_total()?, need_line()?)),
+            MarketKind::TeamTotal => Ok(Selection::TeamTotal(
+                need_team()?,
+                need_total()?,
+                need_line()?,
+            )),
+        }
+    }
+
+    pub fn kind(&self) -> MarketKind {
+        match self {
+            Selection::Moneyline(_) => MarketKind::Moneyline,
+            Selection::Spread(..) => MarketKind::Spread,
+            Selection::Total(..) => MarketKind::Total,
+            Selection::TeamTotal(..) => MarketKind::TeamTotal,
+        }
+    }
+
+    pub fn team(&self) -> Option<TeamSide> {
+        match *self {
+            Selection::Moneyline(team)
+            | Selection::Spread(team, _)
+            | Selection::TeamTotal(team, _, _) => Some(team),
+            Selection::Total(..) => None,
+        }
+    }
+
+    pub fn total(&self) -> Option<TotalSide> {
+        match *self {
+            Selection::Total(total, _) | Selection::TeamTotal(_, total, _) => Some(total),
+            _ => None,
+        }
+    }
+
+    pub fn line(&self) -> Option<Line> {
+        match *self {
+            Selection::Moneyline(_) => None,
+            Selection::Spread(_, line)
+            | Selection::Total(_, line)
+            | Selection::TeamTotal(_, _, line) => Some(line),
+        }
+    }
+
+    /// Human readable description of the selection, e.g. `LAL -5.5` or `BOS vs LAL O 221.5`.
+    pub fn describe(&self, game: &Game) -> String {
+        let name = |team: &TeamSide| match team {
+            TeamSide::Home => game.home.as_str(),
+            TeamSide::Away => game.away.as_str(),
+        };
+        let over_under = |total: &TotalSide| match total {
+            TotalSide::Over => "O",
+            TotalSide::Under => "U",
+        };
+        match self {
+            Selection::Moneyline(team) => format!("{} ML", name(team)),
+            Selection::Spread(team, Line(0)) => format!("{} PK", name(team)),
+            Selection::Spread(team, line) => format!("{} {:+}", name(team), line.points()),
+            Selection::Total(total, line) => format!(
+                "{} vs {} {} {}",
+                game.home,
+                game.away,
+                over_under(total),
+                line
+            ),
+            Selection::TeamTotal(team, total, line) => {
+                format!("{} {} {}", name(team), over_under(total), line)
+            }
+        }
+    }
+
+    /// Grade the selection against a game's final score.
+    pub fn grade(&self, result: &GameResult) -> Grade {
+        // Half-points keep the arithmetic in integers
+        let (home, away) = (result.home * 2, result.away * 2);
+        let margin = |team: &TeamSide| match team {
+            TeamSide::Home => home - away,
+            TeamSide::Away => away - home,
+        };
+        let score = |team: &TeamSide| match team {
+            TeamSide::Home => home,
+            TeamSide::Away => away,
+        };
+        let over_under = |total: &TotalSide, points: i32, line: &Line| match total {
+            TotalSide::Over => points - line.0,
+            TotalSide::Under => line.0 - points,
+        };
+        Grade::from_margin(match self {
+            Selection::Moneyline(team) => margin(team),
+            Selection::Spread(team, line) => margin(team) + line.0,
+            Selection::Total(total, line) => over_under(total, home + away, line),
+            Selection::TeamTotal(team, total, line) => over_under(total, score(team), line),
+        })
+    }
+}
+
+impl NewMarket {
+    /// The market's two selections, home or over first.
+    pub fn selections(&self) -> Result<[Selection; 2], MarketError> {
+        let line = || self.line.map(Line).ok_or(MarketError::MissingLine);
+        match self.kind {
+            MarketKind::Moneyline => Ok([
+                Selection::Moneyline(TeamSide::Home),
+                Selection::Moneyline(TeamSide::Away),
+            ]),
+            MarketKind::Spread => {
+                let line = line()?;
+                Ok([
+                    Selection::Spread(TeamSide::Home, line),
+                    Selection::Spread(TeamSide::Away, Line(-line.0)),
+                ])
+            }
+            MarketKind::Total => Ok([
+                Selection::Total(TotalSide::Over, line()?),
+                Selection::Total(TotalSide::Under, line()?),
+            ]),
+            MarketKind::TeamTotal => {
+                let team = self.team.ok_or(MarketError::MissingSide)?;
+                Ok([
+                    Selection::TeamTotal(team, TotalSide::Over, line()?),
+                    Selection::TeamTotal(team, TotalSide::Under, line()?),
+                ])
+            }
+        }
+    }
+}
+
+impl Market {
+    /// Retrieve the market with the given `id`.
+    pub fn get(conn: &PgConnection, id: i32) -> Result<Market, DieselError> {
+        markets_dsl::markets.find(id).first(conn)
+    }
+
+    /// Resume the markets on `game_id` that were suspended when its bets were voided. Markets a
+    /// trader suspended stay suspended.
+    pub fn resume_voided(conn: &PgConnection, game_id: i32) -> Result<Vec<Market>, DieselError> {
+        diesel::update(markets_dsl::markets)
+            .filter(markets_dsl::game_id.eq(game_id))
+            .filter(markets_dsl::voided.eq(true))
+            .set((
+                markets_dsl::suspended.eq(false),
+                markets_dsl::voided.eq(false),
+            ))
+            .get_results(conn)
+    }
+
+    /// Open a new market on `game` together with its two selections, priced at `prices` in the
+    /// order given by `NewMarket::selections`.
+    pub fn open(
+        conn: &PgConnection,
+        game: &Game,
+        new: &NewMarket,
+        prices: [Odds; 2],
+    ) -> Result<Market, MarketError> {
+        let selections = new.selections()?;
+        conn.transaction(|| {
+            let market = new.create(conn)?;
+            for (selection, odds) in selections.iter().zip(prices.iter()) {
+                NewEvent {
+                    market_id: Some(market.id),
+                    ..NewEvent::new(game, *selection, *odds)
+                }
+                .create(conn)?;
+            }
+            Ok(market)
+        })
+    }
+
+    /// The latest version of each of the market's selections.
+    pub fn selections(&self, conn: &PgConnection) -> Result<Vec<Event>, DieselError> {
+        let versions: Vec<Event> = events_dsl::events
+            .filter(events_dsl::market_id.eq(self.id))
+            .order_by((events_dsl::id.asc(), events_dsl::timestamp.desc()))
+            .load(conn)?;
+        let mut latest: Vec<Event> = Vec::new();
+        for event in versions {
+            if latest.last().map(|e| e.id) != Some(event.id) {
+                latest.push(event);
+            }
+        }
+        Ok(latest)
+    }
+
+    /// The bookmaker's margin on the market's current prices.
+    pub fn margin(&self, conn: &PgConnection) -> Result<f64, DieselError> {
+        let prices: Vec<Odds> = self.selections(conn)?.iter().map(|e| e.odds).collect();
+        Ok(odds::overround(&prices))
+    }
+
+    /// The house result on every open single bet in the market for each selection that could win.
+    pub fn liability(&self, conn: &PgConnection) -> Result<Vec<SelectionLiability>, DieselError> {
+        let selections = self.selections(conn)?;
+        let ids: Vec<i32> = selections.iter().map(|e| e.id).collect();
+        let bets: Vec<Bet> = bets_dsl::bets
+            .filter(bets_dsl::event_id.eq_any(ids))
+            .filter(bets_dsl::status.eq(BetStatus::Open))
+            .load(conn)?;
+
+        let mut stakes: HashMap<i32, (i64, i64)> = HashMap::new();
+        for bet in &bets {
+            if let Some(event_id) = bet.event_id {
+                let entry = stakes.entry(event_id).or_insert((0, 0));
+                entry.0 += bet.stake;
+                entry.1 += bet.payout();
+            }
+        }
+        let taken: i64 = bets.iter().map(|b| b.stake).sum();
+        Ok(selections
+            .into_iter()
+            .map(|event| {
+                let (stake, payout) = stakes.get(&event.id).cloned().unwrap_or((0, 0));
+                SelectionLiability {
+                    event_id: event.id,
+                    description: event.description,
+                    odds: event.odds,
+                    stake,
+                    payout,
+                    house_result: taken - payout,
+                }
+            })
+            .collect())
+    }
+
+    /// Check that grades for all of a market's selections leave exactly one winner, or refund the
+    /// whole market on a push or void.
+    pub fn is_consistent(grades: &[Grade]) -> bool {
+        let count = |g: Grade| grades.iter().filter(|&&x| x == g).count();
+        let refunds = count(Grade::Push) + count(Grade::Void);
+        (count(Grade::Win) == 1 && count(Grade::Lose) == grades.len() - 1)
+            || refunds == grades.len()
+    }
+}
+
+impl Creatable for NewMarket {
+    type Output = Market;
+    fn create(&self, conn: &PgConnection) -> Result<Market, DieselError> {
+        diesel::insert_into(markets_dsl::markets)
+            .values(self)
+            .get_result(conn)
+    }
+}
+
+impl Updatable for Market {
+    /// Suspend or resume betting on the market.
+    fn update(&self, conn: &PgConnection) -> Result<Market, DieselError> {
+        diesel::update(markets_dsl::markets)
+            .filter(markets_dsl::id.eq(&self.id))
+            .set((
+                markets_dsl::suspended.eq(&self.suspended),
+                markets_dsl::voided.eq(&self.voided),
+            ))
+            .get_result(conn)
+    }
+}
+
+impl Retrievable<MarketQuery> for Market {
+    fn query(conn: &PgConnection, data: &MarketQuery) -> Result<Vec<Market>, DieselError> {
+        match data.game_id {
+            Some(_game_id) => markets_dsl::markets
+                .filter(markets_dsl::game_id.eq(_game_id))
+                .order_by(markets_dsl::id.asc())
+                .load(conn),
+            None => markets_dsl::markets
+                .order_by(markets_dsl::id.asc())
+                .load(conn),
+        }
+    }
+
+    fn all(conn: &PgConnection) -> Result<Vec<Market>, DieselError> {
+        markets_dsl::markets
+            .filter(markets_dsl::suspended.eq(false))
+            .order_by(markets_dsl::id.desc())
+            .limit(100)
+            .load(conn)
+    }
+}
@@ -1,104 +1,382 @@
+pub mod bet;
+pub mod closing;
+pub mod exposure;
+pub mod game_result;
+pub mod import;
+pub mod league;
+pub mod ledger;
+pub mod market;
+pub mod parlay;
+pub mod permission;
+pub mod pricing;
 pub mod session;
+pub mod settlement;
+pub mod team;
+pub mod token;
 pub mod user;
 
-use super::db::{Creatable, Deletable, Retrievable};
+use super::db::{Creatable, Deletable, Retrievable, Updatable};
+use super::odds::Odds;
 use super::schema::events::{self, dsl as events_dsl};
+use super::schema::game_results::dsl as results_dsl;
+use super::schema::game_transitions::{self, dsl as transitions_dsl};
 use super::schema::games::{self, dsl as games_dsl};
+use league::League;
+use market::{Line, Market, MarketError, MarketKind, Selection, TeamSide, TotalSide};
+use settlement::SettlementError;
 
-use chrono::NaiveDateTime;
+use chrono::{Duration, NaiveDateTime, Utc};
 use diesel::pg::PgConnection;
 use diesel::result::Error as DieselError;
-use diesel::sql_types::{Integer, Timestamp, Varchar};
-use diesel::{sql_query, ExpressionMethods, Insertable, QueryDsl, Queryable, RunQueryDsl};
+use diesel::{Connection, ExpressionMethods, Insertable, QueryDsl, Queryable, RunQueryDsl};
 use diesel_derive_enum::DbEnum;
 use serde::{Deserialize, Serialize};
+use std::{error, fmt};
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Clone, Copy, Debug, DbEnum, Deserialize, Serialize, PartialEq)]
-pub enum League {
-    NBA,
-    NFL,
+/// Where a game is in its lifecycle. Games start out `Scheduled` and become `Final` when their
+/// result is recorded; see `GameStatus::can_become` for the other moves.
+#[derive(Clone, Copy, Debug, DbEnum, Deserialize, Serialize, PartialEq, ToSchema)]
+pub enum GameStatus {
+    Scheduled,
+    Live,
+    Final,
+    Postponed,
+    Cancelled,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize, Queryable, QueryableByName)]
+#[derive(Clone, Debug, Deserialize, Serialize, Queryable, ToSchema)]
 pub struct Game {
-    #[sql_type = "Integer"]
     pub id: i32,
-    #[sql_type = "LeagueMapping"]
-    pub league: League,
-    #[sql_type = "Varchar"]
+    pub league: String,
     pub home: String,
-    #[sql_type = "Varchar"]
     pub away: String,
-    #[sql_type = "Timestamp"]
     pub start: NaiveDateTime,
+    pub status: GameStatus,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize, Insertable)]
+/// A change of a game's status. `user_id` is `None` for changes the system made itself.
+#[derive(Clone, Debug, Deserialize, Serialize, Queryable, ToSchema)]
+pub struct GameTransition {
+    pub id: i32,
+    pub game_id: i32,
+    pub from_status: GameStatus,
+    pub to_status: GameStatus,
+    pub user_id: Option<i32>,
+    pub changed_at: NaiveDateTime,
+}
+
+#[derive(Clone, Copy, Debug, Insertable)]
+#[table_name = "game_transitions"]
+struct NewGameTransition {
+    game_id: i32,
+    from_status: GameStatus,
+    to_status: GameStatus,
+    user_id: Option<i32>,
+}
+
+#[derive(Debug)]
+pub enum GameError {
+    /// The game can't move from the first status to the second
+    InvalidTransition(GameStatus, GameStatus),
+    /// Games only become final when their result is recorded
+    MissingResult,
+    /// The game's league, teams and start can't be changed: it is under way or over
+    Locked(GameStatus),
+    Settlement(SettlementError),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Insertable, ToSchema)]
 #[table_name = "games"]
 pub struct NewGame {
-    pub league: League,
+    pub league: String,
     pub home: String,
     pub away: String,
     pub start: NaiveDateTime,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, Queryable)]
+#[derive(Clone, Debug, Serialize, Deserialize, Queryable, ToSchema)]
 pub struct Event {
     pub id: i32,
     pub description: String,
-    pub odds: i32,
+    pub odds: Odds,
     pub game_id: Option<i32>,
     pub timestamp: NaiveDateTime,
+    pub market_kind: Option<MarketKind>,
+    pub team: Option<TeamSide>,
+    pub total: Option<TotalSide>,
+    pub line: Option<i32>,
+    pub market_id: Option<i32>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize, Insertable)]
+#[derive(Clone, Debug, Deserialize, Serialize, Insertable, ToSchema)]
 #[table_name = "events"]
 pub struct NewEvent {
     pub game_id: i32,
     pub description: String,
-    pub odds: i32,
+    pub odds: Odds,
+    pub market_kind: Option<MarketKind>,
+    pub team: Option<TeamSide>,
+    pub total: Option<TotalSide>,
+    pub line: Option<i32>,
+    pub market_id: Option<i32>,
+}
+
+/// A new version of an existing event, written with the same `id` and a new timestamp
+#[derive(Clone, Debug, Insertable)]
+#[table_name = "events"]
+struct EventVersion<'a> {
+    id: i32,
+    timestamp: NaiveDateTime,
+    description: &'a str,
+    odds: Odds,
+    game_id: Option<i32>,
+    market_kind: Option<MarketKind>,
+    team: Option<TeamSide>,
+    total: Option<TotalSide>,
+    line: Option<i32>,
+    market_id: Option<i32>,
+}
+
+/// One version of an event's price, as returned by the line history endpoint
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct PricePoint {
+    pub timestamp: NaiveDateTime,
+    pub odds: Odds,
+    pub decimal: f64,
+    pub implied_probability: f64,
+    pub line: Option<f64>,
+}
+
+/// An event's line movement, both as a list of versions and as parallel series for charting
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct LineHistory {
+    pub event_id: i32,
+    pub description: String,
+    pub history: Vec<PricePoint>,
+    pub series: PriceSeries,
 }
 
-#[derive(Clone, Copy, Serialize)]
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct PriceSeries {
+    pub labels: Vec<NaiveDateTime>,
+    pub odds: Vec<i32>,
+    pub implied_probability: Vec<f64>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct EventQuery {
     pub id: Option<i32>,
     pub odds: Option<i32>,
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct GameQuery {
-    pub league: Option<League>,
+    pub league: Option<String>,
+    pub status: Option<GameStatus>,
 }
 
-impl ToString for League {
-    fn to_string(&self) -> String {
+impl Default for GameQuery {
+    fn default() -> Self {
+        GameQuery {
+            league: None,
+            status: None,
+        }
+    }
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            League::NBA => "NBA".to_string(),
-            League::NFL => "NFL".to_string(),
+            GameError::InvalidTransition(from, to) => {
+                write!(f, "InvalidTransition: {:?} to {:?}", from, to)
+            }
+            GameError::MissingResult => write!(f, "MissingResult"),
+            GameError::Locked(status) => write!(f, "Locked: {:?}", status),
+            GameError::Settlement(e) => write!(f, "{}", e),
         }
     }
 }
 
-impl Default for GameQuery {
-    fn default() -> Self {
-        GameQuery { league: None }
+impl error::Error for GameError {}
+
+impl From<SettlementError> for GameError {
+    fn from(e: SettlementError) -> Self {
+        GameError::Settlement(e)
+    }
+}
+
+impl From<DieselError> for GameError {
+    fn from(e: DieselError) -> Self {
+        GameError::Settlement(SettlementError::Db(e))
+    }
+}
+
+impl GameStatus {
+    /// Whether a game can move from this status to `to`. Postponed games can be rescheduled;
+    /// final and cancelled games can't move at all.
+    pub fn can_become(self, to: GameStatus) -> bool {
+        use GameStatus::*;
+        matches!(
+            (self, to),
+            (Scheduled, Live)
+                | (Scheduled, Final)
+                | (Scheduled, Postponed)
+                | (Scheduled, Cancelled)
+                | (Live, Final)
+                | (Live, Postponed)
+                | (Live, Cancelled)
+                | (Postponed, Scheduled)
+                | (Postponed, Cancelled)
+        )
     }
 }
 
 impl Retrievable<GameQuery> for Game {
+    /// Retrieves the games in `q.league`, or in every league, with status `q.status`. Without a
+    /// status, only games that haven't finished or been cancelled are retrieved.
     fn query(conn: &PgConnection, q: &GameQuery) -> Result<Vec<Game>, DieselError> {
-        let stmt = match q.league {
-            Some(League::NBA) => "SELECT * FROM games WHERE league = 'nba' and id NOT IN (SELECT game_id FROM game_results)",
-            Some(League::NFL) => "SELECT * FROM games WHERE league = 'nfl' and id NOT IN (SELECT game_id FROM game_results)",
-            None => "SELECT * FROM games WHERE id NOT IN (SELECT game_id FROM game_results)"
+        let mut query = games_dsl::games.into_boxed();
+        query = match q.status {
+            Some(_status) => query.filter(games_dsl::status.eq(_status)),
+            None => query
+                .filter(games_dsl::status.ne_all(vec![GameStatus::Final, GameStatus::Cancelled])),
         };
-        sql_query(stmt).get_results(conn)
+        if let Some(_league) = &q.league {
+            query = query.filter(games_dsl::league.eq(_league));
+        }
+        query.load(conn)
     }
 
-    /// Retrieves all games that don't have a result (i.e. don't have a final score)
+    /// Retrieves all games that haven't finished or been cancelled
     fn all(conn: &PgConnection) -> Result<Vec<Game>, DieselError> {
-        let _stmt = "SELECT * FROM games WHERE games.id NOT IN (SELECT game_id FROM game_results)";
-        sql_query(_stmt).load(conn)
+        Game::query(conn, &GameQuery::default())
+    }
+}
+
+impl Game {
+    /// Retrieve the game with the given `id`.
+    pub fn get(conn: &PgConnection, id: i32) -> Result<Game, DieselError> {
+        games_dsl::games.find(id).first(conn)
+    }
+
+    /// Move the game to status `to` on behalf of `user_id`. Postponing or cancelling a game
+    /// suspends its markets and voids every bet on it, refunding the stakes. Rescheduling a
+    /// postponed game resumes the markets that were suspended, but not those a trader suspended.
+    pub fn transition(
+        &self,
+        conn: &PgConnection,
+        to: GameStatus,
+        user_id: Option<i32>,
+    ) -> Result<Game, GameError> {
+        conn.transaction(|| {
+            let game = Game::get(conn, self.id)?;
+            if !game.status.can_become(to) {
+                return Err(GameError::InvalidTransition(game.status, to));
+            }
+            if to == GameStatus::Final
+                && results_dsl::game_results
+                    .filter(results_dsl::game_id.eq(game.id))
+                    .count()
+                    .get_result::<i64>(conn)?
+                    == 0
+            {
+                return Err(GameError::MissingResult);
+            }
+            let game = game.record_transition(conn, to, user_id)?;
+            match to {
+                GameStatus::Postponed | GameStatus::Cancelled => {
+                    settlement::void_game(conn, game.id)?;
+                }
+                GameStatus::Scheduled => {
+                    Market::resume_voided(conn, game.id)?;
+                }
+                _ => {}
+            }
+            Ok(game)
+        })
+    }
+
+    /// Whether bets can be placed on the game at `now`. Scheduled games stop taking bets at their
+    /// start; after that only a game a trader has marked live takes bets, on its in-play markets.
+    pub fn accepts_bets(&self, now: NaiveDateTime) -> bool {
+        match self.status {
+            GameStatus::Scheduled => now < self.start,
+            GameStatus::Live => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the game's league, teams and start can still be changed at `now`. Once a game is
+    /// under way its bets are graded against it, so only scheduled games that haven't started,
+    /// and postponed games, can be edited.
+    pub fn editable(&self, now: NaiveDateTime) -> bool {
+        match self.status {
+            GameStatus::Scheduled => now < self.start,
+            GameStatus::Postponed => true,
+            _ => false,
+        }
+    }
+
+    /// Replace the game's league, teams and start with `new`'s, if the game is still editable.
+    pub fn edit(&self, conn: &PgConnection, new: &NewGame) -> Result<Game, GameError> {
+        conn.transaction(|| {
+            let game: Game = games_dsl::games.find(self.id).for_update().first(conn)?;
+            if !game.editable(Utc::now().naive_utc()) {
+                return Err(GameError::Locked(game.status));
+            }
+            Game {
+                league: new.league.clone(),
+                home: new.home.clone(),
+                away: new.away.clone(),
+                start: new.start,
+                ..game
+            }
+            .update(conn)
+            .map_err(GameError::from)
+        })
+    }
+
+    /// Set the game's status and record the change, without checking that it is allowed.
+    pub(crate) fn record_transition(
+        &self,
+        conn: &PgConnection,
+        to: GameStatus,
+        user_id: Option<i32>,
+    ) -> Result<Game, DieselError> {
+        diesel::insert_into(transitions_dsl::game_transitions)
+            .values(&NewGameTransition {
+                game_id: self.id,
+                from_status: self.status,
+                to_status: to,
+                user_id,
+            })
+            .execute(conn)?;
+        diesel::update(games_dsl::games.find(self.id))
+            .set(games_dsl::status.eq(to))
+            .get_result(conn)
+    }
+
+    /// Every change of the game's status, oldest first.
+    pub fn transitions(&self, conn: &PgConnection) -> Result<Vec<GameTransition>, DieselError> {
+        transitions_dsl::game_transitions
+            .filter(transitions_dsl::game_id.eq(self.id))
+            .order_by(transitions_dsl::changed_at.asc())
+            .then_order_by(transitions_dsl::id.asc())
+            .load(conn)
+    }
+}
+
+impl NewGame {
+    /// Create the game and open its league's default markets on it.
+    pub fn schedule(&self, conn: &PgConnection) -> Result<Game, MarketError> {
+        conn.transaction(|| {
+            let game = self.create(conn)?;
+            League::get(conn, &game.league)?.open_markets(conn, &game)?;
+            Ok(game)
+        })
     }
 }
 
@@ -111,6 +389,117 @@ impl Creatable for NewGame {
     }
 }
 
+impl Updatable for Game {
+    fn update(&self, conn: &PgConnection) -> Result<Game, DieselError> {
+        diesel::update(games_dsl::games.find(self.id))
+            .set((
+                games_dsl::league.eq(&self.league),
+                games_dsl::home.eq(&self.home),
+                games_dsl::away.eq(&self.away),
+                games_dsl::start.eq(&self.start),
+            ))
+            .get_result(conn)
+    }
+}
+
+impl Deletable for Game {
+    fn delete(&self, conn: &PgConnection) -> Result<Game, DieselError> {
+        diesel::delete(games_dsl::games.find(self.id)).get_result(conn)
+    }
+}
+
+impl Event {
+    /// The event's typed selection, or `None` for legacy free-text events.
+    pub fn selection(&self) -> Option<Selection> {
+        self.market_kind.and_then(|kind| {
+            Selection::from_parts(kind, self.team, self.total, self.line.map(Line)).ok()
+        })
+    }
+
+    /// Retrieve the most recent version of the event with the given `id`.
+    pub fn latest(conn: &PgConnection, id: i32) -> Result<Event, DieselError> {
+        events_dsl::events
+            .filter(events_dsl::id.eq(id))
+            .order_by(events_dsl::timestamp.desc())
+            .first(conn)
+    }
+
+    /// Every version of the event with the given `id`, oldest first.
+    pub fn history(conn: &PgConnection, id: i32) -> Result<Vec<Event>, DieselError> {
+        events_dsl::events
+            .filter(events_dsl::id.eq(id))
+            .order_by(events_dsl::timestamp.asc())
+            .load(conn)
+    }
+
+    /// Write a new version of the event at `odds`.
+    pub fn reprice(&self, conn: &PgConnection, odds: Odds) -> Result<Event, DieselError> {
+        Event {
+            odds,
+            ..self.clone()
+        }
+        .update(conn)
+    }
+}
+
+impl LineHistory {
+    /// Build the line history from an event's versions, oldest first. The description is the
+    /// latest version's. Returns `None` if there are no versions.
+    pub fn new(versions: &[Event]) -> Option<LineHistory> {
+        let latest = versions.last()?;
+        let history: Vec<PricePoint> = versions.iter().map(PricePoint::from).collect();
+        let series = PriceSeries {
+            labels: history.iter().map(|p| p.timestamp).collect(),
+            odds: history.iter().map(|p| p.odds.value()).collect(),
+            implied_probability: history.iter().map(|p| p.implied_probability).collect(),
+        };
+        Some(LineHistory {
+            event_id: latest.id,
+            description: latest.description.clone(),
+            history,
+            series,
+        })
+    }
+}
+
+impl From<&Event> for PricePoint {
+    fn from(event: &Event) -> Self {
+        PricePoint {
+            timestamp: event.timestamp,
+            odds: event.odds,
+            decimal: event.odds.to_decimal(),
+            implied_probability: event.odds.implied_probability(),
+            line: event.line.map(|l| Line(l).points()),
+        }
+    }
+}
+
+impl Updatable for Event {
+    /// Events are versioned rather than edited: the changes are written as a new version with the
+    /// same `id`, which becomes the latest. The version is stamped with the current time rather
+    /// than the column default, which is the start of the transaction, and always after the
+    /// version it replaces.
+    fn update(&self, conn: &PgConnection) -> Result<Event, DieselError> {
+        let timestamp = Utc::now()
+            .naive_utc()
+            .max(self.timestamp + Duration::microseconds(1));
+        diesel::insert_into(events_dsl::events)
+            .values(&EventVersion {
+                id: self.id,
+                timestamp,
+                description: &self.description,
+                odds: self.odds,
+                game_id: self.game_id,
+                market_kind: self.market_kind,
+                team: self.team,
+                total: self.total,
+                line: self.line,
+                market_id: self.market_id,
+            })
+            .get_result(conn)
+    }
+}
+
 impl Deletable for Event {
     fn delete(&self, conn: &PgConnection) -> Result<Event, DieselError> {
         diesel::delete(events_dsl::events.filter(events_dsl::id.eq(&self.id))).get_result(conn)
@@ -149,14 +538,47 @@ impl Retrievable<EventQuery> for Event {
         }
     }
 
+    /// Retrieves the latest version of up to 100 events
     fn all(conn: &PgConnection) -> Result<Vec<Event>, DieselError> {
         events_dsl::events
+            .distinct_on(events_dsl::id)
+            .order_by((events_dsl::id.desc(), events_dsl::timestamp.desc()))
             .limit(100)
-            .order_by(events_dsl::timestamp.desc())
             .load(conn)
     }
 }
 
+impl NewEvent {
+    /// Create a new event for `selection` on `game`, generating its description.
+    pub fn new(game: &Game, selection: Selection, odds: Odds) -> Self {
+        NewEvent {
+            game_id: game.id,
+            description: selection.describe(game),
+            odds,
+            market_kind: Some(selection.kind()),
+            team: selection.team(),
+            total: selection.total(),
+            line: selection.line().map(|l| l.0),
+            market_id: None,
+        }
+    }
+}
+
+impl Default for NewEvent {
+    fn default() -> Self {
+        NewEvent {
+            game_id: -1,
+            description: String::new(),
+            odds: Odds::EVEN,
+            market_kind: None,
+            team: None,
+            total: None,
+            line: None,
+            market_id: None,
+        }
+    }
+}
+
 impl Creatable for NewEvent {
     type Output = Event;
     fn create(&self, conn: &PgConnection) -> Result<Event, DieselError> {
@@ -0,0 +1,172 @@
+//! Parlays
+//!
+//! A parlay is a single `Bet` on several events at once, with no `event_id` of its own. Each of
+//! its selections is a `ParlayLeg` that keeps the event version it was priced at and its own
+//! grade, so a ticket spanning several games is graded leg by leg as their results come in. The
+//! ticket wins only if none of its legs lose, at the combined price of the legs that won.
+use crate::db::{Creatable, Retrievable, Updatable};
+use crate::model::bet::{ensure_open, Bet, BetError, BetStatus, NewBet};
+use crate::model::settlement::Grade;
+use crate::model::Event;
+use crate::odds::{self, Odds};
+use crate::schema::parlay_legs::{self, dsl as legs_dsl};
+
+use chrono::NaiveDateTime;
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+use diesel::{Connection, ExpressionMethods, Insertable, QueryDsl, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Queryable)]
+pub struct ParlayLeg {
+    pub id: i32,
+    pub bet_id: i32,
+    pub event_id: i32,
+    pub odds: Odds,
+    pub status: BetStatus,
+    pub event_timestamp: Option<NaiveDateTime>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Insertable)]
+#[table_name = "parlay_legs"]
+pub struct NewParlayLeg {
+    pub bet_id: i32,
+    pub event_id: i32,
+    pub odds: Odds,
+    pub event_timestamp: Option<NaiveDateTime>,
+}
+
+/// A parlay ticket to be placed on the latest versions of `event_ids`
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NewParlay {
+    pub user_id: i32,
+    pub stake: i64,
+    pub event_ids: Vec<i32>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct ParlayLegQuery {
+    pub bet_id: Option<i32>,
+    pub event_id: Option<i32>,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+/////// Implementations ///////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl NewParlay {
+    /// Place the parlay. Every leg is priced at its event's current odds and the stake is taken
+    /// from the punter's wallet exactly as for a single bet. Tickets with fewer than two legs, two
+    /// selections from the same market or a leg on a suspended market are refused.
+    pub fn place(&self, conn: &PgConnection) -> Result<Bet, BetError> {
+        if self.event_ids.len() < 2 {
+            return Err(BetError::TooFewLegs);
+        }
+        conn.transaction::<Bet, BetError, _>(|| {
+            let mut events = Vec::new();
+            // Legacy events without a market are treated as a market of their own
+            let mut markets = HashSet::new();
+            for id in &self.event_ids {
+                let event = Event::latest(conn, *id)?;
+                ensure_open(conn, &event)?;
+                let market = match event.market_id {
+                    Some(market_id) => (true, market_id),
+                    None => (false, event.id),
+                };
+                if !markets.insert(market) {
+                    return Err(BetError::SameMarket);
+                }
+                events.push(event);
+            }
+
+            let prices: Vec<Odds> = events.iter().map(|e| e.odds).collect();
+            let bet = NewBet {
+                user_id: self.user_id,
+                event_id: None,
+                stake: self.stake,
+                odds: odds::parlay(&prices).ok_or(BetError::TooFewLegs)?,
+                event_timestamp: None,
+            }
+            .place(conn)?;
+            for event in &events {
+                NewParlayLeg {
+                    bet_id: bet.id,
+                    event_id: event.id,
+                    odds: event.odds,
+                    event_timestamp: Some(event.timestamp),
+                }
+                .create(conn)?;
+            }
+            Ok(bet)
+        })
+    }
+}
+
+/// Grade a ticket from its legs. Any losing leg loses the ticket; otherwise the ticket stays
+/// ungraded while a leg is still open. A ticket whose legs all pushed or were voided is refunded.
+pub fn grade(legs: &[ParlayLeg]) -> Option<Grade> {
+    let any = |status: BetStatus| legs.iter().any(|l| l.status == status);
+    if any(BetStatus::Lost) {
+        Some(Grade::Lose)
+    } else if any(BetStatus::Open) {
+        None
+    } else if any(BetStatus::Won) {
+        Some(Grade::Win)
+    } else if any(BetStatus::Push) {
+        Some(Grade::Push)
+    } else {
+        Some(Grade::Void)
+    }
+}
+
+/// The ticket's price over the legs that haven't pushed or been voided, or `None` if there are
+/// none left.
+pub fn price(legs: &[ParlayLeg]) -> Option<Odds> {
+    let prices: Vec<Odds> = legs
+        .iter()
+        .filter(|l| l.status != BetStatus::Push && l.status != BetStatus::Void)
+        .map(|l| l.odds)
+        .collect();
+    odds::parlay(&prices)
+}
+
+impl Creatable for NewParlayLeg {
+    type Output = ParlayLeg;
+    fn create(&self, conn: &PgConnection) -> Result<ParlayLeg, DieselError> {
+        diesel::insert_into(legs_dsl::parlay_legs)
+            .values(self)
+            .get_result(conn)
+    }
+}
+
+impl Updatable for ParlayLeg {
+    /// Record the leg's grade.
+    fn update(&self, conn: &PgConnection) -> Result<ParlayLeg, DieselError> {
+        diesel::update(legs_dsl::parlay_legs)
+            .filter(legs_dsl::id.eq(&self.id))
+            .set(legs_dsl::status.eq(&self.status))
+            .get_result(conn)
+    }
+}
+
+impl Retrievable<ParlayLegQuery> for ParlayLeg {
+    fn query(conn: &PgConnection, data: &ParlayLegQuery) -> Result<Vec<ParlayLeg>, DieselError> {
+        let mut query = legs_dsl::parlay_legs.into_boxed();
+        if let Some(_bet_id) = data.bet_id {
+            query = query.filter(legs_dsl::bet_id.eq(_bet_id));
+        }
+        if let Some(_event_id) = data.event_id {
+            query = query.filter(legs_dsl::event_id.eq(_event_id));
+        }
+        query.order_by(legs_dsl::id.asc()).load(conn)
+    }
+
+    fn all(conn: &PgConnection) -> Result<Vec<ParlayLeg>, DieselError> {
+        legs_dsl::parlay_legs
+            .limit(100)
+            .order_by(legs_dsl::id.desc())
+            .load(conn)
+    }
+}
@@ -0,0 +1,107 @@
+//! Permissions
+//!
+//! Handlers check a named `Permission` rather than a role. Which roles hold which permissions is
+//! kept in the `role_permissions` table, so the matrix can be changed by an admin at runtime.
+use crate::model::user::Role;
+use crate::schema::role_permissions::{self, dsl as permissions_dsl};
+
+use diesel::dsl::exists;
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+use diesel::{Connection, ExpressionMethods, Insertable, QueryDsl, Queryable, RunQueryDsl};
+use diesel_derive_enum::DbEnum;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, DbEnum, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum Permission {
+    /// Change users' roles and the permissions matrix
+    ManageUsers,
+    /// Create games and record their results
+    ManageGames,
+    /// Open, suspend and resume markets
+    ManageMarkets,
+    /// Reprice events and set pricing policies
+    SetPrices,
+    /// See markets' liability, everyone's bets and closing-line value
+    ViewBook,
+    ViewExposure,
+    /// See other users' wallets
+    ViewAccounts,
+    /// Credit other users' wallets
+    MoveMoney,
+    PlaceBets,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Queryable, Insertable)]
+#[table_name = "role_permissions"]
+pub struct RolePermission {
+    pub role: Role,
+    pub permission: Permission,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+/////// Implementations ///////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl Permission {
+    pub const ALL: [Permission; 9] = [
+        Permission::ManageUsers,
+        Permission::ManageGames,
+        Permission::ManageMarkets,
+        Permission::SetPrices,
+        Permission::ViewBook,
+        Permission::ViewExposure,
+        Permission::ViewAccounts,
+        Permission::MoveMoney,
+        Permission::PlaceBets,
+    ];
+
+    /// Whether `role` holds this permission.
+    pub fn granted(self, conn: &PgConnection, role: Role) -> Result<bool, DieselError> {
+        diesel::select(exists(
+            permissions_dsl::role_permissions
+                .filter(permissions_dsl::role.eq(role))
+                .filter(permissions_dsl::permission.eq(self)),
+        ))
+        .get_result(conn)
+    }
+}
+
+impl RolePermission {
+    /// The whole matrix, one row per permission held.
+    pub fn all(conn: &PgConnection) -> Result<Vec<RolePermission>, DieselError> {
+        permissions_dsl::role_permissions.load(conn)
+    }
+
+    /// Replace the permissions held by `role` with `permissions`. Admins always keep
+    /// `ManageUsers`, so the matrix can't be left without anyone able to change it.
+    pub fn set(
+        conn: &PgConnection,
+        role: Role,
+        permissions: &[Permission],
+    ) -> Result<Vec<RolePermission>, DieselError> {
+        let mut rows: Vec<RolePermission> = permissions
+            .iter()
+            .map(|&permission| RolePermission { role, permission })
+            .collect();
+        if role == Role::Admin && !permissions.contains(&Permission::ManageUsers) {
+            rows.push(RolePermission {
+                role,
+                permission: Permission::ManageUsers,
+            });
+        }
+        conn.transaction(|| {
+            diesel::delete(
+                permissions_dsl::role_permissions.filter(permissions_dsl::role.eq(role)),
+            )
+            .execute(conn)?;
+            if rows.is_empty() {
+                return Ok(Vec::new());
+            }
+            diesel::insert_into(permissions_dsl::role_permissions)
+                .values(&rows)
+                .get_results(conn)
+        })
+    }
+}
@@ -0,0 +1,211 @@
+//! Automatic pricing
+//!
+//! A market with a `PricingPolicy` reprices itself as bets come in. After every bet the house
+//! result of each selection is compared: when the selection the house is most exposed to trails
+//! the best one by at least `threshold`, it is shortened by `step` and every other selection is
+//! lengthened by `step`, within the policy's price limits. Each move writes a new version of the
+//! event and is logged as a `PriceMove` with the reason it fired.
+use crate::db::{Creatable, Retrievable};
+use crate::model::market::{Market, SelectionLiability};
+use crate::model::Event;
+use crate::odds::Odds;
+use crate::schema::price_moves::{self, dsl as moves_dsl};
+use crate::schema::pricing_policies::{self, dsl as policies_dsl};
+
+use chrono::NaiveDateTime;
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+use diesel::{
+    AsChangeset, ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable, RunQueryDsl,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Queryable)]
+pub struct PricingPolicy {
+    pub id: i32,
+    pub market_id: i32,
+    pub threshold: i64,
+    pub step: i32,
+    pub min_odds: Odds,
+    pub max_odds: Odds,
+    pub enabled: bool,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Insertable, AsChangeset)]
+#[table_name = "pricing_policies"]
+pub struct NewPricingPolicy {
+    pub market_id: i32,
+    pub threshold: i64,
+    pub step: i32,
+    pub min_odds: Odds,
+    pub max_odds: Odds,
+    pub enabled: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Queryable)]
+pub struct PriceMove {
+    pub id: i32,
+    pub market_id: i32,
+    pub event_id: i32,
+    pub event_timestamp: NaiveDateTime,
+    pub old_odds: Odds,
+    pub new_odds: Odds,
+    pub reason: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Insertable)]
+#[table_name = "price_moves"]
+pub struct NewPriceMove {
+    pub market_id: i32,
+    pub event_id: i32,
+    pub event_timestamp: NaiveDateTime,
+    pub old_odds: Odds,
+    pub new_odds: Odds,
+    pub reason: String,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct PriceMoveQuery {
+    pub market_id: Option<i32>,
+}
+
+/// A price change decided by a policy, before it is written
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reprice {
+    pub event_id: i32,
+    pub old_odds: Odds,
+    pub new_odds: Odds,
+    pub reason: String,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+/////// Implementations ///////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl PricingPolicy {
+    /// Retrieve the policy of the market with the given `market_id`, if it has one.
+    pub fn for_market(
+        conn: &PgConnection,
+        market_id: i32,
+    ) -> Result<Option<PricingPolicy>, DieselError> {
+        policies_dsl::pricing_policies
+            .filter(policies_dsl::market_id.eq(market_id))
+            .first(conn)
+            .optional()
+    }
+
+    /// Decide how to reprice a market given each selection's liability. Returns no moves while
+    /// the book is balanced within `threshold`, or when every price is already at its limit. A
+    /// price outside the limits is only pulled back inside them when that moves it the way the
+    /// book needs, shorter on the heavy side and longer on the others.
+    pub fn decide(&self, selections: &[SelectionLiability]) -> Vec<Reprice> {
+        let heavy = selections.iter().min_by_key(|s| s.house_result);
+        let light = selections.iter().max_by_key(|s| s.house_result);
+        let (heavy, light) = match (heavy, light) {
+            (Some(heavy), Some(light)) => (heavy, light),
+            _ => return Vec::new(),
+        };
+        let imbalance = light.house_result - heavy.house_result;
+        if imbalance < self.threshold {
+            return Vec::new();
+        }
+        let reason = format!(
+            "house result {:.2} on {} against {:.2} on {}; imbalance {:.2} reached threshold {:.2}",
+            heavy.house_result as f64 / 100.0,
+            heavy.description,
+            light.house_result as f64 / 100.0,
+            light.description,
+            imbalance as f64 / 100.0,
+            self.threshold as f64 / 100.0,
+        );
+        selections
+            .iter()
+            .filter_map(|s| {
+                let is_heavy = s.event_id == heavy.event_id;
+                let step = if is_heavy { -self.step } else { self.step };
+                let new_odds = s.odds.step(step).clamp(self.min_odds, self.max_odds);
+                // A price already outside the limits may be clamped the wrong way; leave it be
+                let shortened = new_odds.implied_probability() > s.odds.implied_probability();
+                match new_odds == s.odds || shortened != is_heavy {
+                    true => None,
+                    false => Some(Reprice {
+                        event_id: s.event_id,
+                        old_odds: s.odds,
+                        new_odds,
+                        reason: reason.clone(),
+                    }),
+                }
+            })
+            .collect()
+    }
+
+    /// Reprice `market` under its policy, if it has an enabled one, writing a new version of
+    /// each event that moves and logging the move. Suspended markets are left alone.
+    pub fn apply(conn: &PgConnection, market: &Market) -> Result<Vec<PriceMove>, DieselError> {
+        let policy = match PricingPolicy::for_market(conn, market.id)? {
+            Some(policy) if policy.enabled && !market.suspended => policy,
+            _ => return Ok(Vec::new()),
+        };
+        policy
+            .decide(&market.liability(conn)?)
+            .into_iter()
+            .map(|reprice| {
+                let event =
+                    Event::latest(conn, reprice.event_id)?.reprice(conn, reprice.new_odds)?;
+                NewPriceMove {
+                    market_id: market.id,
+                    event_id: event.id,
+                    event_timestamp: event.timestamp,
+                    old_odds: reprice.old_odds,
+                    new_odds: reprice.new_odds,
+                    reason: reprice.reason,
+                }
+                .create(conn)
+            })
+            .collect()
+    }
+}
+
+impl Creatable for NewPricingPolicy {
+    type Output = PricingPolicy;
+    /// Create the market's policy, or replace the one it already has. Policies whose `min_odds`
+    /// is longer than their `max_odds` are refused by the database.
+    fn create(&self, conn: &PgConnection) -> Result<PricingPolicy, DieselError> {
+        diesel::insert_into(policies_dsl::pricing_policies)
+            .values(self)
+            .on_conflict(policies_dsl::market_id)
+            .do_update()
+            .set(self)
+            .get_result(conn)
+    }
+}
+
+impl Creatable for NewPriceMove {
+    type Output = PriceMove;
+    fn create(&self, conn: &PgConnection) -> Result<PriceMove, DieselError> {
+        diesel::insert_into(moves_dsl::price_moves)
+            .values(self)
+            .get_result(conn)
+    }
+}
+
+impl Retrievable<PriceMoveQuery> for PriceMove {
+    fn query(conn: &PgConnection, data: &PriceMoveQuery) -> Result<Vec<PriceMove>, DieselError> {
+        match data.market_id {
+            Some(_market_id) => moves_dsl::price_moves
+                .filter(moves_dsl::market_id.eq(_market_id))
+                .order_by(moves_dsl::id.desc())
+                .load(conn),
+            None => PriceMove::all(conn),
+        }
+    }
+
+    fn all(conn: &PgConnection) -> Result<Vec<PriceMove>, DieselError> {
+        moves_dsl::price_moves
+            .limit(100)
+            .order_by(moves_dsl::id.desc())
+            .load(conn)
+    }
+}
@@ -1,10 +1,11 @@
 use crate::db::*;
 use crate::model::user::User;
 use crate::schema::sessions::{self, dsl as sessions_dsl};
+use crate::schema::users::dsl as users_dsl;
 use chrono::{NaiveDateTime, Utc};
 use diesel::pg::PgConnection;
 use diesel::result::Error as DieselError;
-use diesel::{ExpressionMethods, Insertable, Queryable, RunQueryDsl};
+use diesel::{ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable, RunQueryDsl};
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Copy, Debug, Deserialize, Serialize, Queryable)]
@@ -22,6 +23,18 @@ pub struct NewSession {
     pub logout_date: Option<NaiveDateTime>,
 }
 
+impl Session {
+    /// Retrieve the session with the given `id` and its user, unless it has been logged out.
+    pub fn active(conn: &PgConnection, id: i32) -> Result<Option<(Session, User)>, DieselError> {
+        sessions_dsl::sessions
+            .inner_join(users_dsl::users)
+            .filter(sessions_dsl::id.eq(id))
+            .filter(sessions_dsl::logout_date.is_null())
+            .first(conn)
+            .optional()
+    }
+}
+
 impl Updatable for Session {
     fn update(&self, conn: &PgConnection) -> Result<Session, DieselError> {
         diesel::update(sessions_dsl::sessions)
@@ -0,0 +1,334 @@
+//! Settlement engine
+//!
+//! Grades every bet on a game against its final score and moves the money accordingly. Settlement
+//! is idempotent: each run recomputes what every bet should have paid out and only posts the
+//! difference from what the ledger has already paid, so it can be re-run safely after a score
+//! correction. Earlier payouts are never edited, they are reversed by compensating entries.
+//! A game whose markets don't grade to exactly one winner each is not settled at all. Parlay legs
+//! on the game are graded with it, and a parlay is settled once its legs decide it. Games that are
+//! postponed or cancelled are voided instead: every stake on them is refunded.
+use crate::db::{Creatable, Retrievable, Updatable};
+use crate::model::bet::{Bet, BetStatus};
+use crate::model::closing::ClosingLine;
+use crate::model::game_result::GameResult;
+use crate::model::ledger::{Account, AccountKind, Entry, EntryKind, EntryQuery, NewEntry};
+use crate::model::market::{Market, MarketQuery};
+use crate::model::parlay::{self, ParlayLeg, ParlayLegQuery};
+use crate::model::{Event, Game};
+use crate::schema::bets::dsl as bets_dsl;
+use crate::schema::events::dsl as events_dsl;
+use crate::schema::game_results::dsl as results_dsl;
+use crate::schema::parlay_legs::dsl as legs_dsl;
+
+use chrono::Utc;
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+use diesel::{Connection, ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::{error, fmt};
+
+/// Outcome of a single event once its game has been scored
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub enum Grade {
+    Win,
+    Lose,
+    Push,
+    Void,
+}
+
+#[derive(Debug)]
+pub enum SettlementError {
+    /// The market's selections didn't grade to exactly one winner
+    InconsistentMarket(i32),
+    Db(DieselError),
+}
+
+impl fmt::Display for SettlementError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettlementError::InconsistentMarket(id) => write!(f, "InconsistentMarket: {}", id),
+            SettlementError::Db(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for SettlementError {}
+
+impl From<DieselError> for SettlementError {
+    fn from(e: DieselError) -> Self {
+        SettlementError::Db(e)
+    }
+}
+
+impl From<Grade> for BetStatus {
+    fn from(grade: Grade) -> Self {
+        match grade {
+            Grade::Win => BetStatus::Won,
+            Grade::Lose => BetStatus::Lost,
+            Grade::Push => BetStatus::Push,
+            Grade::Void => BetStatus::Void,
+        }
+    }
+}
+
+impl Grade {
+    /// Grade a selection from how far it finished ahead of its line
+    pub(crate) fn from_margin(margin: i32) -> Self {
+        match margin {
+            m if m > 0 => Grade::Win,
+            m if m < 0 => Grade::Lose,
+            _ => Grade::Push,
+        }
+    }
+}
+
+/// Grade every event on `game_id` against the game's final score and settle every bet placed on
+/// them. Bets on legacy free-text events, which have no market, are left open. Returns the bets
+/// that were settled.
+pub fn settle_game(conn: &PgConnection, game_id: i32) -> Result<Vec<Bet>, SettlementError> {
+    conn.transaction::<_, SettlementError, _>(|| {
+        let result: GameResult = results_dsl::game_results
+            .filter(results_dsl::game_id.eq(game_id))
+            .first(conn)?;
+        // A finished game has closed even if the closing lines were never captured
+        ClosingLine::capture(conn, &Game::get(conn, game_id)?)?;
+        let events: Vec<Event> = events_dsl::events
+            .filter(events_dsl::game_id.eq(game_id))
+            .order_by(events_dsl::timestamp.asc())
+            .load(conn)?;
+
+        // Later versions of an event overwrite earlier ones
+        let mut grades = HashMap::new();
+        let mut markets = HashMap::new();
+        for event in &events {
+            let grade = event.selection().map(|s| s.grade(&result));
+            grades.insert(event.id, grade);
+            if let Some(market_id) = event.market_id {
+                markets.insert(event.id, market_id);
+            }
+        }
+
+        let mut market_grades: HashMap<i32, Vec<Grade>> = HashMap::new();
+        for (event_id, market_id) in &markets {
+            if let Some(Some(grade)) = grades.get(event_id) {
+                market_grades.entry(*market_id).or_default().push(*grade);
+            }
+        }
+        for (market_id, grades) in &market_grades {
+            if !Market::is_consistent(grades) {
+                return Err(SettlementError::InconsistentMarket(*market_id));
+            }
+        }
+
+        let event_ids: Vec<i32> = grades.keys().cloned().collect();
+        let bets: Vec<Bet> = bets_dsl::bets
+            .filter(bets_dsl::event_id.eq_any(event_ids.clone()))
+            .load(conn)?;
+        let mut settled = Vec::new();
+        for bet in bets {
+            if let Some(Some(grade)) = bet.event_id.and_then(|id| grades.get(&id)) {
+                settled.push(settle_bet(conn, &bet, *grade)?);
+            }
+        }
+
+        let legs: Vec<ParlayLeg> = legs_dsl::parlay_legs
+            .filter(legs_dsl::event_id.eq_any(event_ids))
+            .load(conn)?;
+        let mut parlays: Vec<i32> = Vec::new();
+        for leg in legs {
+            if let Some(Some(grade)) = grades.get(&leg.event_id) {
+                let status = BetStatus::from(*grade);
+                if leg.status != status {
+                    ParlayLeg { status, ..leg }.update(conn)?;
+                }
+                if !parlays.contains(&leg.bet_id) {
+                    parlays.push(leg.bet_id);
+                }
+            }
+        }
+        for bet_id in parlays {
+            let bet = settle_parlay(conn, &bets_dsl::bets.find(bet_id).first(conn)?)?;
+            if bet.status != BetStatus::Open {
+                settled.push(bet);
+            }
+        }
+        Ok(settled)
+    })
+}
+
+/// Void every bet on `game_id` and suspend its markets, for a game that won't be played as
+/// scheduled. Singles are refunded; parlay legs on the game are voided and their parlays repriced
+/// over the remaining legs. Returns the bets that were settled.
+pub fn void_game(conn: &PgConnection, game_id: i32) -> Result<Vec<Bet>, SettlementError> {
+    conn.transaction::<_, SettlementError, _>(|| {
+        let markets = Market::query(
+            conn,
+            &MarketQuery {
+                game_id: Some(game_id),
+            },
+        )?;
+        for market in markets.into_iter().filter(|m| !m.suspended) {
+            Market {
+                suspended: true,
+                voided: true,
+                ..market
+            }
+            .update(conn)?;
+        }
+
+        let event_ids: Vec<i32> = events_dsl::events
+            .filter(events_dsl::game_id.eq(game_id))
+            .select(events_dsl::id)
+            .distinct()
+            .load(conn)?;
+        let bets: Vec<Bet> = bets_dsl::bets
+            .filter(bets_dsl::event_id.eq_any(event_ids.clone()))
+            .load(conn)?;
+        let mut settled = Vec::new();
+        for bet in bets {
+            settled.push(settle_bet(conn, &bet, Grade::Void)?);
+        }
+
+        let legs: Vec<ParlayLeg> = legs_dsl::parlay_legs
+            .filter(legs_dsl::event_id.eq_any(event_ids))
+            .load(conn)?;
+        let mut parlays: Vec<i32> = Vec::new();
+        for leg in legs {
+            if leg.status != BetStatus::Void {
+                ParlayLeg {
+                    status: BetStatus::Void,
+                    ..leg
+                }
+                .update(conn)?;
+            }
+            if !parlays.contains(&leg.bet_id) {
+                parlays.push(leg.bet_id);
+            }
+        }
+        for bet_id in parlays {
+            let bet = settle_parlay(conn, &bets_dsl::bets.find(bet_id).first(conn)?)?;
+            if bet.status != BetStatus::Open {
+                settled.push(bet);
+            }
+        }
+        Ok(settled)
+    })
+}
+
+/// Settle a single bet. The stake held in escrow is released to the house, then the house pays
+/// the punter whatever the bet is owed under `grade` minus what it has already been paid.
+pub fn settle_bet(conn: &PgConnection, bet: &Bet, grade: Grade) -> Result<Bet, DieselError> {
+    let owed = match grade {
+        Grade::Win => bet.payout(),
+        Grade::Push | Grade::Void => bet.stake,
+        Grade::Lose => 0,
+    };
+    transfer(conn, bet, BetStatus::from(grade), owed)
+}
+
+/// Settle a parlay from the grades of its legs, repricing it over the legs that didn't push or
+/// get voided. A ticket that a regrade leaves undecided is reopened and anything it was paid is
+/// taken back until its remaining legs are graded.
+pub fn settle_parlay(conn: &PgConnection, bet: &Bet) -> Result<Bet, DieselError> {
+    let legs = ParlayLeg::query(
+        conn,
+        &ParlayLegQuery {
+            bet_id: Some(bet.id),
+            event_id: None,
+        },
+    )?;
+    let mut bet = bet.clone();
+    if let Some(odds) = parlay::price(&legs) {
+        if odds != bet.odds {
+            bet = Bet { odds, ..bet }.update(conn)?;
+        }
+    }
+    match parlay::grade(&legs) {
+        Some(grade) => settle_bet(conn, &bet, grade),
+        None if bet.status == BetStatus::Open => Ok(bet),
+        None => transfer(conn, &bet, BetStatus::Open, 0),
+    }
+}
+
+/// Pay the punter `owed` on `bet` net of earlier settlements and record its new `status`. The
+/// stake stays in escrow while the bet is open, and goes back there if a regrade reopens it.
+fn transfer(
+    conn: &PgConnection,
+    bet: &Bet,
+    status: BetStatus,
+    owed: i64,
+) -> Result<Bet, DieselError> {
+    let wallet = Account::wallet(conn, bet.user_id)?;
+    let house = Account::system(conn, AccountKind::House)?;
+    let escrow = Account::system(conn, AccountKind::Escrow)?;
+    let entries = Entry::query(
+        conn,
+        &EntryQuery {
+            account_id: None,
+            bet_id: Some(bet.id),
+        },
+    )?;
+    // Net amount moved from one account to another on behalf of this bet
+    let net = |from: &Account, to: &Account| -> i64 {
+        entries
+            .iter()
+            .map(|e| {
+                if e.from_account_id == from.id && e.to_account_id == to.id {
+                    e.amount
+                } else if e.from_account_id == to.id && e.to_account_id == from.id {
+                    -e.amount
+                } else {
+                    0
+                }
+            })
+            .sum()
+    };
+
+    let held = net(&wallet, &escrow) - net(&escrow, &house);
+    if held > 0 && status != BetStatus::Open {
+        NewEntry::new(EntryKind::Stake, &escrow, &house, held)
+            .bet(bet.id)
+            .memo("stake released at settlement")
+            .create(conn)?;
+    } else if status == BetStatus::Open {
+        let released = net(&escrow, &house);
+        if released > 0 {
+            NewEntry::new(EntryKind::Stake, &house, &escrow, released)
+                .bet(bet.id)
+                .memo("stake returned to escrow on reopening")
+                .create(conn)?;
+        }
+    }
+
+    let delta = owed - net(&house, &wallet);
+    let kind = match (bet.status, status) {
+        (BetStatus::Open, BetStatus::Won) => EntryKind::Payout,
+        (BetStatus::Open, _) => EntryKind::Refund,
+        _ => EntryKind::Adjustment,
+    };
+    if delta > 0 {
+        NewEntry::new(kind, &house, &wallet, delta)
+            .bet(bet.id)
+            .create(conn)?;
+    } else if delta < 0 {
+        NewEntry::new(EntryKind::Adjustment, &wallet, &house, -delta)
+            .bet(bet.id)
+            .memo("reversal after regrade")
+            .create(conn)?;
+    }
+
+    if bet.status == status {
+        return Ok(bet.clone());
+    }
+    let settled_at = match status {
+        BetStatus::Open => None,
+        _ => Some(Utc::now().naive_utc()),
+    };
+    Bet {
+        status,
+        settled_at,
+        ..bet.clone()
+    }
+    .update(conn)
+}
@@ -0,0 +1,115 @@
+//! Team registry
+//!
+//! Every team a game can be played by, keyed by league and abbreviation. Games refer to teams by
+//! abbreviation; `Team::lookup` resolves a full name or one of a team's aliases to its team, so
+//! abbreviations and names can be translated either way.
+use crate::db::{Creatable, Retrievable, Updatable};
+use crate::schema::teams::{self, dsl as teams_dsl};
+
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+use diesel::{ExpressionMethods, Insertable, QueryDsl, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// A team in a league. Teams that have moved or folded are kept, but inactive, so the games they
+/// played still refer to them.
+#[derive(Clone, Debug, Deserialize, Serialize, Queryable, Insertable, ToSchema)]
+#[table_name = "teams"]
+pub struct Team {
+    pub league: String,
+    pub abbreviation: String,
+    pub name: String,
+    /// Other names and abbreviations the team goes by
+    pub aliases: Vec<String>,
+    pub active: bool,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TeamQuery {
+    pub league: Option<String>,
+    pub active: Option<bool>,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+/////// Implementations ///////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl Team {
+    /// Retrieve the team in `league` with the given `abbreviation`.
+    pub fn get(conn: &PgConnection, league: &str, abbreviation: &str) -> Result<Team, DieselError> {
+        teams_dsl::teams.find((league, abbreviation)).first(conn)
+    }
+
+    /// Find the team in `league` that goes by `name`, which may be its abbreviation, its full
+    /// name or one of its aliases. Active teams are preferred over inactive ones.
+    pub fn lookup(conn: &PgConnection, league: &str, name: &str) -> Result<Team, DieselError> {
+        let teams: Vec<Team> = teams_dsl::teams
+            .filter(teams_dsl::league.eq(league))
+            .order_by(teams_dsl::active.desc())
+            .load(conn)?;
+        teams
+            .into_iter()
+            .find(|team| team.goes_by(name))
+            .ok_or(DieselError::NotFound)
+    }
+
+    /// Whether the team goes by `name`, ignoring case and surrounding whitespace.
+    pub fn goes_by(&self, name: &str) -> bool {
+        let name = name.trim();
+        self.abbreviation.eq_ignore_ascii_case(name)
+            || self.name.eq_ignore_ascii_case(name)
+            || self.aliases.iter().any(|a| a.eq_ignore_ascii_case(name))
+    }
+}
+
+impl Creatable for Team {
+    type Output = Team;
+    fn create(&self, conn: &PgConnection) -> Result<Team, DieselError> {
+        diesel::insert_into(teams_dsl::teams)
+            .values(self)
+            .get_result(conn)
+    }
+}
+
+impl Updatable for Team {
+    /// Rename the team, change its aliases or retire it. Its league and abbreviation can't be
+    /// changed.
+    fn update(&self, conn: &PgConnection) -> Result<Team, DieselError> {
+        diesel::update(teams_dsl::teams.find((&self.league, &self.abbreviation)))
+            .set((
+                teams_dsl::name.eq(&self.name),
+                teams_dsl::aliases.eq(&self.aliases),
+                teams_dsl::active.eq(&self.active),
+            ))
+            .get_result(conn)
+    }
+}
+
+impl Retrievable<TeamQuery> for Team {
+    fn query(conn: &PgConnection, data: &TeamQuery) -> Result<Vec<Team>, DieselError> {
+        let mut query = teams_dsl::teams.into_boxed();
+        if let Some(_league) = &data.league {
+            query = query.filter(teams_dsl::league.eq(_league));
+        }
+        if let Some(_active) = data.active {
+            query = query.filter(teams_dsl::active.eq(_active));
+        }
+        query
+            .order_by((teams_dsl::league.asc(), teams_dsl::name.asc()))
+            .load(conn)
+    }
+
+    /// Every active team
+    fn all(conn: &PgConnection) -> Result<Vec<Team>, DieselError> {
+        Team::query(
+            conn,
+            &TeamQuery {
+                league: None,
+                active: Some(true),
+            },
+        )
+    }
+}
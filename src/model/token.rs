@@ -0,0 +1,111 @@
+//! Refresh tokens
+//!
+//! API clients trade a refresh token for a new access token once theirs expires. A token is
+//! handed out as `<id>.<secret>`; only an argon2 hash of the secret is stored, so a leaked table
+//! can't be replayed. Tokens are single use: redeeming one revokes it.
+use crate::db::{Creatable, Updatable};
+use crate::password;
+use crate::schema::refresh_tokens::{self, dsl as tokens_dsl};
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::pg::PgConnection;
+use diesel::result::Error as DieselError;
+use diesel::{ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+
+/// How long a refresh token can be redeemed for
+pub const REFRESH_TOKEN_DAYS: i64 = 30;
+
+#[derive(Clone, Debug, Deserialize, Serialize, Queryable)]
+pub struct RefreshToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub token_hash: String,
+    pub issued_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Insertable)]
+#[table_name = "refresh_tokens"]
+pub struct NewRefreshToken {
+    pub user_id: i32,
+    pub token_hash: String,
+    pub expires_at: NaiveDateTime,
+}
+
+///////////////////////////////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+/////// Implementations ///////////////////////////////////////////////////////////////////////////
+//                                                                                               //
+///////////////////////////////////////////////////////////////////////////////////////////////////
+impl RefreshToken {
+    /// Issue a new refresh token to `user_id`. Returns the stored token along with the string to
+    /// hand to the client, which can't be recovered later.
+    pub fn issue(conn: &PgConnection, user_id: i32) -> Result<(RefreshToken, String), DieselError> {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let secret: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        let token = NewRefreshToken {
+            user_id,
+            token_hash: password::hash(&secret)
+                .map_err(|e| DieselError::SerializationError(Box::new(e)))?,
+            expires_at: Utc::now().naive_utc() + Duration::days(REFRESH_TOKEN_DAYS),
+        }
+        .create(conn)?;
+        let value = format!("{}.{}", token.id, secret);
+        Ok((token, value))
+    }
+
+    /// Look up the token the client presented as `value`, if it exists, matches, hasn't expired
+    /// and hasn't been revoked. The row stays locked until the caller's transaction ends, so a
+    /// concurrent request redeeming the same token waits and then finds it revoked.
+    pub fn find_active(
+        conn: &PgConnection,
+        value: &str,
+    ) -> Result<Option<RefreshToken>, DieselError> {
+        let (id, secret) = match value.split_once('.').map(|(id, s)| (id.parse::<i32>(), s)) {
+            Some((Ok(id), secret)) => (id, secret),
+            _ => return Ok(None),
+        };
+        let token: Option<RefreshToken> = tokens_dsl::refresh_tokens
+            .filter(tokens_dsl::id.eq(id))
+            .filter(tokens_dsl::revoked_at.is_null())
+            .filter(tokens_dsl::expires_at.gt(Utc::now().naive_utc()))
+            .for_update()
+            .first(conn)
+            .optional()?;
+        Ok(token.filter(|t| {
+            password::is_hashed(&t.token_hash) && password::verify(secret, &t.token_hash)
+        }))
+    }
+
+    /// Revoke the token so it can't be redeemed again.
+    pub fn revoke(&self, conn: &PgConnection) -> Result<RefreshToken, DieselError> {
+        RefreshToken {
+            revoked_at: Some(Utc::now().naive_utc()),
+            ..self.clone()
+        }
+        .update(conn)
+    }
+}
+
+impl Creatable for NewRefreshToken {
+    type Output = RefreshToken;
+    fn create(&self, conn: &PgConnection) -> Result<RefreshToken, DieselError> {
+        diesel::insert_into(tokens_dsl::refresh_tokens)
+            .values(self)
+            .get_result(conn)
+    }
+}
+
+impl Updatable for RefreshToken {
+    /// Record when the token was revoked.
+    fn update(&self, conn: &PgConnection) -> Result<RefreshToken, DieselError> {
+        diesel::update(tokens_dsl::refresh_tokens)
+            .filter(tokens_dsl::id.eq(&self.id))
+            .set(tokens_dsl::revoked_at.eq(&self.revoked_at))
+            .get_result(conn)
+    }
+}
@@ -1,17 +1,24 @@
 use crate::db::{Creatable, Deletable, Retrievable};
-use crate::model::session::NewSession;
+use crate::model::session::{NewSession, Session};
+use crate::password;
 use crate::schema::users::{self, dsl as users_dsl};
 
 use diesel::pg::PgConnection;
-use diesel::sql_types::{Integer, Varchar};
-use diesel::{sql_query, ExpressionMethods, Insertable, QueryDsl, Queryable, RunQueryDsl};
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable,
+    RunQueryDsl,
+};
 use diesel_derive_enum::DbEnum;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 type DieselError = diesel::result::Error;
 
-#[derive(Clone, Copy, Debug, DbEnum, Deserialize, Serialize, PartialEq)]
+#[derive(Clone, Copy, Debug, DbEnum, Deserialize, Serialize, PartialEq, Eq, Hash, ToSchema)]
 pub enum Role {
-    Bookie,
+    Admin,
+    Trader,
+    Support,
+    Risk,
     Punter,
 }
 
@@ -24,31 +31,22 @@ pub struct NewUser {
     pub role: Role,
 }
 
-#[derive(Clone, Deserialize, Serialize, Queryable, QueryableByName)]
-#[table_name = "users"]
+#[derive(Clone, Deserialize, Serialize, Queryable, ToSchema)]
 pub struct User {
-    #[sql_type = "Integer"]
     pub id: i32,
-    #[sql_type = "Varchar"]
     pub email: String,
-    #[sql_type = "Varchar"]
     pub username: String,
-    #[sql_type = "Varchar"]
+    /// The password hash, which is never sent to clients
+    #[serde(skip_serializing)]
     pub password: String,
-    #[sql_type = "RoleMapping"]
     pub role: Role,
 }
 
-#[derive(Clone, Serialize, Deserialize, Queryable, QueryableByName)]
-#[table_name = "users"]
+#[derive(Clone, Serialize, Deserialize, Queryable)]
 pub struct AuthedUser {
-    #[sql_type = "Varchar"]
     pub email: String,
-    #[sql_type = "Varchar"]
     pub username: String,
-    #[sql_type = "Varchar"]
     pub password: String,
-    #[sql_type = "RoleMapping"]
     pub role: Role,
 }
 
@@ -63,14 +61,53 @@ pub struct UserQuery<'a> {
 /////// Implementations ///////////////////////////////////////////////////////////////////////////
 //                                                                                               //
 ///////////////////////////////////////////////////////////////////////////////////////////////////
+impl Role {
+    pub const ALL: [Role; 5] = [
+        Role::Admin,
+        Role::Trader,
+        Role::Support,
+        Role::Risk,
+        Role::Punter,
+    ];
+}
+
 impl User {
     pub fn new() -> Self {
         User::default()
     }
 
-    pub fn login(&self, conn: &PgConnection) -> Result<(), DieselError> {
-        let _ = NewSession::new(&self).create(conn);
-        Ok(())
+    /// Retrieve the user with the given `id`.
+    pub fn get(conn: &PgConnection, id: i32) -> Result<User, DieselError> {
+        users_dsl::users.find(id).first(conn)
+    }
+
+    /// Retrieve the user with the given `email`, if there is one. Emails are unique.
+    pub fn by_email(conn: &PgConnection, email: &str) -> Result<Option<User>, DieselError> {
+        users_dsl::users
+            .filter(users_dsl::email.eq(email))
+            .first(conn)
+            .optional()
+    }
+
+    /// Replace the user's stored password with a fresh hash of `password`.
+    pub fn rehash(&self, conn: &PgConnection, password: &str) -> Result<User, DieselError> {
+        let hash =
+            password::hash(password).map_err(|e| DieselError::SerializationError(Box::new(e)))?;
+        diesel::update(users_dsl::users.find(self.id))
+            .set(users_dsl::password.eq(hash))
+            .get_result(conn)
+    }
+
+    /// Give the user a new role.
+    pub fn set_role(&self, conn: &PgConnection, role: Role) -> Result<User, DieselError> {
+        diesel::update(users_dsl::users.find(self.id))
+            .set(users_dsl::role.eq(role))
+            .get_result(conn)
+    }
+
+    /// Start a new session for the user.
+    pub fn login(&self, conn: &PgConnection) -> Result<Session, DieselError> {
+        NewSession::new(&self).create(conn)
     }
 }
 
@@ -93,12 +130,15 @@ impl Deletable for User {
 }
 
 impl Retrievable<UserQuery<'_>> for User {
+    /// Retrieves the users whose email or username matches the query's.
     fn query(conn: &PgConnection, data: &UserQuery) -> Result<Vec<User>, DieselError> {
-        let stmt = format!(
-            "SELECT * FROM users WHERE email = '{}' or username ='{}'",
-            data.email, data.username
-        );
-        sql_query(stmt).load(conn)
+        users_dsl::users
+            .filter(
+                users_dsl::email
+                    .eq(data.email)
+                    .or(users_dsl::username.eq(data.username)),
+            )
+            .load(conn)
     }
 
     fn all(conn: &PgConnection) -> Result<Vec<User>, DieselError> {
@@ -108,9 +148,15 @@ impl Retrievable<UserQuery<'_>> for User {
 
 impl Creatable for NewUser {
     type Output = User;
+    /// Create the user, storing a hash of their password.
     fn create(&self, conn: &PgConnection) -> Result<User, DieselError> {
+        let hash = password::hash(&self.password)
+            .map_err(|e| DieselError::SerializationError(Box::new(e)))?;
         diesel::insert_into(users_dsl::users)
-            .values(self)
+            .values(&NewUser {
+                password: hash,
+                ..self.clone()
+            })
             .get_result(conn)
     }
 }
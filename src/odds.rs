@@ -0,0 +1,321 @@
+//! Odds math
+//!
+//! Prices are stored as American odds. `Odds` validates them and converts between American,
+//! decimal and fractional formats, implied probabilities and payouts. Amounts are in cents.
+use diesel::deserialize::{self, FromSql};
+use diesel::pg::Pg;
+use diesel::serialize::{self, Output, ToSql};
+use diesel::sql_types::Integer;
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+use std::io::Write;
+use std::str::FromStr;
+use std::{error, fmt};
+use utoipa::ToSchema;
+
+/// A validated price in American odds. Values between -100 and +100 don't exist, even money is
+/// always stored as +100, and no price is longer than `Odds::LIMIT` either way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, AsExpression, FromSqlRow, ToSchema)]
+#[sql_type = "Integer"]
+pub struct Odds(i32);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OddsError {
+    OutOfRange(i32),
+    InvalidDecimal(f64),
+    InvalidFractional(u32, u32),
+    InvalidProbability(f64),
+    Unparseable(String),
+}
+
+impl fmt::Display for OddsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OddsError::OutOfRange(v) => write!(f, "OutOfRange: {}", v),
+            OddsError::InvalidDecimal(d) => write!(f, "InvalidDecimal: {}", d),
+            OddsError::InvalidFractional(n, d) => write!(f, "InvalidFractional: {}/{}", n, d),
+            OddsError::InvalidProbability(p) => write!(f, "InvalidProbability: {}", p),
+            OddsError::Unparseable(s) => write!(f, "Unparseable: {}", s),
+        }
+    }
+}
+
+impl error::Error for OddsError {}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+impl Odds {
+    /// Even money, +100 or 2.0 in decimal
+    pub const EVEN: Odds = Odds(100);
+
+    /// The longest price accepted, either way: +1000000 or -1000000
+    pub const LIMIT: i32 = 1_000_000;
+
+    /// Create odds from an American price. Rejects anything strictly between -100 and +100, and
+    /// anything past `Odds::LIMIT`.
+    pub fn american(value: i32) -> Result<Odds, OddsError> {
+        match value {
+            -100 | 100 => Ok(Odds::EVEN),
+            v if (101..=Odds::LIMIT).contains(&v) || (-Odds::LIMIT..=-101).contains(&v) => {
+                Ok(Odds(v))
+            }
+            v => Err(OddsError::OutOfRange(v)),
+        }
+    }
+
+    /// Create odds from a decimal price, which must be greater than 1.0. The result is rounded to
+    /// the nearest whole American price.
+    pub fn from_decimal(decimal: f64) -> Result<Odds, OddsError> {
+        if !decimal.is_finite() || decimal <= 1.0 {
+            return Err(OddsError::InvalidDecimal(decimal));
+        }
+        let american = if decimal >= 2.0 {
+            ((decimal - 1.0) * 100.0).round()
+        } else {
+            (-100.0 / (decimal - 1.0)).round()
+        };
+        // Checked before casting, which would saturate
+        if american.abs() > Odds::LIMIT as f64 {
+            return Err(OddsError::InvalidDecimal(decimal));
+        }
+        Odds::american(american as i32).map_err(|_| OddsError::InvalidDecimal(decimal))
+    }
+
+    /// Create odds from a fractional price `numerator/denominator`, e.g. 5/2.
+    pub fn from_fractional(numerator: u32, denominator: u32) -> Result<Odds, OddsError> {
+        if numerator == 0 || denominator == 0 {
+            return Err(OddsError::InvalidFractional(numerator, denominator));
+        }
+        Odds::from_decimal(1.0 + numerator as f64 / denominator as f64)
+            .map_err(|_| OddsError::InvalidFractional(numerator, denominator))
+    }
+
+    /// Create the fair odds for an outcome with probability `p`.
+    pub fn from_probability(p: f64) -> Result<Odds, OddsError> {
+        if !(p > 0.0 && p < 1.0) {
+            return Err(OddsError::InvalidProbability(p));
+        }
+        Odds::from_decimal(1.0 / p).map_err(|_| OddsError::InvalidProbability(p))
+    }
+
+    /// The American price, e.g. -110
+    pub fn value(&self) -> i32 {
+        self.0
+    }
+
+    /// The decimal price, stake included, e.g. 1.909 for -110
+    pub fn to_decimal(&self) -> f64 {
+        if self.0 > 0 {
+            1.0 + self.0 as f64 / 100.0
+        } else {
+            1.0 + 100.0 / -self.0 as f64
+        }
+    }
+
+    /// The fractional price in lowest terms, e.g. (10, 11) for -110
+    pub fn to_fractional(&self) -> (u32, u32) {
+        let (num, den) = if self.0 > 0 {
+            (self.0 as u32, 100)
+        } else {
+            (100, self.0.unsigned_abs())
+        };
+        let d = gcd(num, den);
+        (num / d, den / d)
+    }
+
+    /// Probability of the outcome implied by the price, bookmaker margin included
+    pub fn implied_probability(&self) -> f64 {
+        1.0 / self.to_decimal()
+    }
+
+    /// Winnings on a winning bet of `stake` cents, excluding the stake. Rounded down to the cent,
+    /// and capped at `i64::MAX` for stakes too large to pay out.
+    pub fn profit(&self, stake: i64) -> i64 {
+        let winnings = if self.0 > 0 {
+            stake.checked_mul(self.0 as i64).map(|w| w / 100)
+        } else {
+            stake.checked_mul(100).map(|w| w / -(self.0 as i64))
+        };
+        winnings.unwrap_or(i64::MAX)
+    }
+
+    /// Amount returned on a winning bet of `stake` cents, stake included. Capped like `profit`.
+    pub fn payout(&self, stake: i64) -> i64 {
+        stake.saturating_add(self.profit(stake))
+    }
+
+    /// Move the price by `cents` the way a bookie quotes it, skipping the gap between -100 and
+    /// +100: a positive value lengthens the price (-110 to -105, +100 to +105) and a negative one
+    /// shortens it (+100 to -105). The price stops at `Odds::LIMIT`.
+    pub fn step(&self, cents: i32) -> Odds {
+        let limit = Odds::LIMIT - 100;
+        let scale =
+            self.scale()
+                .checked_add(cents)
+                .unwrap_or(if cents > 0 { limit } else { -limit });
+        Odds::from_scale(scale.clamp(-limit, limit))
+    }
+
+    /// Clamp the price between `shortest` and `longest`. Bounds given the wrong way round are
+    /// swapped.
+    pub fn clamp(&self, shortest: Odds, longest: Odds) -> Odds {
+        let (low, high) = (shortest.scale(), longest.scale());
+        Odds::from_scale(self.scale().clamp(low.min(high), low.max(high)))
+    }
+
+    /// Position of the price on a continuous scale where even money is 0
+    fn scale(&self) -> i32 {
+        if self.0 > 0 {
+            self.0 - 100
+        } else {
+            self.0 + 100
+        }
+    }
+
+    fn from_scale(scale: i32) -> Odds {
+        if scale >= 0 {
+            Odds(scale + 100)
+        } else {
+            Odds(scale - 100)
+        }
+    }
+}
+
+/// The bookmaker's margin across a complete set of mutually exclusive outcomes, as a fraction of
+/// the stake. Two sides at -110 have an overround of about 0.0476.
+pub fn overround(prices: &[Odds]) -> f64 {
+    prices.iter().map(Odds::implied_probability).sum::<f64>() - 1.0
+}
+
+/// Remove the bookmaker's margin from a complete set of outcomes, returning each outcome's fair
+/// probability. Implied probabilities are scaled so that they sum to one.
+pub fn remove_vig(prices: &[Odds]) -> Vec<f64> {
+    let book: f64 = prices.iter().map(Odds::implied_probability).sum();
+    prices
+        .iter()
+        .map(|p| p.implied_probability() / book)
+        .collect()
+}
+
+/// The combined price of a parlay, the product of its legs' decimal prices rounded to the nearest
+/// whole American price. Returns `None` when there are no legs left to price.
+pub fn parlay(legs: &[Odds]) -> Option<Odds> {
+    if legs.is_empty() {
+        return None;
+    }
+    Odds::from_decimal(legs.iter().map(Odds::to_decimal).product()).ok()
+}
+
+/// Closing-line value of a bet taken at `placed` on a selection that closed at `closing`: the
+/// closing implied probability minus the one the bet was priced at. A positive value means the
+/// bet beat the closing line, e.g. +150 against a close of +120 is worth about 5.5 points.
+pub fn closing_line_value(placed: Odds, closing: Odds) -> f64 {
+    closing.implied_probability() - placed.implied_probability()
+}
+
+impl fmt::Display for Odds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:+}", self.0)
+    }
+}
+
+/// Parse a price in any of the supported formats: American (`-110`, `+150`), decimal (`1.91`),
+/// fractional (`10/11`) or `EVEN`.
+impl FromStr for Odds {
+    type Err = OddsError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let unparseable = || OddsError::Unparseable(s.to_owned());
+        if s.eq_ignore_ascii_case("even") || s.eq_ignore_ascii_case("evs") {
+            Ok(Odds::EVEN)
+        } else if let Some((num, den)) = s.split_once('/') {
+            let num = num.trim().parse().map_err(|_| unparseable())?;
+            let den = den.trim().parse().map_err(|_| unparseable())?;
+            Odds::from_fractional(num, den)
+        } else if s.contains('.') {
+            Odds::from_decimal(s.parse().map_err(|_| unparseable())?)
+        } else {
+            Odds::american(s.parse().map_err(|_| unparseable())?)
+        }
+    }
+}
+
+impl TryFrom<i32> for Odds {
+    type Error = OddsError;
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        Odds::american(value)
+    }
+}
+
+impl From<Odds> for i32 {
+    fn from(odds: Odds) -> i32 {
+        odds.0
+    }
+}
+
+impl PartialEq<i32> for Odds {
+    fn eq(&self, other: &i32) -> bool {
+        self.0 == *other
+    }
+}
+
+impl Serialize for Odds {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i32(self.0)
+    }
+}
+
+struct OddsVisitor;
+
+impl<'de> Visitor<'de> for OddsVisitor {
+    type Value = Odds;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "American, decimal or fractional odds")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Odds, E> {
+        let v = i32::try_from(v).map_err(|_| E::custom(format!("odds out of range: {}", v)))?;
+        Odds::american(v).map_err(E::custom)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Odds, E> {
+        let v = i32::try_from(v).map_err(|_| E::custom(format!("odds out of range: {}", v)))?;
+        Odds::american(v).map_err(E::custom)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Odds, E> {
+        Odds::from_decimal(v).map_err(E::custom)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Odds, E> {
+        v.parse().map_err(E::custom)
+    }
+}
+
+/// Odds deserialize from an American integer, a decimal float or any string `FromStr` accepts,
+/// so form fields can be entered in whichever format the bookie prefers.
+impl<'de> Deserialize<'de> for Odds {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Odds, D::Error> {
+        deserializer.deserialize_any(OddsVisitor)
+    }
+}
+
+impl ToSql<Integer, Pg> for Odds {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, Pg>) -> serialize::Result {
+        <i32 as ToSql<Integer, Pg>>::to_sql(&self.0, out)
+    }
+}
+
+impl FromSql<Integer, Pg> for Odds {
+    fn from_sql(bytes: Option<&[u8]>) -> deserialize::Result<Self> {
+        let value = <i32 as FromSql<Integer, Pg>>::from_sql(bytes)?;
+        Ok(Odds::american(value)?)
+    }
+}
@@ -0,0 +1,73 @@
+//! Password hashing
+//!
+//! Passwords are stored as argon2id hashes in PHC string format, which carries the salt and
+//! parameters alongside the hash. Parameters come from the environment so they can be raised
+//! over time; hashes made with older parameters, and plaintext passwords left from before
+//! hashing, are replaced the next time their owner logs in.
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{Error as HashError, SaltString};
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
+use std::env;
+
+/// Read a cost parameter from the environment variable `key`, or use `default` if it is unset.
+fn cost(key: &str, default: u32) -> u32 {
+    env::var(key)
+        .map(|v| {
+            v.parse()
+                .unwrap_or_else(|_| panic!("{} must be a positive integer.", key))
+        })
+        .unwrap_or(default)
+}
+
+/// The configured parameters: memory in KiB (`ARGON2_MEMORY_COST`), iterations
+/// (`ARGON2_TIME_COST`) and lanes (`ARGON2_PARALLELISM`).
+pub fn params() -> Result<Params, HashError> {
+    Params::new(
+        cost("ARGON2_MEMORY_COST", Params::DEFAULT_M_COST),
+        cost("ARGON2_TIME_COST", Params::DEFAULT_T_COST),
+        cost("ARGON2_PARALLELISM", Params::DEFAULT_P_COST),
+        None,
+    )
+    .map_err(HashError::from)
+}
+
+/// Hash `password` with argon2id and a fresh random salt.
+pub fn hash(password: &str) -> Result<String, HashError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params()?)
+        .hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+}
+
+/// Whether `stored` is a password hash rather than a legacy plaintext password
+pub fn is_hashed(stored: &str) -> bool {
+    PasswordHash::new(stored).is_ok()
+}
+
+/// Check `password` against the `stored` hash, using the parameters recorded in the hash. Legacy
+/// plaintext passwords are compared directly.
+pub fn verify(password: &str, stored: &str) -> bool {
+    match PasswordHash::new(stored) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => password == stored,
+    }
+}
+
+/// Whether `stored` should be replaced with a fresh hash: it is plaintext, isn't argon2id, or was
+/// made with parameters other than the configured ones.
+pub fn needs_rehash(stored: &str) -> bool {
+    let hash = match PasswordHash::new(stored) {
+        Ok(hash) => hash,
+        Err(_) => return true,
+    };
+    match (Params::try_from(&hash), params()) {
+        (Ok(old), Ok(new)) => {
+            hash.algorithm != Algorithm::Argon2id.ident()
+                || (old.m_cost(), old.t_cost(), old.p_cost())
+                    != (new.m_cost(), new.t_cost(), new.p_cost())
+        }
+        _ => true,
+    }
+}
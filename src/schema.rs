@@ -1,3 +1,44 @@
+table! {
+    use diesel::sql_types::*;
+    use crate::exports::*;
+
+    accounts (id) {
+        id -> Int4,
+        kind -> AccountKind,
+        user_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::exports::*;
+
+    bets (id) {
+        id -> Int4,
+        user_id -> Int4,
+        event_id -> Nullable<Int4>,
+        stake -> Int8,
+        odds -> Int4,
+        placed_at -> Timestamp,
+        status -> BetStatus,
+        settled_at -> Nullable<Timestamp>,
+        event_timestamp -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::exports::*;
+
+    closing_lines (id) {
+        id -> Int4,
+        event_id -> Int4,
+        event_timestamp -> Timestamp,
+        odds -> Int4,
+        captured_at -> Timestamp,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use crate::exports::*;
@@ -8,6 +49,34 @@ table! {
         odds -> Int4,
         game_id -> Nullable<Int4>,
         timestamp -> Timestamp,
+        market_kind -> Nullable<MarketKind>,
+        team -> Nullable<TeamSide>,
+        total -> Nullable<TotalSide>,
+        line -> Nullable<Int4>,
+        market_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::exports::*;
+
+    feed_games (provider, provider_id) {
+        provider -> Varchar,
+        provider_id -> Varchar,
+        game_id -> Int4,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::exports::*;
+
+    feed_teams (provider, league, provider_id) {
+        provider -> Varchar,
+        provider_id -> Varchar,
+        league -> Varchar,
+        abbreviation -> Varchar,
     }
 }
 
@@ -23,16 +92,175 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use crate::exports::*;
+
+    game_transitions (id) {
+        id -> Int4,
+        game_id -> Int4,
+        from_status -> GameStatus,
+        to_status -> GameStatus,
+        user_id -> Nullable<Int4>,
+        changed_at -> Timestamp,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use crate::exports::*;
 
     games (id) {
         id -> Int4,
-        league -> League,
+        league -> Varchar,
         home -> Varchar,
         away -> Varchar,
         start -> Timestamp,
+        status -> GameStatus,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::exports::*;
+
+    leagues (code) {
+        code -> Varchar,
+        name -> Varchar,
+        sport -> Varchar,
+        draws_allowed -> Bool,
+        periods -> Int4,
+        active -> Bool,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::exports::*;
+
+    ledger_entries (id) {
+        id -> Int4,
+        kind -> EntryKind,
+        from_account_id -> Int4,
+        to_account_id -> Int4,
+        amount -> Int8,
+        bet_id -> Nullable<Int4>,
+        memo -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::exports::*;
+
+    market_templates (id) {
+        id -> Int4,
+        league -> Varchar,
+        kind -> MarketKind,
+        team -> Nullable<TeamSide>,
+        line -> Nullable<Int4>,
+        odds -> Int4,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::exports::*;
+
+    markets (id) {
+        id -> Int4,
+        game_id -> Int4,
+        kind -> MarketKind,
+        team -> Nullable<TeamSide>,
+        line -> Nullable<Int4>,
+        suspended -> Bool,
+        voided -> Bool,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::exports::*;
+
+    parlay_legs (id) {
+        id -> Int4,
+        bet_id -> Int4,
+        event_id -> Int4,
+        odds -> Int4,
+        status -> BetStatus,
+        event_timestamp -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::exports::*;
+
+    price_moves (id) {
+        id -> Int4,
+        market_id -> Int4,
+        event_id -> Int4,
+        event_timestamp -> Timestamp,
+        old_odds -> Int4,
+        new_odds -> Int4,
+        reason -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::exports::*;
+
+    pricing_policies (id) {
+        id -> Int4,
+        market_id -> Int4,
+        threshold -> Int8,
+        step -> Int4,
+        min_odds -> Int4,
+        max_odds -> Int4,
+        enabled -> Bool,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::exports::*;
+
+    refresh_tokens (id) {
+        id -> Int4,
+        user_id -> Int4,
+        token_hash -> Varchar,
+        issued_at -> Timestamp,
+        expires_at -> Timestamp,
+        revoked_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::exports::*;
+
+    result_corrections (id) {
+        id -> Int4,
+        result_id -> Int4,
+        user_id -> Int4,
+        old_home -> Int4,
+        old_away -> Int4,
+        new_home -> Int4,
+        new_away -> Int4,
+        corrected_at -> Timestamp,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::exports::*;
+
+    role_permissions (role, permission) {
+        role -> Role,
+        permission -> Permission,
     }
 }
 
@@ -48,6 +276,19 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::*;
+    use crate::exports::*;
+
+    teams (league, abbreviation) {
+        league -> Varchar,
+        abbreviation -> Varchar,
+        name -> Varchar,
+        aliases -> Array<Text>,
+        active -> Bool,
+    }
+}
+
 table! {
     use diesel::sql_types::*;
     use crate::exports::*;
@@ -61,14 +302,48 @@ table! {
     }
 }
 
+joinable!(accounts -> users (user_id));
+joinable!(bets -> users (user_id));
 joinable!(events -> games (game_id));
+joinable!(events -> markets (market_id));
+joinable!(feed_games -> games (game_id));
 joinable!(game_results -> games (game_id));
+joinable!(game_transitions -> games (game_id));
+joinable!(game_transitions -> users (user_id));
+joinable!(games -> leagues (league));
+joinable!(ledger_entries -> bets (bet_id));
+joinable!(market_templates -> leagues (league));
+joinable!(markets -> games (game_id));
+joinable!(parlay_legs -> bets (bet_id));
+joinable!(price_moves -> markets (market_id));
+joinable!(pricing_policies -> markets (market_id));
+joinable!(refresh_tokens -> users (user_id));
+joinable!(result_corrections -> game_results (result_id));
+joinable!(result_corrections -> users (user_id));
 joinable!(sessions -> users (user_id));
+joinable!(teams -> leagues (league));
 
 allow_tables_to_appear_in_same_query!(
+    accounts,
+    bets,
+    closing_lines,
     events,
+    feed_games,
+    feed_teams,
     game_results,
+    game_transitions,
     games,
+    leagues,
+    ledger_entries,
+    market_templates,
+    markets,
+    parlay_legs,
+    price_moves,
+    pricing_policies,
+    refresh_tokens,
+    result_corrections,
+    role_permissions,
     sessions,
+    teams,
     users,
 );
@@ -23,7 +23,6 @@ mod form_tests {
             username: "foobars".to_owned(),
             password1: "password".to_owned(),
             password2: "password".to_owned(),
-            role: crate::model::user::Role::Punter,
         };
     }
 
@@ -36,12 +35,25 @@ mod form_tests {
             username: "foobars".to_owned(),
             password1: "password".to_owned(),
             password2: "password".to_owned(),
-            role: crate::model::user::Role::Bookie,
         };
         let res = dta.authenticate(&conn);
         assert!(res.is_err())
     }
 
+    #[test]
+    fn signup_needs_username() {
+        let dta = SignupForm {
+            email: "nameless@email.com".to_owned(),
+            username: " ".to_owned(),
+            password1: "password".to_owned(),
+            password2: "password".to_owned(),
+        };
+        assert!(matches!(
+            dta.validate(),
+            Err(ValidationError::MissingUsername)
+        ));
+    }
+
     #[actix_web::main]
     #[test]
     async fn associated_user_returned() {
@@ -66,6 +78,29 @@ mod form_tests {
         assert!(usr.is_none());
     }
 
+    #[test]
+    fn login_ignores_empty_usernames() {
+        use crate::db::{Creatable, Deletable};
+        use crate::model::user::{NewUser, Role};
+        let conn = establish_connection().unwrap();
+        let nameless = NewUser {
+            email: "nameless@bar.com".to_owned(),
+            username: String::new(),
+            password: "hunter22".to_owned(),
+            role: Role::Punter,
+        }
+        .create(&conn);
+        let res = LoginForm {
+            email: "doesnt@exist.com".to_owned(),
+            password: "hunter22".to_owned(),
+        }
+        .authenticate(&conn);
+        assert!(matches!(res, Err(AuthError::EmailNotFound)));
+        if let Ok(nameless) = nameless {
+            let _ = nameless.delete(&conn);
+        }
+    }
+
     #[test]
     fn user_authenticated() {
         let conn = establish_connection().unwrap();
@@ -77,19 +112,69 @@ mod form_tests {
         assert!(usr.is_ok())
     }
 
+    #[test]
+    fn legacy_password_rehashed_on_login() {
+        use crate::db::Deletable;
+        use crate::model::user::{NewUser, Role, User};
+        use crate::schema::users::dsl::users;
+        use diesel::RunQueryDsl;
+        let conn = establish_connection().unwrap();
+        let legacy: User = diesel::insert_into(users)
+            .values(&NewUser {
+                email: "legacy@user.com".to_string(),
+                username: "legacy-user".to_string(),
+                password: "password".to_string(),
+                role: Role::Punter,
+            })
+            .get_result(&conn)
+            .unwrap();
+        let form = LoginForm {
+            email: "legacy@user.com".to_string(),
+            password: "password".to_string(),
+        };
+        let usr = form.clone().authenticate(&conn).unwrap();
+        assert!(crate::password::is_hashed(&usr.password));
+        assert!(form.authenticate(&conn).is_ok());
+        let _ = legacy.delete(&conn);
+    }
+
     #[test]
     fn password_validated() {
-        use crate::model::user::Role;
         let form = SignupForm {
             email: "cyobero@gmail.com".to_string(),
             username: "cyobero".to_string(),
             password1: "password123".to_string(),
             password2: "password123".to_string(),
-            role: Role::Bookie,
         };
         let res = form.validate();
         assert!(res.is_ok());
     }
+
+    #[test]
+    fn game_start_parsed_without_panicking() {
+        use chrono::NaiveDate;
+        let form = |start: &str| GameForm {
+            start: start.to_string(),
+            ..GameForm::new()
+        };
+        assert_eq!(
+            form("2022-11-13T13:00").start_to_naive().unwrap(),
+            NaiveDate::from_ymd(2022, 11, 13).and_hms(13, 0, 0)
+        );
+        assert!(form("1987-10-03T17:00:00").start_to_naive().is_ok());
+        for start in [
+            "",
+            "tomorrow",
+            "2022-13-01T13:00",
+            "2022-11-13T25:00",
+            "2022-11-13T13:0é",
+        ] {
+            assert!(matches!(
+                form(start).start_to_naive(),
+                Err(ValidationError::InvalidStart)
+            ));
+        }
+    }
 }
 
 #[cfg(test)]
@@ -98,17 +183,43 @@ mod db_tests {
     use crate::db::*;
     use crate::model::user::*;
     use crate::model::*;
+    use crate::odds::Odds;
     use crate::schema::events::{self, dsl};
     use chrono::naive::{NaiveDate, NaiveDateTime};
+    use chrono::{Duration, Utc};
+    use diesel::pg::PgConnection;
     use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
 
+    /// A game that hasn't started, so it takes bets
+    fn upcoming(conn: &PgConnection, home: &str, away: &str) -> Game {
+        NewGame {
+            league: "NFL".to_string(),
+            home: home.to_string(),
+            away: away.to_string(),
+            start: Utc::now().naive_utc() + Duration::days(1),
+        }
+        .create(conn)
+        .unwrap()
+    }
+
+    /// Move `game`'s start into the past, as if it had kicked off, so its result can be recorded.
+    fn kick_off(conn: &PgConnection, game: &Game) -> Game {
+        Game {
+            start: Utc::now().naive_utc() - Duration::hours(3),
+            ..game.clone()
+        }
+        .update(conn)
+        .unwrap()
+    }
+
     #[test]
     fn new_event_created_is_ok() {
         let conn = establish_connection().unwrap();
         let new = NewEvent {
             description: "CHI (+3) vs DET (-3)".to_owned(),
             game_id: 1,
-            odds: -110,
+            odds: Odds::american(-110).unwrap(),
+            ..Default::default()
         };
         let event = new.create(&conn).unwrap();
         assert_eq!(event.odds, -110);
@@ -130,17 +241,20 @@ mod db_tests {
             NewEvent {
                 description: "FOO vs BAR (-6.5)".to_owned(),
                 game_id: 1,
-                odds: -105,
+                odds: Odds::american(-105).unwrap(),
+                ..Default::default()
             },
             NewEvent {
                 description: "FOO (+6.5) vs BAR".to_owned(),
                 game_id: 1,
-                odds: -110,
+                odds: Odds::american(-110).unwrap(),
+                ..Default::default()
             },
             NewEvent {
                 description: "CHI vs BOS U 51.5".to_owned(),
                 game_id: 1,
-                odds: -110,
+                odds: Odds::american(-110).unwrap(),
+                ..Default::default()
             },
         ];
 
@@ -182,7 +296,8 @@ mod db_tests {
         let new = NewEvent {
             description: "test".to_string(),
             game_id: 1,
-            odds: 110,
+            odds: Odds::american(110).unwrap(),
+            ..Default::default()
         };
         let event = new.create(&conn).unwrap();
         let deleted = event.delete(&conn);
@@ -203,7 +318,7 @@ mod db_tests {
         use crate::schema::games::dsl::*;
         let conn = establish_connection().unwrap();
         let new = NewGame {
-            league: League::NBA,
+            league: "NBA".to_string(),
             home: "BOS".to_string(),
             away: "GSW".to_string(),
             start: NaiveDate::from_ymd(2022, 06, 08).and_hms(17, 30, 0),
@@ -213,6 +328,31 @@ mod db_tests {
         let _ = diesel::delete(games.find(game.id)).get_result::<Game>(&conn);
     }
 
+    #[test]
+    fn game_updated_and_deleted() {
+        let conn = establish_connection().unwrap();
+        let game = NewGame {
+            league: "NBA".to_string(),
+            home: "BOS".to_string(),
+            away: "GSW".to_string(),
+            start: NaiveDate::from_ymd(2022, 06, 13).and_hms(21, 0, 0),
+        }
+        .create(&conn)
+        .unwrap();
+        let moved = Game {
+            start: NaiveDate::from_ymd(2022, 06, 14).and_hms(21, 0, 0),
+            ..game.clone()
+        }
+        .update(&conn)
+        .unwrap();
+        assert_eq!(
+            moved.start,
+            NaiveDate::from_ymd(2022, 06, 14).and_hms(21, 0, 0)
+        );
+        moved.delete(&conn).unwrap();
+        assert!(Game::get(&conn, game.id).is_err());
+    }
+
     #[test]
     fn all_games_retrieved() {
         let conn = establish_connection().unwrap();
@@ -228,15 +368,37 @@ mod db_tests {
             email: "foo1@bar.com".to_string(),
             username: "test-user".to_string(),
             password: "password".to_string(),
-            role: Bookie,
+            role: Trader,
         };
         let conn = establish_connection().unwrap();
         let new = usr.create(&conn).unwrap();
-        assert_eq!(new.role, Bookie);
+        assert_eq!(new.role, Trader);
+        assert_ne!(new.password, "password");
         let del = new.delete(&conn);
         assert!(del.is_ok());
     }
 
+    #[test]
+    fn user_email_taken_refused() {
+        use crate::model::user::{NewUser, Role};
+        use diesel::result::{DatabaseErrorKind, Error as DieselError};
+        let conn = establish_connection().unwrap();
+        let res = NewUser {
+            email: "foo@bar.com".to_string(),
+            username: "someone-else".to_string(),
+            password: "password".to_string(),
+            role: Role::Punter,
+        }
+        .create(&conn);
+        assert!(matches!(
+            res,
+            Err(DieselError::DatabaseError(
+                DatabaseErrorKind::UniqueViolation,
+                _
+            ))
+        ));
+    }
+
     #[test]
     fn user_queried() {
         use crate::model::user::{User, UserQuery};
@@ -252,6 +414,21 @@ mod db_tests {
         assert_ne!(res.len(), 0);
     }
 
+    #[test]
+    fn user_query_parameters_bound() {
+        use crate::model::user::{User, UserQuery};
+        let conn = establish_connection().unwrap();
+        let res = User::query(
+            &conn,
+            &UserQuery {
+                email: "' OR '1'='1",
+                username: "' OR '1'='1",
+            },
+        )
+        .unwrap();
+        assert_eq!(res.len(), 0);
+    }
+
     #[test]
     fn nba_games_retrieved() {
         let conn = establish_connection().unwrap();
@@ -259,12 +436,13 @@ mod db_tests {
         let games = Game::query(
             &conn,
             &GameQuery {
-                league: Some(League::NBA),
+                league: Some("NBA".to_string()),
+                status: None,
             },
         )
         .unwrap();
         assert_ne!(games.len(), 0);
-        assert_eq!(games[0].league, League::NBA);
+        assert_eq!(games[0].league, "NBA");
     }
 
     #[test]
@@ -274,18 +452,19 @@ mod db_tests {
         let games = Game::query(
             &conn,
             &GameQuery {
-                league: Some(League::NFL),
+                league: Some("NFL".to_string()),
+                status: None,
             },
         )
         .unwrap();
         assert_ne!(games.len(), 0);
-        assert_eq!(games[0].league, League::NFL);
+        assert_eq!(games[0].league, "NFL");
     }
 
     #[test]
     fn all_games_retrieved_no_league_input() {
         let conn = establish_connection().unwrap();
-        let games = Game::query(&conn, &GameQuery { league: None }).unwrap();
+        let games = Game::query(&conn, &GameQuery::default()).unwrap();
         assert_ne!(games.len(), 0);
     }
 
@@ -335,4 +514,2389 @@ mod db_tests {
         assert_ne!(res.logout_date, None);
         let _ = res.delete(&conn);
     }
+
+    #[test]
+    fn logged_out_session_inactive() {
+        use crate::model::session::*;
+        use chrono::Utc;
+        let conn = establish_connection().unwrap();
+        let usr = User::query(
+            &conn,
+            &UserQuery {
+                email: "foo@bar.com",
+                username: "",
+            },
+        )
+        .map(|usrs| usrs[0].clone())
+        .unwrap();
+        let sess = usr.login(&conn).unwrap();
+        let (active, owner) = Session::active(&conn, sess.id).unwrap().unwrap();
+        assert_eq!((active.id, owner.id), (sess.id, usr.id));
+        let ended = Session {
+            logout_date: Some(Utc::now().naive_utc()),
+            ..sess
+        }
+        .update(&conn)
+        .unwrap();
+        assert!(Session::active(&conn, sess.id).unwrap().is_none());
+        let _ = ended.delete(&conn);
+    }
+
+    #[test]
+    fn bet_placed_at_event_odds() {
+        use crate::model::bet::*;
+        use crate::model::ledger::Account;
+        let conn = establish_connection().unwrap();
+        let usr = User::query(
+            &conn,
+            &UserQuery {
+                email: "foo@bar.com",
+                username: "",
+            },
+        )
+        .map(|usrs| usrs[0].clone())
+        .unwrap();
+        let game = upcoming(&conn, "CHI", "DET");
+        let event = NewEvent {
+            description: "LAL (-5.5) vs BOS".to_owned(),
+            game_id: game.id,
+            odds: Odds::american(-110).unwrap(),
+            ..Default::default()
+        }
+        .create(&conn)
+        .unwrap();
+        Account::deposit(&conn, usr.id, 2_500).unwrap();
+        let bet = NewBet::new(usr.id, &event, 2_500).place(&conn).unwrap();
+        assert_eq!(bet.odds, -110);
+        assert_eq!(bet.stake, 2_500);
+
+        let res = Bet::query(
+            &conn,
+            &BetQuery {
+                user_id: Some(usr.id),
+                event_id: Some(event.id),
+            },
+        )
+        .unwrap();
+        assert_eq!(res.len(), 1);
+        let _ = bet.delete(&conn);
+        let _ = event.delete(&conn);
+        let _ = game.delete(&conn);
+    }
+
+    #[test]
+    fn repricing_writes_new_version() {
+        use crate::model::bet::{BetError, NewBet};
+        use crate::model::ledger::Account;
+        let conn = establish_connection().unwrap();
+        let usr = User::query(
+            &conn,
+            &UserQuery {
+                email: "foo@bar.com",
+                username: "",
+            },
+        )
+        .map(|usrs| usrs[0].clone())
+        .unwrap();
+        let game = upcoming(&conn, "CHI", "DET");
+        let event = NewEvent {
+            description: "MIL ML".to_owned(),
+            game_id: game.id,
+            odds: Odds::american(-150).unwrap(),
+            ..Default::default()
+        }
+        .create(&conn)
+        .unwrap();
+        let repriced = event.reprice(&conn, Odds::american(-130).unwrap()).unwrap();
+        assert_eq!(repriced.id, event.id);
+        assert!(repriced.timestamp > event.timestamp);
+        assert_eq!(Event::latest(&conn, event.id).unwrap().odds, -130);
+
+        let history = LineHistory::new(&Event::history(&conn, event.id).unwrap()).unwrap();
+        assert_eq!(history.series.odds, vec![-150, -130]);
+        assert_eq!(history.history[1].timestamp, repriced.timestamp);
+
+        Account::deposit(&conn, usr.id, 1_000).unwrap();
+        let stale = NewBet::new(usr.id, &event, 1_000).place(&conn);
+        assert!(matches!(stale, Err(BetError::PriceChanged)));
+        let bet = NewBet::new(usr.id, &repriced, 1_000).place(&conn).unwrap();
+        assert_eq!(bet.odds, -130);
+        assert_eq!(bet.event_timestamp, Some(repriced.timestamp));
+
+        let _ = bet.delete(&conn);
+        let _ = event.delete(&conn);
+        let _ = game.delete(&conn);
+    }
+
+    #[test]
+    fn versions_in_one_transaction_ordered() {
+        use diesel::result::Error as DieselError;
+        use diesel::Connection;
+        let conn = establish_connection().unwrap();
+        let event = NewEvent {
+            description: "MIL ML".to_owned(),
+            game_id: 1,
+            odds: Odds::american(-150).unwrap(),
+            ..Default::default()
+        }
+        .create(&conn)
+        .unwrap();
+        let (first, second) = conn
+            .transaction::<_, DieselError, _>(|| {
+                let first = event.reprice(&conn, Odds::american(-140).unwrap())?;
+                let second = first.reprice(&conn, Odds::american(-130).unwrap())?;
+                Ok((first, second))
+            })
+            .unwrap();
+        assert!(first.timestamp > event.timestamp);
+        assert!(second.timestamp > first.timestamp);
+        assert_eq!(Event::latest(&conn, event.id).unwrap().odds, -130);
+        let _ = event.delete(&conn);
+    }
+
+    #[test]
+    fn stake_debited_from_wallet() {
+        use crate::model::bet::{BetError, NewBet};
+        use crate::model::ledger::*;
+        let conn = establish_connection().unwrap();
+        let usr = User::query(
+            &conn,
+            &UserQuery {
+                email: "foo@bar.com",
+                username: "",
+            },
+        )
+        .map(|usrs| usrs[0].clone())
+        .unwrap();
+        let game = upcoming(&conn, "CHI", "DET");
+        let event = NewEvent {
+            description: "CHI vs DET O 44.5".to_owned(),
+            game_id: game.id,
+            odds: Odds::american(-110).unwrap(),
+            ..Default::default()
+        }
+        .create(&conn)
+        .unwrap();
+        let wallet = Account::wallet(&conn, usr.id).unwrap();
+        Account::deposit(&conn, usr.id, 1_000).unwrap();
+        let before = wallet.balance(&conn).unwrap();
+
+        let bet = NewBet::new(usr.id, &event, 1_000).place(&conn).unwrap();
+        assert_eq!(wallet.balance(&conn).unwrap(), before - 1_000);
+        let entries = Entry::query(
+            &conn,
+            &EntryQuery {
+                account_id: None,
+                bet_id: Some(bet.id),
+            },
+        )
+        .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].kind, EntryKind::Stake);
+
+        let res = NewBet::new(usr.id, &event, before + 1).place(&conn);
+        assert!(matches!(res, Err(BetError::InsufficientFunds)));
+        let _ = bet.delete(&conn);
+        let _ = event.delete(&conn);
+        let _ = game.delete(&conn);
+    }
+
+    #[test]
+    fn bets_settled_and_regraded() {
+        use crate::model::bet::*;
+        use crate::model::game_result::*;
+        use crate::model::ledger::Account;
+        use crate::model::market::*;
+        use crate::schema::games::dsl::*;
+        let conn = establish_connection().unwrap();
+        let usr = User::query(
+            &conn,
+            &UserQuery {
+                email: "foo@bar.com",
+                username: "",
+            },
+        )
+        .map(|usrs| usrs[0].clone())
+        .unwrap();
+        let game = NewGame {
+            league: "NBA".to_string(),
+            home: "BOS".to_string(),
+            away: "GSW".to_string(),
+            start: Utc::now().naive_utc() + Duration::days(1),
+        }
+        .create(&conn)
+        .unwrap();
+        let event = NewEvent::new(
+            &game,
+            Selection::Spread(TeamSide::Home, Line::from_points(-3.5).unwrap()),
+            Odds::american(150).unwrap(),
+        )
+        .create(&conn)
+        .unwrap();
+        assert_eq!(event.description, "BOS -3.5");
+        let wallet = Account::wallet(&conn, usr.id).unwrap();
+        Account::deposit(&conn, usr.id, 1_000).unwrap();
+        let before = wallet.balance(&conn).unwrap();
+        let bet = NewBet::new(usr.id, &event, 1_000).place(&conn).unwrap();
+        let game = kick_off(&conn, &game);
+
+        let result = NewGameResult {
+            home: 107,
+            away: 97,
+            game_id: game.id,
+        }
+        .create(&conn)
+        .unwrap();
+        let won = &Bet::query(
+            &conn,
+            &BetQuery {
+                user_id: None,
+                event_id: Some(event.id),
+            },
+        )
+        .unwrap()[0];
+        assert_eq!(won.status, BetStatus::Won);
+        assert_eq!(wallet.balance(&conn).unwrap(), before + 1_500);
+
+        // Score correction turns the win into a loss and claws back the payout
+        let corrected = GameResult {
+            home: 100,
+            ..result
+        }
+        .update(&conn)
+        .unwrap();
+        let lost = &Bet::query(
+            &conn,
+            &BetQuery {
+                user_id: None,
+                event_id: Some(event.id),
+            },
+        )
+        .unwrap()[0];
+        assert_eq!(lost.status, BetStatus::Lost);
+        assert_eq!(wallet.balance(&conn).unwrap(), before - 1_000);
+
+        // Re-running settlement without a change posts nothing
+        corrected.update(&conn).unwrap();
+        assert_eq!(wallet.balance(&conn).unwrap(), before - 1_000);
+
+        let _ = bet.delete(&conn);
+        let _ = diesel::delete(games.find(game.id)).get_result::<Game>(&conn);
+    }
+
+    #[test]
+    fn parlay_settled_leg_by_leg() {
+        use crate::model::bet::*;
+        use crate::model::game_result::NewGameResult;
+        use crate::model::ledger::Account;
+        use crate::model::market::*;
+        use crate::model::parlay::NewParlay;
+        use crate::schema::games::dsl::*;
+        let conn = establish_connection().unwrap();
+        let usr = User::query(
+            &conn,
+            &UserQuery {
+                email: "foo@bar.com",
+                username: "",
+            },
+        )
+        .map(|usrs| usrs[0].clone())
+        .unwrap();
+        let mut selections = Vec::new();
+        let mut created = Vec::new();
+        for (h, a, kind, l) in [
+            ("MIA", "NYK", MarketKind::Moneyline, None),
+            ("DEN", "PHX", MarketKind::Spread, Some(-3.0)),
+        ] {
+            let game = NewGame {
+                league: "NBA".to_string(),
+                home: h.to_string(),
+                away: a.to_string(),
+                start: Utc::now().naive_utc() + Duration::days(1),
+            }
+            .create(&conn)
+            .unwrap();
+            let market = Market::open(
+                &conn,
+                &game,
+                &NewMarket {
+                    game_id: game.id,
+                    kind,
+                    team: None,
+                    line: l.map(|p| Line::from_points(p).unwrap().0),
+                },
+                [Odds::american(150).unwrap(), Odds::american(-170).unwrap()],
+            )
+            .unwrap();
+            selections.push(market.selections(&conn).unwrap());
+            created.push(game);
+        }
+        Account::deposit(&conn, usr.id, 1_000).unwrap();
+        let wallet = Account::wallet(&conn, usr.id).unwrap();
+        let before = wallet.balance(&conn).unwrap();
+
+        let res = NewParlay {
+            user_id: usr.id,
+            stake: 1_000,
+            event_ids: vec![selections[0][0].id, selections[0][1].id],
+        }
+        .place(&conn);
+        assert!(matches!(res, Err(BetError::SameMarket)));
+
+        let bet = NewParlay {
+            user_id: usr.id,
+            stake: 1_000,
+            event_ids: vec![selections[0][0].id, selections[1][0].id],
+        }
+        .place(&conn)
+        .unwrap();
+        assert_eq!(bet.event_id, None);
+        assert_eq!(bet.odds, 525);
+        assert_eq!(wallet.balance(&conn).unwrap(), before - 1_000);
+        for game in &created {
+            kick_off(&conn, game);
+        }
+
+        let ticket = || {
+            Bet::query(&conn, &BetQuery::default())
+                .unwrap()
+                .into_iter()
+                .find(|b| b.id == bet.id)
+                .unwrap()
+        };
+        // First leg wins, the ticket waits for the second game
+        NewGameResult {
+            home: 110,
+            away: 100,
+            game_id: created[0].id,
+        }
+        .create(&conn)
+        .unwrap();
+        assert_eq!(ticket().status, BetStatus::Open);
+
+        // Second leg pushes, so the ticket is repriced at the first leg's price alone
+        NewGameResult {
+            home: 103,
+            away: 100,
+            game_id: created[1].id,
+        }
+        .create(&conn)
+        .unwrap();
+        let won = ticket();
+        assert_eq!(won.status, BetStatus::Won);
+        assert_eq!(won.odds, 150);
+        assert_eq!(wallet.balance(&conn).unwrap(), before + 1_500);
+
+        let _ = bet.delete(&conn);
+        for game in created {
+            let _ = diesel::delete(games.find(game.id)).get_result::<Game>(&conn);
+        }
+    }
+
+    #[test]
+    fn reopened_parlay_stake_back_in_escrow() {
+        use crate::model::bet::*;
+        use crate::model::game_result::*;
+        use crate::model::ledger::*;
+        use crate::model::market::*;
+        use crate::model::parlay::NewParlay;
+        use crate::schema::games::dsl::*;
+        let conn = establish_connection().unwrap();
+        let usr = User::query(
+            &conn,
+            &UserQuery {
+                email: "foo@bar.com",
+                username: "",
+            },
+        )
+        .map(|usrs| usrs[0].clone())
+        .unwrap();
+        let mut selections = Vec::new();
+        let mut created = Vec::new();
+        for (h, a) in [("SEA", "ARI"), ("LAR", "SF")] {
+            let game = upcoming(&conn, h, a);
+            let market = Market::open(
+                &conn,
+                &game,
+                &NewMarket {
+                    game_id: game.id,
+                    kind: MarketKind::Moneyline,
+                    team: None,
+                    line: None,
+                },
+                [Odds::american(150).unwrap(), Odds::american(-170).unwrap()],
+            )
+            .unwrap();
+            selections.push(market.selections(&conn).unwrap());
+            created.push(game);
+        }
+        Account::deposit(&conn, usr.id, 1_000).unwrap();
+        let wallet = Account::wallet(&conn, usr.id).unwrap();
+        let before = wallet.balance(&conn).unwrap();
+        let bet = NewParlay {
+            user_id: usr.id,
+            stake: 1_000,
+            event_ids: vec![selections[0][0].id, selections[1][0].id],
+        }
+        .place(&conn)
+        .unwrap();
+        for game in &created {
+            kick_off(&conn, game);
+        }
+
+        let escrow = Account::system(&conn, AccountKind::Escrow).unwrap();
+        // What escrow holds on behalf of the ticket
+        let held = || -> i64 {
+            Entry::query(
+                &conn,
+                &EntryQuery {
+                    account_id: Some(escrow.id),
+                    bet_id: Some(bet.id),
+                },
+            )
+            .unwrap()
+            .iter()
+            .map(|e| {
+                if e.to_account_id == escrow.id {
+                    e.amount
+                } else {
+                    -e.amount
+                }
+            })
+            .sum()
+        };
+        let status = || {
+            Bet::query(&conn, &BetQuery::default())
+                .unwrap()
+                .into_iter()
+                .find(|b| b.id == bet.id)
+                .unwrap()
+                .status
+        };
+        assert_eq!(held(), 1_000);
+
+        // First leg loses, which decides the ticket and releases the stake to the house
+        let result = NewGameResult {
+            home: 17,
+            away: 20,
+            game_id: created[0].id,
+        }
+        .create(&conn)
+        .unwrap();
+        assert_eq!(status(), BetStatus::Lost);
+        assert_eq!(held(), 0);
+
+        // A correction makes it a winner, so the ticket waits on the second game again
+        GameResult { home: 24, ..result }.update(&conn).unwrap();
+        assert_eq!(status(), BetStatus::Open);
+        assert_eq!(held(), 1_000);
+        assert_eq!(wallet.balance(&conn).unwrap(), before - 1_000);
+
+        let _ = bet.delete(&conn);
+        for game in created {
+            let _ = diesel::delete(games.find(game.id)).get_result::<Game>(&conn);
+        }
+    }
+
+    #[test]
+    fn closing_line_captured_before_start() {
+        use crate::model::bet::NewBet;
+        use crate::model::closing::*;
+        use crate::model::ledger::Account;
+        use crate::schema::games::dsl::*;
+        let conn = establish_connection().unwrap();
+        let usr = User::query(
+            &conn,
+            &UserQuery {
+                email: "foo@bar.com",
+                username: "",
+            },
+        )
+        .map(|usrs| usrs[0].clone())
+        .unwrap();
+        let game = NewGame {
+            league: "NFL".to_string(),
+            home: "KC".to_string(),
+            away: "BUF".to_string(),
+            start: NaiveDate::from_ymd(2100, 1, 20).and_hms(18, 30, 0),
+        }
+        .create(&conn)
+        .unwrap();
+        let event = NewEvent {
+            description: "BUF ML".to_owned(),
+            game_id: game.id,
+            odds: Odds::american(150).unwrap(),
+            ..Default::default()
+        }
+        .create(&conn)
+        .unwrap();
+        Account::deposit(&conn, usr.id, 1_000).unwrap();
+        let bet = NewBet::new(usr.id, &event, 1_000).place(&conn).unwrap();
+        let closed = event.reprice(&conn, Odds::american(120).unwrap()).unwrap();
+
+        // The game hasn't started yet
+        let before = NaiveDate::from_ymd(2099, 1, 1).and_hms(0, 0, 0);
+        let captured = ClosingLine::capture_started(&conn, before).unwrap();
+        assert!(captured.iter().all(|c| c.event_id != event.id));
+
+        // However long ago the game started, it is caught while it has no closing line
+        let after = game.start + Duration::days(365);
+        let captured: Vec<ClosingLine> = ClosingLine::capture_started(&conn, after)
+            .unwrap()
+            .into_iter()
+            .filter(|c| c.event_id == event.id)
+            .collect();
+        assert_eq!(captured.len(), 1);
+        assert_eq!(captured[0].event_timestamp, closed.timestamp);
+        assert_eq!(captured[0].odds, 120);
+        // Capturing again doesn't overwrite the closing line
+        assert!(ClosingLine::capture(&conn, &game).unwrap().is_empty());
+
+        let clv = closing_line_values(&conn, &[bet.clone()]).unwrap();
+        assert!((clv[&bet.id] - 0.0545).abs() < 0.0001);
+
+        let _ = bet.delete(&conn);
+        let _ = diesel::delete(games.find(game.id)).get_result::<Game>(&conn);
+    }
+
+    #[test]
+    fn bet_refused_on_suspended_market() {
+        use crate::model::bet::{BetError, NewBet};
+        use crate::model::ledger::Account;
+        use crate::model::market::*;
+        use crate::schema::games::dsl::*;
+        let conn = establish_connection().unwrap();
+        let usr = User::query(
+            &conn,
+            &UserQuery {
+                email: "foo@bar.com",
+                username: "",
+            },
+        )
+        .map(|usrs| usrs[0].clone())
+        .unwrap();
+        let game = NewGame {
+            league: "NBA".to_string(),
+            home: "LAL".to_string(),
+            away: "BOS".to_string(),
+            start: Utc::now().naive_utc() + Duration::days(1),
+        }
+        .create(&conn)
+        .unwrap();
+        let vig = Odds::american(-110).unwrap();
+        let market = Market::open(
+            &conn,
+            &game,
+            &NewMarket {
+                game_id: game.id,
+                kind: MarketKind::Spread,
+                team: None,
+                line: Some(Line::from_points(-5.5).unwrap().0),
+            },
+            [vig, vig],
+        )
+        .unwrap();
+        let selections = market.selections(&conn).unwrap();
+        assert_eq!(selections.len(), 2);
+        assert_eq!(selections[1].description, "BOS +5.5");
+        assert!((market.margin(&conn).unwrap() - 0.0476).abs() < 0.001);
+
+        Account::deposit(&conn, usr.id, 1_000).unwrap();
+        let suspended = Market {
+            suspended: true,
+            ..market
+        }
+        .update(&conn)
+        .unwrap();
+        let res = NewBet::new(usr.id, &selections[0], 1_000).place(&conn);
+        assert!(matches!(res, Err(BetError::MarketSuspended)));
+
+        let resumed = Market {
+            suspended: false,
+            ..suspended
+        }
+        .update(&conn)
+        .unwrap();
+        let bet = NewBet::new(usr.id, &selections[0], 1_000)
+            .place(&conn)
+            .unwrap();
+        let liability = resumed.liability(&conn).unwrap();
+        assert_eq!(liability[0].house_result, 1_000 - bet.payout());
+        assert_eq!(liability[1].house_result, 1_000);
+
+        let _ = bet.delete(&conn);
+        let _ = diesel::delete(games.find(game.id)).get_result::<Game>(&conn);
+    }
+}
+
+#[cfg(test)]
+mod market_tests {
+    use crate::model::game_result::GameResult;
+    use crate::model::market::*;
+    use crate::model::settlement::Grade;
+    use crate::model::{Game, GameStatus};
+    use chrono::NaiveDate;
+
+    fn game() -> Game {
+        Game {
+            id: 1,
+            league: "NFL".to_string(),
+            home: "CHI".to_string(),
+            away: "DET".to_string(),
+            start: NaiveDate::from_ymd(2022, 9, 11).and_hms(13, 0, 0),
+            status: GameStatus::Scheduled,
+        }
+    }
+
+    fn result(home: i32, away: i32) -> GameResult {
+        GameResult {
+            id: 1,
+            home,
+            away,
+            game_id: 1,
+        }
+    }
+
+    fn line(points: f64) -> Line {
+        Line::from_points(points).unwrap()
+    }
+
+    #[test]
+    fn lines_must_be_half_points() {
+        assert_eq!(line(-5.5), Line(-11));
+        assert!(Line::from_points(3.25).is_err());
+        assert_eq!("PK".parse::<Line>().unwrap(), Line(0));
+    }
+
+    #[test]
+    fn markets_described() {
+        let g = game();
+        assert_eq!(Selection::Moneyline(TeamSide::Away).describe(&g), "DET ML");
+        assert_eq!(
+            Selection::Spread(TeamSide::Home, line(3.0)).describe(&g),
+            "CHI +3"
+        );
+        assert_eq!(
+            Selection::Total(TotalSide::Under, line(44.5)).describe(&g),
+            "CHI vs DET U 44.5"
+        );
+        assert_eq!(
+            Selection::TeamTotal(TeamSide::Away, TotalSide::Over, line(20.5)).describe(&g),
+            "DET O 20.5"
+        );
+    }
+
+    #[test]
+    fn missing_line_rejected() {
+        let res = Selection::from_parts(MarketKind::Spread, Some(TeamSide::Home), None, None);
+        assert!(matches!(res, Err(MarketError::MissingLine)));
+    }
+
+    #[test]
+    fn market_selections_paired() {
+        let new = NewMarket {
+            game_id: 1,
+            kind: MarketKind::Spread,
+            team: None,
+            line: Some(line(-3.5).0),
+        };
+        let [home, away] = new.selections().unwrap();
+        assert_eq!(home.describe(&game()), "CHI -3.5");
+        assert_eq!(away.describe(&game()), "DET +3.5");
+        let r = result(20, 23);
+        assert_eq!((home.grade(&r), away.grade(&r)), (Grade::Lose, Grade::Win));
+    }
+
+    #[test]
+    fn market_consistency() {
+        assert!(Market::is_consistent(&[Grade::Win, Grade::Lose]));
+        assert!(Market::is_consistent(&[Grade::Push, Grade::Push]));
+        assert!(!Market::is_consistent(&[Grade::Win, Grade::Win]));
+        assert!(!Market::is_consistent(&[Grade::Lose, Grade::Lose]));
+        assert!(!Market::is_consistent(&[Grade::Win, Grade::Push]));
+    }
+
+    #[test]
+    fn markets_graded() {
+        let r = result(20, 23);
+        assert_eq!(
+            Selection::Spread(TeamSide::Home, line(3.0)).grade(&r),
+            Grade::Push
+        );
+        assert_eq!(
+            Selection::Spread(TeamSide::Home, line(3.5)).grade(&r),
+            Grade::Win
+        );
+        assert_eq!(Selection::Moneyline(TeamSide::Away).grade(&r), Grade::Win);
+        assert_eq!(
+            Selection::Total(TotalSide::Under, line(42.5)).grade(&r),
+            Grade::Lose
+        );
+        assert_eq!(
+            Selection::TeamTotal(TeamSide::Away, TotalSide::Over, line(22.5)).grade(&r),
+            Grade::Win
+        );
+    }
+}
+
+#[cfg(test)]
+mod odds_tests {
+    use crate::odds::*;
+
+    #[test]
+    fn american_odds_validated() {
+        assert!(Odds::american(0).is_err());
+        assert!(Odds::american(50).is_err());
+        assert!(Odds::american(-99).is_err());
+        assert_eq!(Odds::american(-100).unwrap(), Odds::EVEN);
+    }
+
+    #[test]
+    fn out_of_range_odds_not_wrapped() {
+        assert_eq!(serde_json::from_str::<Odds>("-110").unwrap(), -110);
+        // 2^32 + 100 would wrap to +100
+        assert!(serde_json::from_str::<Odds>("4294967396").is_err());
+        assert!(serde_json::from_str::<Odds>("-4294967396").is_err());
+        // In range for an i32, but too long a price to pay out without overflowing
+        assert!(serde_json::from_str::<Odds>("-2147483648").is_err());
+        assert!("-2147483648".parse::<Odds>().is_err());
+        assert!(Odds::american(Odds::LIMIT).is_ok());
+        assert!(Odds::american(-Odds::LIMIT - 1).is_err());
+    }
+
+    #[test]
+    fn decimals_near_even_not_saturated() {
+        assert!(Odds::from_decimal(1.0000000001).is_err());
+        assert!("1.0000000001".parse::<Odds>().is_err());
+        assert_eq!(Odds::from_decimal(1.0001).unwrap(), -Odds::LIMIT);
+        assert!(Odds::from_decimal(1.00001).is_err());
+        assert!(Odds::from_decimal(1e12).is_err());
+    }
+
+    #[test]
+    fn longest_prices_paid_without_overflow() {
+        let longest = Odds::american(-Odds::LIMIT).unwrap();
+        assert_eq!(longest.profit(1_000_000), 100);
+        assert_eq!(
+            Odds::american(Odds::LIMIT).unwrap().profit(i64::MAX),
+            i64::MAX
+        );
+        assert_eq!(longest.step(-5), longest);
+        assert_eq!(
+            Odds::american(Odds::LIMIT).unwrap().step(i32::MAX),
+            Odds::LIMIT
+        );
+    }
+
+    #[test]
+    fn odds_converted_between_formats() {
+        let odds = Odds::american(-110).unwrap();
+        assert_eq!(odds.to_fractional(), (10, 11));
+        assert!((odds.to_decimal() - 1.909).abs() < 0.001);
+        assert_eq!(Odds::from_decimal(2.5).unwrap(), 150);
+        assert_eq!(Odds::from_fractional(5, 2).unwrap(), 250);
+        assert_eq!("10/11".parse::<Odds>().unwrap(), -110);
+        assert_eq!("1.91".parse::<Odds>().unwrap(), -110);
+        assert!("+50".parse::<Odds>().is_err());
+    }
+
+    #[test]
+    fn payout_includes_stake() {
+        assert_eq!(Odds::american(-110).unwrap().payout(1_100), 2_100);
+        assert_eq!(Odds::american(250).unwrap().payout(1_000), 3_500);
+    }
+
+    #[test]
+    fn vig_removed() {
+        let prices = [Odds::american(-110).unwrap(), Odds::american(-110).unwrap()];
+        assert!((overround(&prices) - 0.0476).abs() < 0.0001);
+        let fair = remove_vig(&prices);
+        assert!((fair[0] - 0.5).abs() < 1e-9);
+        assert!((fair.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn closing_line_value_positive_when_beating_close() {
+        let placed = Odds::american(150).unwrap();
+        let close = Odds::american(120).unwrap();
+        assert!((closing_line_value(placed, close) - 0.0545).abs() < 0.0001);
+        assert!(closing_line_value(close, placed) < 0.0);
+        assert_eq!(closing_line_value(placed, placed), 0.0);
+    }
+
+    #[test]
+    fn parlay_priced_from_legs() {
+        let legs = [Odds::american(150).unwrap(), Odds::american(200).unwrap()];
+        assert_eq!(parlay(&legs).unwrap(), 650);
+        let vig = Odds::american(-110).unwrap();
+        assert_eq!(parlay(&[vig, vig]).unwrap(), 264);
+        assert!(parlay(&[]).is_none());
+    }
+
+    #[test]
+    fn odds_stepped_across_even_money() {
+        let vig = Odds::american(-110).unwrap();
+        assert_eq!(vig.step(5), -105);
+        assert_eq!(vig.step(15), 105);
+        assert_eq!(Odds::american(100).unwrap().step(-5), -105);
+        assert_eq!(Odds::american(150).unwrap().step(-10), 140);
+        let (shortest, longest) = (Odds::american(-200).unwrap(), Odds::american(200).unwrap());
+        assert_eq!(Odds::american(-250).unwrap().clamp(shortest, longest), -200);
+        assert_eq!(Odds::american(300).unwrap().clamp(shortest, longest), 200);
+        assert_eq!(vig.clamp(shortest, longest), vig);
+        // Inverted bounds are swapped rather than panicking
+        assert_eq!(Odds::american(300).unwrap().clamp(longest, shortest), 200);
+    }
+}
+
+#[cfg(test)]
+mod parlay_tests {
+    use crate::model::bet::BetStatus;
+    use crate::model::parlay::*;
+    use crate::model::settlement::Grade;
+    use crate::odds::Odds;
+
+    fn leg(odds: i32, status: BetStatus) -> ParlayLeg {
+        ParlayLeg {
+            id: 1,
+            bet_id: 1,
+            event_id: 1,
+            odds: Odds::american(odds).unwrap(),
+            status,
+            event_timestamp: None,
+        }
+    }
+
+    #[test]
+    fn parlay_graded_from_legs() {
+        let won = leg(150, BetStatus::Won);
+        assert_eq!(grade(&[won, leg(200, BetStatus::Open)]), None);
+        assert_eq!(grade(&[won, leg(200, BetStatus::Lost)]), Some(Grade::Lose));
+        assert_eq!(
+            grade(&[leg(200, BetStatus::Open), leg(200, BetStatus::Lost)]),
+            Some(Grade::Lose)
+        );
+        assert_eq!(grade(&[won, leg(200, BetStatus::Push)]), Some(Grade::Win));
+        assert_eq!(
+            grade(&[leg(150, BetStatus::Void), leg(200, BetStatus::Push)]),
+            Some(Grade::Push)
+        );
+    }
+
+    #[test]
+    fn parlay_repriced_without_pushed_legs() {
+        let legs = [
+            leg(150, BetStatus::Won),
+            leg(200, BetStatus::Push),
+            leg(-110, BetStatus::Open),
+        ];
+        assert_eq!(price(&legs).unwrap(), Odds::american(377).unwrap());
+        assert!(price(&[leg(150, BetStatus::Void)]).is_none());
+    }
+}
+
+#[cfg(test)]
+mod exposure_tests {
+    use super::establish_connection;
+    use crate::model::exposure::*;
+    use crate::model::market::{Market, MarketKind, SelectionLiability};
+    use crate::model::user::Role;
+    use crate::model::{Game, GameStatus};
+    use crate::odds::Odds;
+    use chrono::NaiveDate;
+
+    fn selection(event_id: i32, stake: i64, payout: i64, taken: i64) -> SelectionLiability {
+        SelectionLiability {
+            event_id,
+            description: String::new(),
+            odds: Odds::EVEN,
+            stake,
+            payout,
+            house_result: taken - payout,
+        }
+    }
+
+    fn game(id: i32, league: &str, day: u32, worst_case: i64) -> GameExposure {
+        GameExposure {
+            game: Game {
+                id,
+                league: league.to_string(),
+                home: "HOM".to_string(),
+                away: "AWY".to_string(),
+                start: NaiveDate::from_ymd(2022, 10, day).and_hms(19, 0, 0),
+                status: GameStatus::Scheduled,
+            },
+            stake: 1_000,
+            worst_case,
+            markets: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn market_worst_case_is_costliest_selection() {
+        let market = Market {
+            id: 1,
+            game_id: 1,
+            kind: MarketKind::Moneyline,
+            team: None,
+            line: None,
+            suspended: false,
+            voided: false,
+        };
+        let exposure = MarketExposure::new(
+            &market,
+            vec![
+                selection(1, 3_000, 7_500, 4_000),
+                selection(2, 1_000, 1_900, 4_000),
+            ],
+        );
+        assert_eq!(exposure.stake, 4_000);
+        assert_eq!(exposure.worst_case, -3_500);
+    }
+
+    #[test]
+    fn exposure_rolled_up_by_league_and_day() {
+        let games = [
+            game(1, "NBA", 18, -500),
+            game(2, "NBA", 19, 200),
+            game(3, "NFL", 18, -1_000),
+        ];
+        let leagues = rollup(&games, |g| g.league.to_string());
+        assert_eq!(leagues.len(), 2);
+        assert_eq!(leagues[0].key, "NBA");
+        assert_eq!((leagues[0].games, leagues[0].worst_case), (2, -300));
+        let days = rollup(&games, |g| g.start.date().to_string());
+        assert_eq!(days[0].key, "2022-10-18");
+        assert_eq!((days[0].stake, days[0].worst_case), (2_000, -1_500));
+    }
+
+    #[actix_web::main]
+    #[test]
+    async fn exposure_only_for_bookies() {
+        use crate::auth::TokenPair;
+        use crate::db::{Creatable, Deletable};
+        use crate::handler::report;
+        use crate::model::user::NewUser;
+        use actix_web::http::{header, StatusCode};
+        use actix_web::{test, web, App};
+        use diesel::pg::PgConnection;
+        use diesel::r2d2::{ConnectionManager, Pool};
+        let conn = establish_connection().unwrap();
+        let pool = Pool::builder()
+            .build(ConnectionManager::<PgConnection>::new(
+                std::env::var("DATABASE_URL").unwrap(),
+            ))
+            .unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(crate::templates()))
+                .data(pool)
+                .service(report::get_exposure),
+        )
+        .await;
+        for (role, status) in [
+            (Role::Punter, StatusCode::FORBIDDEN),
+            (Role::Risk, StatusCode::OK),
+            (Role::Admin, StatusCode::OK),
+        ] {
+            let usr = NewUser {
+                email: format!("exposure-{:?}@bar.com", role).to_lowercase(),
+                username: format!("exposure-{:?}", role).to_lowercase(),
+                password: "password".to_string(),
+                role,
+            }
+            .create(&conn)
+            .unwrap();
+            let token = TokenPair::issue(&conn, &usr).unwrap().access_token;
+            let req = test::TestRequest::get()
+                .uri("/exposure")
+                .header(header::AUTHORIZATION, format!("Bearer {}", token))
+                .to_request();
+            let res = test::call_service(&mut app, req).await;
+            assert_eq!(res.status(), status, "{:?}", role);
+            let _ = usr.delete(&conn);
+        }
+    }
+}
+
+#[cfg(test)]
+mod pricing_tests {
+    use super::establish_connection;
+    use crate::db::Creatable;
+    use crate::model::market::{MarketKind, NewMarket, SelectionLiability};
+    use crate::model::pricing::*;
+    use crate::model::NewGame;
+    use crate::odds::Odds;
+    use chrono::NaiveDate;
+    use diesel::result::Error as DieselError;
+    use diesel::Connection;
+
+    fn policy(threshold: i64) -> PricingPolicy {
+        PricingPolicy {
+            id: 1,
+            market_id: 1,
+            threshold,
+            step: 5,
+            min_odds: Odds::american(-120).unwrap(),
+            max_odds: Odds::american(120).unwrap(),
+            enabled: true,
+        }
+    }
+
+    fn selection(event_id: i32, odds: i32, house_result: i64) -> SelectionLiability {
+        SelectionLiability {
+            event_id,
+            description: format!("selection {}", event_id),
+            odds: Odds::american(odds).unwrap(),
+            stake: 0,
+            payout: 0,
+            house_result,
+        }
+    }
+
+    #[test]
+    fn balanced_book_not_repriced() {
+        let selections = [selection(1, -110, -500), selection(2, -110, 400)];
+        assert!(policy(1_000).decide(&selections).is_empty());
+    }
+
+    #[test]
+    fn heavy_side_shortened_and_other_side_lengthened() {
+        let selections = [selection(1, -110, -1_000), selection(2, -110, 900)];
+        let moves = policy(1_000).decide(&selections);
+        assert_eq!(moves.len(), 2);
+        assert_eq!(
+            (moves[0].event_id, moves[0].new_odds),
+            (1, Odds::american(-115).unwrap())
+        );
+        assert_eq!(
+            (moves[1].event_id, moves[1].new_odds),
+            (2, Odds::american(-105).unwrap())
+        );
+        assert!(moves[0].reason.contains("selection 1"));
+    }
+
+    #[test]
+    fn repricing_stops_at_limits() {
+        let selections = [selection(1, -120, -1_000), selection(2, 115, 900)];
+        let moves = policy(1_000).decide(&selections);
+        assert_eq!(moves.len(), 1);
+        assert_eq!(
+            (moves[0].event_id, moves[0].new_odds),
+            (2, Odds::american(120).unwrap())
+        );
+    }
+
+    #[test]
+    fn prices_outside_limits_not_moved_against_book() {
+        // Clamping would lengthen the heavy side and shorten the other one
+        let selections = [selection(1, -150, -1_000), selection(2, 200, 900)];
+        assert!(policy(1_000).decide(&selections).is_empty());
+
+        let selections = [selection(1, 200, -1_000), selection(2, -150, 900)];
+        let moves = policy(1_000).decide(&selections);
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[0].new_odds, Odds::american(120).unwrap());
+        assert_eq!(moves[1].new_odds, Odds::american(-120).unwrap());
+    }
+
+    #[test]
+    fn inverted_limits_refused() {
+        let conn = establish_connection().unwrap();
+        conn.test_transaction::<_, DieselError, _>(|| {
+            let game = NewGame {
+                league: "NBA".to_string(),
+                home: "DEN".to_string(),
+                away: "UTA".to_string(),
+                start: NaiveDate::from_ymd(2100, 3, 4).and_hms(2, 0, 0),
+            }
+            .create(&conn)?;
+            let market = NewMarket {
+                game_id: game.id,
+                kind: MarketKind::Moneyline,
+                team: None,
+                line: None,
+            }
+            .create(&conn)?;
+            let new = NewPricingPolicy {
+                market_id: market.id,
+                threshold: 1_000,
+                step: 5,
+                min_odds: Odds::american(200).unwrap(),
+                max_odds: Odds::american(-200).unwrap(),
+                enabled: true,
+            };
+            assert!(matches!(
+                new.create(&conn),
+                Err(DieselError::DatabaseError(..))
+            ));
+            Ok(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod password_tests {
+    use crate::password::*;
+
+    #[test]
+    fn password_verified_against_hash() {
+        let hashed = hash("password").unwrap();
+        assert!(hashed.starts_with("$argon2id$"));
+        assert_ne!(hashed, hash("password").unwrap());
+        assert!(verify("password", &hashed));
+        assert!(!verify("passwOrd", &hashed));
+        assert!(!needs_rehash(&hashed));
+    }
+
+    #[test]
+    fn legacy_plaintext_needs_rehash() {
+        assert!(!is_hashed("password"));
+        assert!(verify("password", "password"));
+        assert!(!verify("password1", "password"));
+        assert!(needs_rehash("password"));
+    }
+}
+
+#[cfg(test)]
+mod token_tests {
+    use super::establish_connection;
+    use crate::auth::*;
+    use crate::db::Retrievable;
+    use crate::model::token::RefreshToken;
+    use crate::model::user::{User, UserQuery};
+
+    fn user() -> (diesel::pg::PgConnection, User) {
+        let conn = establish_connection().unwrap();
+        let usr = User::query(
+            &conn,
+            &UserQuery {
+                email: "foo@bar.com",
+                username: "",
+            },
+        )
+        .map(|usrs| usrs[0].clone())
+        .unwrap();
+        (conn, usr)
+    }
+
+    #[test]
+    fn access_token_carries_user_and_role() {
+        let (_, usr) = user();
+        let token = Claims::new(&usr).encode().unwrap();
+        let claims = Claims::decode(&token).unwrap();
+        assert_eq!((claims.sub, claims.role), (usr.id, usr.role));
+        assert!(matches!(
+            Claims::decode(&format!("{}x", token)),
+            Err(TokenError::InvalidToken)
+        ));
+    }
+
+    #[test]
+    fn expired_access_token_rejected() {
+        let (_, usr) = user();
+        let token = Claims {
+            iat: 0,
+            exp: 60,
+            ..Claims::new(&usr)
+        }
+        .encode()
+        .unwrap();
+        assert!(matches!(
+            Claims::decode(&token),
+            Err(TokenError::InvalidToken)
+        ));
+    }
+
+    #[test]
+    fn refresh_token_redeemed_once() {
+        let (conn, usr) = user();
+        let pair = TokenPair::issue(&conn, &usr).unwrap();
+        let next = TokenPair::refresh(&conn, &pair.refresh_token).unwrap();
+        assert!(matches!(
+            TokenPair::refresh(&conn, &pair.refresh_token),
+            Err(TokenError::InvalidToken)
+        ));
+        let token = RefreshToken::find_active(&conn, &next.refresh_token)
+            .unwrap()
+            .unwrap();
+        token.revoke(&conn).unwrap();
+        assert!(RefreshToken::find_active(&conn, &next.refresh_token)
+            .unwrap()
+            .is_none());
+        assert!(RefreshToken::find_active(&conn, "not-a-token")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn refresh_token_redeemed_once_concurrently() {
+        let (conn, usr) = user();
+        let pair = TokenPair::issue(&conn, &usr).unwrap();
+        let redeemers: Vec<_> = (0..4)
+            .map(|_| {
+                let value = pair.refresh_token.clone();
+                std::thread::spawn(move || {
+                    let conn = establish_connection().unwrap();
+                    TokenPair::refresh(&conn, &value).is_ok()
+                })
+            })
+            .collect();
+        let redeemed = redeemers.into_iter().filter(|r| r.join().unwrap()).count();
+        assert_eq!(redeemed, 1);
+    }
+
+    #[actix_web::main]
+    #[test]
+    async fn session_cookie_not_sent_cross_site() {
+        use actix_session::Session;
+        use actix_web::http::header::SET_COOKIE;
+        use actix_web::{test, web, App, HttpResponse};
+        if std::env::var("SESSION_KEY").is_err() {
+            std::env::set_var("SESSION_KEY", "0".repeat(32));
+        }
+        let mut app = test::init_service(App::new().wrap(session_middleware()).route(
+            "/",
+            web::get().to(|session: Session| async move {
+                session.set("user_id", 1).unwrap();
+                HttpResponse::Ok().finish()
+            }),
+        ))
+        .await;
+        let res =
+            test::call_service(&mut app, test::TestRequest::get().uri("/").to_request()).await;
+        let cookie = res.headers().get(SET_COOKIE).unwrap().to_str().unwrap();
+        assert!(cookie.starts_with(SESSION_COOKIE));
+        assert!(cookie.contains("SameSite=Strict"));
+        assert!(cookie.contains("HttpOnly"));
+    }
+}
+
+#[cfg(test)]
+mod permission_tests {
+    use super::establish_connection;
+    use crate::model::permission::{Permission, RolePermission};
+    use crate::model::user::Role;
+    use diesel::Connection;
+
+    #[test]
+    fn permissions_follow_matrix() {
+        let conn = establish_connection().unwrap();
+        assert!(Permission::ManageUsers.granted(&conn, Role::Admin).unwrap());
+        assert!(Permission::PlaceBets.granted(&conn, Role::Punter).unwrap());
+        assert!(!Permission::ManageUsers
+            .granted(&conn, Role::Punter)
+            .unwrap());
+        assert!(!Permission::MoveMoney.granted(&conn, Role::Trader).unwrap());
+        // Punters can't deposit, not even into their own wallets
+        assert!(!Permission::MoveMoney.granted(&conn, Role::Punter).unwrap());
+    }
+
+    #[test]
+    fn admin_keeps_manage_users() {
+        let conn = establish_connection().unwrap();
+        conn.test_transaction::<_, diesel::result::Error, _>(|| {
+            let rows = RolePermission::set(&conn, Role::Admin, &[Permission::ViewBook])?;
+            assert_eq!(rows.len(), 2);
+            assert!(Permission::ManageUsers.granted(&conn, Role::Admin)?);
+            assert!(!Permission::SetPrices.granted(&conn, Role::Admin)?);
+
+            RolePermission::set(&conn, Role::Support, &[])?;
+            assert!(!Permission::ViewBook.granted(&conn, Role::Support)?);
+            Ok(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod api_tests {
+    use crate::form::{AuthError, ValidationError};
+    use crate::handler::api::ApiError;
+    use crate::model::bet::BetError;
+    use actix_web::error::BlockingError;
+    use actix_web::http::StatusCode;
+    use diesel::result::{DatabaseErrorKind, Error as DieselError};
+
+    #[test]
+    fn errors_mapped_to_status_and_code() {
+        let cases = vec![
+            (ApiError::from(DieselError::NotFound), 404, "NotFound"),
+            (
+                ApiError::from(DieselError::DatabaseError(
+                    DatabaseErrorKind::UniqueViolation,
+                    Box::new("duplicate key".to_string()),
+                )),
+                409,
+                "Conflict",
+            ),
+            (
+                ApiError::from(DieselError::RollbackTransaction),
+                500,
+                "InternalError",
+            ),
+            (
+                ApiError::from(BetError::InsufficientFunds),
+                422,
+                "InsufficientFunds",
+            ),
+            (
+                ApiError::from(BetError::Db(DieselError::NotFound)),
+                404,
+                "NotFound",
+            ),
+            (
+                ApiError::from(ValidationError::InvalidStake),
+                422,
+                "InvalidStake",
+            ),
+            (ApiError::from(AuthError::EmailTaken), 409, "EmailTaken"),
+            (
+                ApiError::from(BlockingError::Error(BetError::PriceChanged)),
+                422,
+                "PriceChanged",
+            ),
+            (
+                ApiError::from(BlockingError::<BetError>::Canceled),
+                500,
+                "InternalError",
+            ),
+        ];
+        for (e, status, code) in cases {
+            assert_eq!((e.status.as_u16(), e.error.as_str()), (status, code));
+        }
+    }
+
+    #[test]
+    fn database_details_hidden() {
+        let e = ApiError::from(DieselError::DatabaseError(
+            DatabaseErrorKind::UnableToSendCommand,
+            Box::new("relation \"users\" does not exist".to_string()),
+        ));
+        assert_eq!(e.status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            serde_json::to_value(&e).unwrap(),
+            serde_json::json!({"error": "InternalError", "message": "Internal server error"})
+        );
+    }
+}
+
+#[cfg(test)]
+mod openapi_tests {
+    use crate::handler::api::{self, ApiDoc};
+    use crate::handler::token;
+    use actix_web::http::{Method, StatusCode};
+    use actix_web::{test, App};
+    use std::collections::BTreeSet;
+    use utoipa::OpenApi;
+
+    /// The `(method, path)` of every handler declared in `source`, mounted under `prefix`
+    fn declared(prefix: &str, source: &str) -> Vec<(String, String)> {
+        source
+            .lines()
+            .filter_map(|line| {
+                let (method, rest) = line.trim().strip_prefix("#[")?.split_once("(\"")?;
+                let path = rest.strip_suffix("\")]")?;
+                match method {
+                    "get" | "post" | "put" | "delete" => {
+                        Some((method.to_string(), format!("{}{}", prefix, path)))
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// The `(method, path)` of every operation in the OpenAPI document
+    fn documented() -> BTreeSet<(String, String)> {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        spec["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, item)| {
+                item.as_object()
+                    .unwrap()
+                    .keys()
+                    .filter(|method| ["get", "post", "put", "delete"].contains(&method.as_str()))
+                    .map(move |method| (method.clone(), path.clone()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn spec_matches_handlers() {
+        let handlers: BTreeSet<_> = vec![
+            declared("", include_str!("handler/token.rs")),
+            declared("/api/v1", include_str!("handler/api/games.rs")),
+            declared("/api/v1", include_str!("handler/api/events.rs")),
+            declared("/api/v1", include_str!("handler/api/results.rs")),
+            declared("/api/v1", include_str!("handler/api/users.rs")),
+            declared("/api/v1", include_str!("handler/api/bets.rs")),
+            declared("/api/v1", include_str!("handler/api/teams.rs")),
+            declared("/api/v1", include_str!("handler/api/leagues.rs")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        assert_eq!(documented(), handlers);
+    }
+
+    #[test]
+    fn spec_describes_types() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let schemas = &spec["components"]["schemas"];
+        for name in &[
+            "Game",
+            "NewGame",
+            "Event",
+            "MarketRequest",
+            "PriceRequest",
+            "SignupForm",
+            "ApiError",
+        ] {
+            assert!(schemas[name].is_object(), "{} missing from spec", name);
+        }
+        assert!(schemas["User"]["properties"].get("password").is_none());
+        assert!(spec["components"]["securitySchemes"]["bearer_token"].is_object());
+        let params = &spec["paths"]["/api/v1/games"]["get"]["parameters"];
+        assert_eq!(params[0]["name"], "league");
+        assert_eq!(params[0]["in"], "query");
+    }
+
+    #[actix_web::main]
+    #[test]
+    async fn documented_operations_routed() {
+        let mut app = test::init_service(
+            App::new()
+                .service(token::post_token)
+                .service(token::refresh_token)
+                .service(token::revoke_token)
+                .service(api::scope()),
+        )
+        .await;
+        for (method, path) in documented() {
+            let req = test::TestRequest::default()
+                .method(Method::from_bytes(method.to_uppercase().as_bytes()).unwrap())
+                .uri(&path.replace("{id}", "1"))
+                .to_request();
+            let res = test::call_service(&mut app, req).await;
+            assert_ne!(res.status(), StatusCode::NOT_FOUND, "{} {}", method, path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod result_tests {
+    use super::establish_connection;
+    use crate::db::{Creatable, Deletable, Retrievable};
+    use crate::model::game_result::*;
+    use crate::model::league::League;
+    use crate::model::user::{User, UserQuery};
+    use crate::model::{Game, GameStatus, NewGame};
+    use chrono::{Duration, NaiveDate, Utc};
+
+    fn league(code: &str, draws_allowed: bool) -> League {
+        League {
+            code: code.to_string(),
+            name: code.to_string(),
+            sport: String::new(),
+            draws_allowed,
+            periods: 4,
+            active: true,
+        }
+    }
+
+    fn game(league: &League) -> Game {
+        Game {
+            id: 1,
+            league: league.code.clone(),
+            home: "CHI".to_string(),
+            away: "DET".to_string(),
+            start: NaiveDate::from_ymd(2022, 9, 11).and_hms(13, 0, 0),
+            status: GameStatus::Scheduled,
+        }
+    }
+
+    #[test]
+    fn scores_validated() {
+        let after = NaiveDate::from_ymd(2022, 9, 11).and_hms(16, 0, 0);
+        let before = NaiveDate::from_ymd(2022, 9, 11).and_hms(12, 0, 0);
+        let (nfl, nba) = (league("NFL", true), league("NBA", false));
+        assert!(validate_score(&game(&nfl), &nfl, 27, 24, after).is_ok());
+        assert!(validate_score(&game(&nfl), &nfl, 20, 20, after).is_ok());
+        assert!(matches!(
+            validate_score(&game(&nba), &nba, 101, 101, after),
+            Err(ResultError::Tie)
+        ));
+        assert!(matches!(
+            validate_score(&game(&nfl), &nfl, -3, 10, after),
+            Err(ResultError::NegativeScore)
+        ));
+        assert!(matches!(
+            validate_score(&game(&nfl), &nfl, 27, 24, before),
+            Err(ResultError::NotStarted)
+        ));
+        let postponed = Game {
+            status: GameStatus::Postponed,
+            ..game(&nfl)
+        };
+        assert!(matches!(
+            validate_score(&postponed, &nfl, 27, 24, after),
+            Err(ResultError::NotPlayed)
+        ));
+    }
+
+    #[test]
+    fn corrections_recorded() {
+        let conn = establish_connection().unwrap();
+        let usr = User::query(
+            &conn,
+            &UserQuery {
+                email: "foo@bar.com",
+                username: "",
+            },
+        )
+        .map(|usrs| usrs[0].clone())
+        .unwrap();
+        let game = NewGame {
+            league: "NBA".to_string(),
+            home: "LAL".to_string(),
+            away: "LAC".to_string(),
+            start: NaiveDate::from_ymd(2022, 10, 20).and_hms(22, 0, 0),
+        }
+        .create(&conn)
+        .unwrap();
+        let result = NewGameResult {
+            home: 103,
+            away: 97,
+            game_id: game.id,
+        }
+        .create(&conn)
+        .unwrap();
+        // A game only has one result; a second score is a correction
+        assert!(NewGameResult {
+            home: 99,
+            away: 97,
+            game_id: game.id,
+        }
+        .create(&conn)
+        .is_err());
+
+        let corrected = result.correct(&conn, 103, 99, usr.id).unwrap();
+        assert_eq!((corrected.home, corrected.away), (103, 99));
+        assert!(matches!(
+            corrected.correct(&conn, 99, 99, usr.id),
+            Err(ResultError::Tie)
+        ));
+        let corrections = corrected.corrections(&conn).unwrap();
+        assert_eq!(corrections.len(), 1);
+        assert_eq!((corrections[0].old_away, corrections[0].new_away), (97, 99));
+        assert_eq!(corrections[0].user_id, usr.id);
+        let _ = game.delete(&conn);
+    }
+
+    #[test]
+    fn future_game_not_scored() {
+        let conn = establish_connection().unwrap();
+        let game = NewGame {
+            league: "NFL".to_string(),
+            home: "KC".to_string(),
+            away: "BUF".to_string(),
+            start: Utc::now().naive_utc() + Duration::days(1),
+        }
+        .create(&conn)
+        .unwrap();
+        let res = NewGameResult {
+            home: 24,
+            away: 20,
+            game_id: game.id,
+        }
+        .create(&conn);
+        assert!(matches!(res, Err(ResultError::NotStarted)));
+        let _ = game.delete(&conn);
+    }
+}
+
+#[cfg(test)]
+mod lifecycle_tests {
+    use super::establish_connection;
+    use crate::db::{Creatable, Deletable, Retrievable, Updatable};
+    use crate::model::bet::{Bet, BetError, BetStatus, NewBet};
+    use crate::model::ledger::Account;
+    use crate::model::market::*;
+    use crate::model::user::{User, UserQuery};
+    use crate::model::{Game, GameError, GameQuery, GameStatus, NewGame};
+    use crate::odds::Odds;
+    use chrono::{Duration, NaiveDate, Utc};
+
+    #[test]
+    fn transitions_allowed() {
+        use GameStatus::*;
+        assert!(Scheduled.can_become(Live));
+        assert!(Scheduled.can_become(Postponed));
+        assert!(Live.can_become(Final));
+        assert!(Postponed.can_become(Scheduled));
+        assert!(Postponed.can_become(Cancelled));
+        assert!(!Final.can_become(Live));
+        assert!(!Cancelled.can_become(Scheduled));
+        assert!(!Postponed.can_become(Live));
+        assert!(!Live.can_become(Scheduled));
+        assert!(!Live.can_become(Live));
+    }
+
+    #[test]
+    fn postponed_game_voided() {
+        let conn = establish_connection().unwrap();
+        let usr = User::query(
+            &conn,
+            &UserQuery {
+                email: "foo@bar.com",
+                username: "",
+            },
+        )
+        .map(|usrs| usrs[0].clone())
+        .unwrap();
+        let game = NewGame {
+            league: "NFL".to_string(),
+            home: "MIA".to_string(),
+            away: "NYJ".to_string(),
+            start: NaiveDate::from_ymd(2100, 10, 9).and_hms(13, 0, 0),
+        }
+        .create(&conn)
+        .unwrap();
+        assert_eq!(game.status, GameStatus::Scheduled);
+        let vig = Odds::american(-110).unwrap();
+        let market = Market::open(
+            &conn,
+            &game,
+            &NewMarket {
+                game_id: game.id,
+                kind: MarketKind::Moneyline,
+                team: None,
+                line: None,
+            },
+            [vig, vig],
+        )
+        .unwrap();
+        // A market the trader suspended before the game was postponed
+        let held = Market::open(
+            &conn,
+            &game,
+            &NewMarket {
+                game_id: game.id,
+                kind: MarketKind::Total,
+                team: None,
+                line: Some(89),
+            },
+            [vig, vig],
+        )
+        .unwrap();
+        Market {
+            suspended: true,
+            ..held
+        }
+        .update(&conn)
+        .unwrap();
+        let selections = market.selections(&conn).unwrap();
+        Account::deposit(&conn, usr.id, 1_000).unwrap();
+        let wallet = Account::wallet(&conn, usr.id).unwrap();
+        let before = wallet.balance(&conn).unwrap();
+        let bet = NewBet::new(usr.id, &selections[0], 1_000)
+            .place(&conn)
+            .unwrap();
+
+        // Only recording a result makes a game final
+        assert!(matches!(
+            game.transition(&conn, GameStatus::Final, Some(usr.id)),
+            Err(GameError::MissingResult)
+        ));
+        let postponed = game
+            .transition(&conn, GameStatus::Postponed, Some(usr.id))
+            .unwrap();
+        assert_eq!(postponed.status, GameStatus::Postponed);
+        assert!(Market::get(&conn, market.id).unwrap().suspended);
+        let voided = Bet::get(&conn, bet.id).unwrap();
+        assert_eq!(voided.status, BetStatus::Void);
+        assert_eq!(wallet.balance(&conn).unwrap(), before);
+        let res = NewBet::new(usr.id, &selections[1], 1_000).place(&conn);
+        assert!(matches!(res, Err(BetError::MarketSuspended)));
+        assert!(matches!(
+            postponed.transition(&conn, GameStatus::Live, Some(usr.id)),
+            Err(GameError::InvalidTransition(
+                GameStatus::Postponed,
+                GameStatus::Live
+            ))
+        ));
+
+        let games = Game::query(
+            &conn,
+            &GameQuery {
+                league: Some("NFL".to_string()),
+                status: Some(GameStatus::Postponed),
+            },
+        )
+        .unwrap();
+        assert!(games.iter().any(|g| g.id == game.id));
+        let history = postponed.transitions(&conn).unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].from_status, GameStatus::Scheduled);
+        assert_eq!(history[0].to_status, GameStatus::Postponed);
+        assert_eq!(history[0].user_id, Some(usr.id));
+
+        // Rescheduling resumes the markets the postponement suspended
+        postponed
+            .transition(&conn, GameStatus::Scheduled, Some(usr.id))
+            .unwrap();
+        assert!(!Market::get(&conn, market.id).unwrap().suspended);
+        assert!(Market::get(&conn, held.id).unwrap().suspended);
+        NewBet::new(usr.id, &selections[1], 1_000)
+            .place(&conn)
+            .unwrap();
+
+        let _ = voided.delete(&conn);
+        let _ = game.delete(&conn);
+    }
+
+    #[test]
+    fn started_game_needs_marking_live() {
+        let conn = establish_connection().unwrap();
+        let usr = User::query(
+            &conn,
+            &UserQuery {
+                email: "foo@bar.com",
+                username: "",
+            },
+        )
+        .map(|usrs| usrs[0].clone())
+        .unwrap();
+        let new = NewGame {
+            league: "NFL".to_string(),
+            home: "DAL".to_string(),
+            away: "NYG".to_string(),
+            start: Utc::now().naive_utc() + Duration::days(1),
+        };
+        let game = new.create(&conn).unwrap();
+        let vig = Odds::american(-110).unwrap();
+        let market = Market::open(
+            &conn,
+            &game,
+            &NewMarket {
+                game_id: game.id,
+                kind: MarketKind::Moneyline,
+                team: None,
+                line: None,
+            },
+            [vig, vig],
+        )
+        .unwrap();
+        let selections = market.selections(&conn).unwrap();
+        Account::deposit(&conn, usr.id, 1_000).unwrap();
+
+        // Kick-off passes without anyone moving the game on
+        let started = Game {
+            start: Utc::now().naive_utc() - Duration::minutes(5),
+            ..game.clone()
+        }
+        .update(&conn)
+        .unwrap();
+        assert_eq!(started.status, GameStatus::Scheduled);
+        let res = NewBet::new(usr.id, &selections[0], 1_000).place(&conn);
+        assert!(matches!(res, Err(BetError::GameStarted)));
+        assert!(matches!(
+            started.edit(&conn, &new),
+            Err(GameError::Locked(GameStatus::Scheduled))
+        ));
+
+        // Marked live, its in-play markets take bets again
+        let live = started
+            .transition(&conn, GameStatus::Live, Some(usr.id))
+            .unwrap();
+        assert!(live.accepts_bets(Utc::now().naive_utc()));
+        let bet = NewBet::new(usr.id, &selections[0], 1_000)
+            .place(&conn)
+            .unwrap();
+
+        let _ = bet.delete(&conn);
+        let _ = game.delete(&conn);
+    }
+
+    #[test]
+    fn finished_game_not_edited() {
+        let conn = establish_connection().unwrap();
+        let new = NewGame {
+            league: "NFL".to_string(),
+            home: "BUF".to_string(),
+            away: "NE".to_string(),
+            start: NaiveDate::from_ymd(2100, 10, 16).and_hms(13, 0, 0),
+        };
+        let game = new.create(&conn).unwrap();
+        let later = NewGame {
+            start: NaiveDate::from_ymd(2100, 10, 17).and_hms(13, 0, 0),
+            ..new.clone()
+        };
+        let moved = game.edit(&conn, &later).unwrap();
+        assert_eq!(moved.start, later.start);
+
+        let cancelled = moved
+            .transition(&conn, GameStatus::Cancelled, None)
+            .unwrap();
+        assert!(matches!(
+            cancelled.edit(&conn, &new),
+            Err(GameError::Locked(GameStatus::Cancelled))
+        ));
+        assert_eq!(Game::get(&conn, game.id).unwrap().start, later.start);
+
+        let _ = game.delete(&conn);
+    }
+}
+
+#[cfg(test)]
+mod team_tests {
+    use super::establish_connection;
+    use crate::db::{Creatable, Retrievable};
+    use crate::model::team::*;
+    use crate::model::NewGame;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn team_goes_by_aliases() {
+        let team = Team {
+            league: "NBA".to_string(),
+            abbreviation: "GSW".to_string(),
+            name: "Golden State Warriors".to_string(),
+            aliases: vec!["Warriors".to_string(), "GS".to_string()],
+            active: true,
+        };
+        assert!(team.goes_by("GSW"));
+        assert!(team.goes_by("gsw"));
+        assert!(team.goes_by(" Golden State Warriors "));
+        assert!(team.goes_by("warriors"));
+        assert!(!team.goes_by("Golden State"));
+    }
+
+    #[test]
+    fn teams_looked_up_both_ways() {
+        let conn = establish_connection().unwrap();
+        let eagles = Team::lookup(&conn, "NFL", "Philadelphia Eagles").unwrap();
+        assert_eq!(eagles.abbreviation, "PHI");
+        let sixers = Team::get(&conn, "NBA", "PHI").unwrap();
+        assert_eq!(sixers.name, "Philadelphia 76ers");
+        assert_eq!(
+            Team::lookup(&conn, "NFL", "CAR").unwrap().name,
+            "Carolina Panthers"
+        );
+        assert!(Team::lookup(&conn, "NBA", "Carolina Panthers").is_err());
+
+        let nfl = Team::query(
+            &conn,
+            &TeamQuery {
+                league: Some("NFL".to_string()),
+                active: Some(true),
+            },
+        )
+        .unwrap();
+        assert_eq!(nfl.len(), 32);
+    }
+
+    #[test]
+    fn game_needs_registered_teams() {
+        let conn = establish_connection().unwrap();
+        let res = NewGame {
+            league: "NBA".to_string(),
+            home: "KC".to_string(),
+            away: "BOS".to_string(),
+            start: NaiveDate::from_ymd(2022, 11, 5).and_hms(19, 30, 0),
+        }
+        .create(&conn);
+        assert!(res.is_err());
+    }
+}
+
+#[cfg(test)]
+mod league_tests {
+    use super::establish_connection;
+    use crate::db::{Creatable, Deletable, Retrievable};
+    use crate::model::league::*;
+    use crate::model::market::{Market, MarketError, MarketKind, MarketQuery};
+    use crate::model::team::Team;
+    use crate::model::NewGame;
+    use crate::odds::Odds;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn templates_validated() {
+        let conn = establish_connection().unwrap();
+        let res = NewMarketTemplate {
+            league: "NBA".to_string(),
+            kind: MarketKind::Spread,
+            team: None,
+            line: None,
+            odds: Odds::american(-110).unwrap(),
+        }
+        .create(&conn);
+        assert!(matches!(res, Err(MarketError::MissingLine)));
+    }
+
+    #[test]
+    fn configured_league_scheduled() {
+        let conn = establish_connection().unwrap();
+        let league = League::get(&conn, "XFL")
+            .or_else(|_| {
+                League {
+                    code: "XFL".to_string(),
+                    name: "XFL".to_string(),
+                    sport: "American football".to_string(),
+                    draws_allowed: false,
+                    periods: 4,
+                    active: true,
+                }
+                .create(&conn)
+            })
+            .unwrap();
+        for (abbreviation, name) in [
+            ("STL", "St. Louis Battlehawks"),
+            ("ARL", "Arlington Renegades"),
+        ] {
+            let _ = Team {
+                league: league.code.clone(),
+                abbreviation: abbreviation.to_string(),
+                name: name.to_string(),
+                aliases: Vec::new(),
+                active: true,
+            }
+            .create(&conn);
+        }
+        if league.templates(&conn).unwrap().is_empty() {
+            NewMarketTemplate {
+                league: league.code.clone(),
+                kind: MarketKind::Total,
+                team: None,
+                line: Some(85),
+                odds: Odds::american(-110).unwrap(),
+            }
+            .create(&conn)
+            .unwrap();
+        }
+
+        let game = NewGame {
+            league: league.code.clone(),
+            home: "STL".to_string(),
+            away: "ARL".to_string(),
+            start: NaiveDate::from_ymd(2100, 3, 4).and_hms(15, 0, 0),
+        }
+        .schedule(&conn)
+        .unwrap();
+        let markets = Market::query(
+            &conn,
+            &MarketQuery {
+                game_id: Some(game.id),
+            },
+        )
+        .unwrap();
+        assert_eq!(markets.len(), 1);
+        assert_eq!(markets[0].kind, MarketKind::Total);
+        assert_eq!(markets[0].line, Some(85));
+        let _ = game.delete(&conn);
+    }
+}
+
+#[cfg(test)]
+mod import_tests {
+    use super::establish_connection;
+    use crate::model::import::*;
+    use crate::model::team::Team;
+    use crate::model::{Game, GameStatus};
+    use chrono::NaiveDate;
+
+    fn team(abbreviation: &str, name: &str, aliases: &[&str]) -> Team {
+        Team {
+            league: "NFL".to_string(),
+            abbreviation: abbreviation.to_string(),
+            name: name.to_string(),
+            aliases: aliases.iter().map(|a| a.to_string()).collect(),
+            active: true,
+        }
+    }
+
+    fn row(home: &str, away: &str, start: &str) -> ScheduleRow {
+        ScheduleRow {
+            home: home.to_string(),
+            away: away.to_string(),
+            start: start.to_string(),
+            timezone: None,
+        }
+    }
+
+    #[test]
+    fn csv_and_json_parsed() {
+        let csv = "home,away,start,timezone\n\
+                   Rams, Bills, 2022-09-08 17:20, America/Los_Angeles\n\
+                   KC,ARI,2022-09-11 16:25,\n";
+        let rows = parse(csv.as_bytes()).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].away, "Bills");
+        assert_eq!(rows[0].timezone.as_deref(), Some("America/Los_Angeles"));
+        assert_eq!(rows[1].timezone, None);
+
+        let json = r#" [{"home": "LAR", "away": "BUF", "start": "2022-09-09T00:20:00Z"}]"#;
+        let rows = parse(json.as_bytes()).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].home, "LAR");
+
+        assert!(matches!(
+            parse(b"home,away\nLAR,BUF\n"),
+            Err(ImportError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn start_converted_to_utc() {
+        // Daylight saving time in September, standard time in December
+        assert_eq!(
+            to_utc("2022-09-08 20:20", "America/New_York").unwrap(),
+            NaiveDate::from_ymd(2022, 9, 9).and_hms(0, 20, 0)
+        );
+        assert_eq!(
+            to_utc("2022-12-08T20:15", "America/New_York").unwrap(),
+            NaiveDate::from_ymd(2022, 12, 9).and_hms(1, 15, 0)
+        );
+        // An offset wins over the timezone
+        assert_eq!(
+            to_utc("2022-09-08T20:20:00-04:00", "Europe/London").unwrap(),
+            NaiveDate::from_ymd(2022, 9, 9).and_hms(0, 20, 0)
+        );
+        assert!(to_utc("2022-09-08 20:20", "Mars/Olympus_Mons").is_err());
+        assert!(to_utc("8 Sept 2022", "UTC").is_err());
+        // Clocks went forward past 02:30
+        assert!(to_utc("2022-03-13 02:30", "America/New_York").is_err());
+    }
+
+    #[test]
+    fn plan_resolves_and_dedupes() {
+        let teams = [
+            team("LAR", "Los Angeles Rams", &["Rams"]),
+            team("BUF", "Buffalo Bills", &["Bills"]),
+            team("KC", "Kansas City Chiefs", &[]),
+            team("ARI", "Arizona Cardinals", &[]),
+        ];
+        let existing = [Game {
+            id: 7,
+            league: "NFL".to_string(),
+            home: "KC".to_string(),
+            away: "ARI".to_string(),
+            start: NaiveDate::from_ymd(2022, 9, 11).and_hms(20, 25, 0),
+            status: GameStatus::Scheduled,
+        }];
+        let rows = [
+            row("Rams", "bills", "2022-09-08 20:20"),
+            row("LAR", "BUF", "2022-09-08 20:20"),
+            row("KC", "ARI", "2022-09-11 16:25"),
+            row("KC", "Jets", "2022-09-18 13:00"),
+            row("KC", "Chiefs", "2022-09-25 13:00"),
+        ];
+        let plan = ImportPlan::build("NFL", &rows, &teams, &existing, "America/New_York");
+        let actions: Vec<Action> = plan.games.iter().map(|g| g.action).collect();
+        assert_eq!(
+            actions,
+            [
+                Action::Create,
+                Action::Skip,
+                Action::Skip,
+                Action::Reject,
+                Action::Reject
+            ]
+        );
+        assert_eq!(plan.games[0].home, "LAR");
+        assert_eq!(plan.games[0].away, "BUF");
+        assert_eq!(
+            plan.games[0].start,
+            Some(NaiveDate::from_ymd(2022, 9, 9).and_hms(0, 20, 0))
+        );
+        assert_eq!(plan.games[1].reason.as_deref(), Some("duplicate of row 1"));
+        assert_eq!(
+            plan.games[2].reason.as_deref(),
+            Some("already scheduled as game 7")
+        );
+        assert_eq!((plan.created, plan.skipped, plan.rejected), (1, 2, 2));
+        assert_eq!(plan.new_games().len(), 1);
+    }
+
+    #[test]
+    fn rejected_rows_import_nothing() {
+        let conn = establish_connection().unwrap();
+        let rows = [
+            row("Philadelphia Eagles", "CAR", "2100-10-03 13:00"),
+            row("PHI", "Nowhere Nomads", "2100-10-10 13:00"),
+        ];
+        let plan = ImportPlan::new(&conn, "NFL", &rows, "America/New_York").unwrap();
+        assert_eq!((plan.created, plan.rejected), (1, 1));
+        assert!(matches!(
+            commit(&conn, "NFL", &rows, "America/New_York"),
+            Err(ImportError::Rejected(1))
+        ));
+        let plan = ImportPlan::new(&conn, "NFL", &rows[..1], "America/New_York").unwrap();
+        assert_eq!(plan.created, 1);
+    }
+}
+
+#[cfg(test)]
+mod feed_tests {
+    use super::establish_connection;
+    use crate::db::{Deletable, Retrievable};
+    use crate::feed::json::JsonFeed;
+    use crate::feed::mock::MockServer;
+    use crate::feed::odds_api::OddsApi;
+    use crate::feed::*;
+    use crate::model::market::{Line, Market, MarketQuery, Selection, TeamSide, TotalSide};
+    use crate::model::{Event, NewGame};
+    use crate::odds::Odds;
+    use chrono::NaiveDate;
+    use reqwest::Client;
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn price(selection: Selection, odds: i32) -> FeedPrice {
+        FeedPrice {
+            selection,
+            odds: Odds::american(odds).unwrap(),
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_until_capped() {
+        let mut backoff = Backoff::new(Duration::from_secs(60), Duration::from_secs(300));
+        assert_eq!(backoff.fail(), Duration::from_secs(120));
+        assert_eq!(backoff.fail(), Duration::from_secs(240));
+        assert_eq!(backoff.fail(), Duration::from_secs(300));
+        assert_eq!(backoff.fail(), Duration::from_secs(300));
+        assert_eq!(backoff.succeed(), Duration::from_secs(60));
+        assert_eq!(backoff.fail(), Duration::from_secs(120));
+    }
+
+    #[test]
+    fn json_feed_parsed() {
+        let body = json!([{
+            "id": "401",
+            "league": "NBA",
+            "start": "2022-10-18T23:30:00Z",
+            "home": { "id": "2", "name": "Boston Celtics" },
+            "away": { "id": "20", "name": "Philadelphia 76ers" },
+            "prices": [
+                { "market": "Spread", "team": "Home", "line": -2.5, "odds": -110 },
+                { "market": "Spread", "team": "Away", "line": 2.5, "odds": -110 },
+                { "market": "Total", "total": "Over", "line": 215.5, "odds": -105 },
+                { "market": "Total", "total": "Under", "odds": -115 }
+            ]
+        }]);
+        let feed = JsonFeed {
+            url: String::new(),
+            key: None,
+        };
+        let games = feed.parse(body.to_string().as_bytes()).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].home.name, "Boston Celtics");
+        assert_eq!(
+            games[0].start,
+            NaiveDate::from_ymd(2022, 10, 18).and_hms(23, 30, 0)
+        );
+        // The under has no line, so it is left out
+        assert_eq!(
+            games[0].prices,
+            [
+                price(Selection::Spread(TeamSide::Home, Line(-5)), -110),
+                price(Selection::Spread(TeamSide::Away, Line(5)), -110),
+                price(Selection::Total(TotalSide::Over, Line(431)), -105),
+            ]
+        );
+        assert!(matches!(feed.parse(b"{}"), Err(FeedError::Parse(_))));
+    }
+
+    #[test]
+    fn odds_api_parsed() {
+        let body = json!([
+            {
+                "id": "e1",
+                "sport_key": "basketball_nba",
+                "commence_time": "2022-10-18T23:30:00Z",
+                "home_team": "Boston Celtics",
+                "away_team": "Philadelphia 76ers",
+                "bookmakers": [
+                    {
+                        "key": "draftkings",
+                        "markets": [{ "key": "h2h", "outcomes": [
+                            { "name": "Boston Celtics", "price": -160 },
+                            { "name": "Philadelphia 76ers", "price": 135 }
+                        ]}]
+                    },
+                    {
+                        "key": "fanduel",
+                        "markets": [
+                            { "key": "h2h", "outcomes": [
+                                { "name": "Boston Celtics", "price": -150 },
+                                { "name": "Philadelphia 76ers", "price": 130 }
+                            ]},
+                            { "key": "totals", "outcomes": [
+                                { "name": "Over", "price": -110, "point": 215.5 },
+                                { "name": "Under", "price": -110, "point": 215.5 }
+                            ]}
+                        ]
+                    }
+                ]
+            },
+            {
+                "id": "e2",
+                "sport_key": "soccer_epl",
+                "commence_time": "2022-10-19T18:30:00Z",
+                "home_team": "Arsenal",
+                "away_team": "Chelsea",
+                "bookmakers": [{ "key": "fanduel", "markets": [{ "key": "h2h", "outcomes": [
+                    { "name": "Arsenal", "price": 120 },
+                    { "name": "Chelsea", "price": 240 },
+                    { "name": "Draw", "price": 250 }
+                ]}]}]
+            },
+            {
+                "id": "e3",
+                "sport_key": "cricket_ipl",
+                "commence_time": "2022-10-19T14:00:00Z",
+                "home_team": "Mumbai Indians",
+                "away_team": "Chennai Super Kings"
+            }
+        ]);
+        let feed = OddsApi {
+            url: String::new(),
+            key: String::new(),
+            bookmaker: Some("fanduel".to_string()),
+        };
+        let games = feed.parse(body.to_string().as_bytes()).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].league, "NBA");
+        assert_eq!(games[0].home.id, "Boston Celtics");
+        assert_eq!(
+            games[0].prices,
+            [
+                price(Selection::Moneyline(TeamSide::Home), -150),
+                price(Selection::Moneyline(TeamSide::Away), 130),
+                price(Selection::Total(TotalSide::Over, Line(431)), -110),
+                price(Selection::Total(TotalSide::Under, Line(431)), -110),
+            ]
+        );
+        assert_eq!(games[1].league, "EPL");
+        assert!(games[1].prices.is_empty());
+    }
+
+    #[actix_web::main]
+    #[test]
+    async fn mock_feed_mirrored() {
+        let conn = establish_connection().unwrap();
+        let game = NewGame {
+            league: "NBA".to_string(),
+            home: "BOS".to_string(),
+            away: "LAL".to_string(),
+            start: NaiveDate::from_ymd(2100, 5, 6).and_hms(0, 0, 0),
+        }
+        .schedule(&conn)
+        .unwrap();
+        let nfl_game = NewGame {
+            league: "NFL".to_string(),
+            home: "MIA".to_string(),
+            away: "NYJ".to_string(),
+            start: NaiveDate::from_ymd(2100, 5, 6).and_hms(0, 0, 0),
+        }
+        .schedule(&conn)
+        .unwrap();
+        let feed_game = |home_odds: i32| {
+            json!([
+                {
+                    // Longer than a provider id can be, so linking the game fails
+                    "id": "x".repeat(64),
+                    "league": "NBA",
+                    "start": "2100-05-06T00:30:00Z",
+                    "home": { "id": "2", "name": "Celtics" },
+                    "away": { "id": "13", "name": "Los Angeles Lakers" },
+                    "prices": [
+                        { "market": "Moneyline", "team": "Home", "odds": 500 },
+                        { "market": "Moneyline", "team": "Away", "odds": -900 }
+                    ]
+                },
+                {
+                    "id": format!("mock-{}", game.id),
+                    "league": "NBA",
+                    "start": "2100-05-06T00:30:00Z",
+                    "home": { "id": "2", "name": "Celtics" },
+                    "away": { "id": "13", "name": "Los Angeles Lakers" },
+                    "prices": [
+                        { "market": "Moneyline", "team": "Home", "odds": home_odds },
+                        { "market": "Moneyline", "team": "Away", "odds": 130 },
+                        { "market": "Spread", "team": "Home", "line": -3.5, "odds": -110 },
+                        { "market": "Spread", "team": "Away", "line": 3.5, "odds": -110 }
+                    ]
+                },
+                {
+                    "id": "mock-unknown",
+                    "league": "NBA",
+                    "start": "2100-05-06T00:30:00Z",
+                    "home": { "id": "x1", "name": "Nowhere Nomads" },
+                    "away": { "id": "13", "name": "Los Angeles Lakers" }
+                },
+                {
+                    // The provider reuses team ids across leagues
+                    "id": format!("mock-{}", nfl_game.id),
+                    "league": "NFL",
+                    "start": "2100-05-06T00:30:00Z",
+                    "home": { "id": "2", "name": "Miami Dolphins" },
+                    "away": { "id": "13", "name": "New York Jets" }
+                }
+            ])
+        };
+        let server = MockServer::start(feed_game(-150)).unwrap();
+        let feed = JsonFeed {
+            url: server.url(),
+            key: None,
+        };
+        let client = Client::new();
+
+        let games = feed.fetch(&client).await.unwrap();
+        let report = ingest(&conn, feed.name(), &games);
+        assert_eq!(report.matched, 2);
+        assert_eq!(report.unmatched, ["mock-unknown"]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, "x".repeat(64));
+        // The moneyline opened with the game is repriced; the spread is new
+        assert_eq!(report.repriced, 2);
+        assert_eq!(report.opened, 1);
+
+        let report = ingest(&conn, feed.name(), &games);
+        assert_eq!((report.repriced, report.opened), (0, 0));
+
+        server.set(feed_game(-170));
+        server.fail_next(1);
+        assert!(matches!(feed.fetch(&client).await, Err(FeedError::Http(_))));
+        let games = feed.fetch(&client).await.unwrap();
+        let report = ingest(&conn, feed.name(), &games);
+        assert_eq!(report.repriced, 1);
+        assert_eq!(server.requests(), 3);
+
+        let home_ml = Market::query(
+            &conn,
+            &MarketQuery {
+                game_id: Some(game.id),
+            },
+        )
+        .unwrap()
+        .into_iter()
+        .find(|m| m.line.is_none())
+        .unwrap()
+        .selections(&conn)
+        .unwrap()
+        .into_iter()
+        .find(|e| e.selection() == Some(Selection::Moneyline(TeamSide::Home)))
+        .unwrap();
+        assert_eq!(home_ml.odds.value(), -170);
+        assert_eq!(Event::history(&conn, home_ml.id).unwrap().len(), 3);
+
+        server.stop().await;
+        let _ = game.delete(&conn);
+        let _ = nfl_game.delete(&conn);
+    }
 }